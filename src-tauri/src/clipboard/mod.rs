@@ -11,4 +11,15 @@ pub fn get_from_clipboard(app_handle: &tauri::AppHandle) -> Result<String> {
     let content = app_handle.clipboard().read_text()
         .map_err(|e| anyhow::anyhow!("Failed to read from clipboard: {}", e))?;
     Ok(content)
-}
\ No newline at end of file
+}
+
+// Appends to the current clipboard text; non-text or empty clipboards start fresh
+pub fn append_to_clipboard(content: &str, separator: &str, app_handle: &tauri::AppHandle) -> Result<String> {
+    let combined = match get_from_clipboard(app_handle) {
+        Ok(existing) if !existing.is_empty() => format!("{}{}{}", existing, separator, content),
+        _ => content.to_string(),
+    };
+
+    copy_to_clipboard(&combined, app_handle)?;
+    Ok(combined)
+}
@@ -1,245 +1,921 @@
 use crate::AppState;
 use serde::{Deserialize, Serialize};
-use tauri::{AppHandle, command};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use tauri::{AppHandle, Emitter, command};
 
 use crate::clipboard;
-use crate::fs::browser::{self, FileItem};
+use crate::config::Config;
+use crate::error::CommandError;
+use crate::fs::browser::{self, FileItem, ScanOutcome};
+use crate::fs::metadata::{self, FileMetadata, SelectionSummary};
+use crate::fs::paths;
+use crate::fs::project::{self, DetectedProject};
 use crate::fs::reader;
-use crate::prompt::manager::{self, Prompt, PromptTag};
-use crate::xml::generator;
-use crate::xml::parser::{self, FileChange, ChangeResult};
+use crate::fs::sandbox;
+use crate::fs::writer::{self, BackupLocation, RecoveredFile};
+use crate::fs::search;
+use crate::fs::watcher;
+use crate::prompt::manager::{self, Prompt, PromptTag, PromptVersion, TagCount};
+use crate::prompt::pricing::{self, CostEstimate};
+use crate::prompt::search::PromptSearchResult;
+use crate::prompt::template::{self, RenderedPrompt};
+use crate::prompt::tokens::{self, TokenEstimate};
+use crate::xml::generator::{self, GenerateOptions, GeneratedPrompt, PromptMode};
+use crate::xml::json_diff;
+use crate::xml::parser::{self, ApplyOptions, ApplySummary, FileChange};
 use crate::undo;
 use crate::workspace;
 
+#[command]
+pub fn get_config(state: tauri::State<'_, AppState>) -> Config {
+    state.config.current()
+}
+
+#[command]
+pub async fn set_config(config: Config, state: tauri::State<'_, AppState>) -> Result<Config, CommandError> {
+    state.config.set(config)
+        .await
+        .map_err(CommandError::from)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DirectoryScanOptions {
     pub use_git_ignore: bool,
     pub include_patterns: Option<Vec<String>>,
     pub exclude_patterns: Option<Vec<String>>,
+    // Skip build/vendor directories such as node_modules and target (see browser::DEFAULT_SKIP_DIRS)
+    #[serde(default = "default_skip_default_noise")]
+    pub skip_default_noise: bool,
+    // Replaces the default skip list when set; applied whatever `skip_default_noise` says
+    #[serde(default)]
+    pub skip_dirs: Option<Vec<String>>,
+    // Merge chains of single-child directories (`src/main/java`) into one node
+    #[serde(default)]
+    pub collapse_single_child_dirs: bool,
+}
+
+fn default_skip_default_noise() -> bool {
+    true
+}
+
+impl DirectoryScanOptions {
+    // The directory names to leave out of the scan; `.git` is skipped regardless
+    fn skip_dirs(&self) -> Vec<String> {
+        match &self.skip_dirs {
+            Some(skip_dirs) => skip_dirs.clone(),
+            None if self.skip_default_noise => browser::default_skip_dirs(),
+            None => Vec::new(),
+        }
+    }
+}
+
+// Resolves the gitignore flag and skip list, falling back to the defaults without options
+fn scan_settings(options: Option<&DirectoryScanOptions>) -> (bool, Vec<String>) {
+    match options {
+        Some(o) => (o.use_git_ignore, o.skip_dirs()),
+        None => (true, browser::default_skip_dirs()),
+    }
+}
+
+// Payload of the `scan-skipped` event
+#[derive(Debug, Serialize, Clone)]
+pub struct ScanSkipped {
+    // The directory that was scanned
+    pub root: String,
+    // Entries below it that couldn't be read and are missing from its tree; empty when all were read
+    pub paths: Vec<String>,
+}
+
+// Emits `scan-skipped` for every scan, so an empty list clears what an earlier scan reported,
+// and returns the tree
+fn report_skipped(app_handle: &AppHandle, outcome: ScanOutcome) -> FileItem {
+    let _ = app_handle.emit("scan-skipped", ScanSkipped {
+        root: outcome.tree.path.clone(),
+        paths: outcome.skipped,
+    });
+
+    outcome.tree
 }
 
 #[command]
-pub async fn scan_directory(path: String, options: Option<DirectoryScanOptions>) -> Result<FileItem, String> {
-    let use_git_ignore = options.as_ref().map_or(true, |o| o.use_git_ignore);
+pub async fn scan_directory(app_handle: AppHandle, path: String, options: Option<DirectoryScanOptions>, state: tauri::State<'_, AppState>) -> Result<FileItem, CommandError> {
+    let (use_git_ignore, skip_dirs) = scan_settings(options.as_ref());
 
-    browser::scan_directory(&path, use_git_ignore)
+    let outcome = browser::scan_directory_outcome(&path, use_git_ignore, &skip_dirs, &state.config.limits())
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)?;
+    let mut tree = report_skipped(&app_handle, outcome);
+
+    if options.as_ref().is_some_and(|o| o.collapse_single_child_dirs) {
+        browser::collapse_single_child_dirs(&mut tree);
+    }
+
+    Ok(tree)
+}
+
+// One root per input path, in input order
+#[command]
+pub async fn scan_directories(app_handle: AppHandle, paths: Vec<String>, options: Option<DirectoryScanOptions>, state: tauri::State<'_, AppState>) -> Result<Vec<FileItem>, CommandError> {
+    let (use_git_ignore, skip_dirs) = scan_settings(options.as_ref());
+
+    let outcomes = browser::scan_directories(&paths, use_git_ignore, &skip_dirs, &state.config.limits())
+        .await
+        .map_err(CommandError::from)?;
+    let mut trees: Vec<FileItem> = outcomes.into_iter()
+        .map(|outcome| report_skipped(&app_handle, outcome))
+        .collect();
+
+    if options.as_ref().is_some_and(|o| o.collapse_single_child_dirs) {
+        for tree in trees.iter_mut() {
+            browser::collapse_single_child_dirs(tree);
+        }
+    }
+
+    Ok(trees)
 }
 
 #[command]
-pub async fn read_file_content(path: String) -> Result<String, String> {
-    reader::read_file(&path)
+pub async fn rescan_path(app_handle: AppHandle, path: String, options: Option<DirectoryScanOptions>, state: tauri::State<'_, AppState>) -> Result<FileItem, CommandError> {
+    let (use_git_ignore, skip_dirs) = scan_settings(options.as_ref());
+
+    let outcome = browser::rescan_path(&path, use_git_ignore, &skip_dirs, &state.config.limits())
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)?;
+    let mut tree = report_skipped(&app_handle, outcome);
+
+    if options.as_ref().is_some_and(|o| o.collapse_single_child_dirs) {
+        browser::collapse_single_child_dirs(&mut tree);
+    }
+
+    Ok(tree)
 }
 
+// Data files that failed to parse and were moved aside (starting empty) since the last call
 #[command]
-pub async fn get_prompts() -> Result<Vec<Prompt>, String> {
+pub fn take_recovered_files() -> Vec<RecoveredFile> {
+    writer::take_recovered_files()
+}
+
+#[command]
+pub fn filter_file_tree(tree: FileItem, extensions: Vec<String>) -> FileItem {
+    browser::filter_file_tree(&tree, &extensions)
+}
+
+// Paths of every file in the tree, plus directories when `include_directories` is set
+#[command]
+pub fn flatten_file_tree(tree: FileItem, include_directories: Option<bool>) -> Vec<String> {
+    let mut paths = Vec::new();
+    workspace::collect_file_paths(&tree, include_directories.unwrap_or(false), &mut paths);
+    paths
+}
+
+#[command]
+pub async fn read_file_content(path: String, state: tauri::State<'_, AppState>) -> Result<String, CommandError> {
+    reader::read_file(&path, &state.config.limits())
+        .await
+        .map_err(CommandError::from)
+}
+
+// Reads through symlinks only when `follow_symlinks` (default true) is set; with a workspace,
+// paths resolving outside its folders are refused
+#[command]
+pub async fn read_file_resolved(path: String, follow_symlinks: Option<bool>, workspace_id: Option<String>, state: tauri::State<'_, AppState>) -> Result<reader::ResolvedFile, CommandError> {
+    let roots = match &workspace_id {
+        Some(id) => workspace::get_workspace_roots(Some(id)).await.map_err(CommandError::from)?,
+        None => Vec::new(),
+    };
+
+    reader::read_file_resolved(&path, follow_symlinks.unwrap_or(true), &roots, &state.config.limits())
+        .await
+        .map_err(CommandError::from)
+}
+
+// Display-only read; `max_line_length` defaults to 10,000 characters
+#[command]
+pub async fn read_file_safe(path: String, max_line_length: Option<usize>, mode: Option<reader::LongLineMode>, state: tauri::State<'_, AppState>) -> Result<reader::DisplayContent, CommandError> {
+    reader::read_file_for_display(&path, max_line_length.unwrap_or(10_000), mode.unwrap_or_default(), &state.config.limits())
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn read_file_with_encoding(path: String, encoding: String, state: tauri::State<'_, AppState>) -> Result<String, CommandError> {
+    reader::read_file_with_encoding(&path, &encoding, &state.config.limits())
+        .await
+        .map_err(CommandError::from)
+}
+
+// The start of a file, bounded by `max_bytes` (default 64 KB) and optionally `max_lines`
+#[command]
+pub async fn read_file_head(path: String, max_bytes: Option<usize>, max_lines: Option<usize>, state: tauri::State<'_, AppState>) -> Result<reader::FilePreview, CommandError> {
+    reader::read_file_head(&path, max_bytes.unwrap_or(64 * 1024), max_lines, &state.config.limits())
+        .await
+        .map_err(CommandError::from)
+}
+
+// The end of a file, bounded like read_file_head
+#[command]
+pub async fn read_file_tail(path: String, max_bytes: Option<usize>, max_lines: Option<usize>, state: tauri::State<'_, AppState>) -> Result<reader::FilePreview, CommandError> {
+    reader::read_file_tail(&path, max_bytes.unwrap_or(64 * 1024), max_lines, &state.config.limits())
+        .await
+        .map_err(CommandError::from)
+}
+
+// `max_bytes` defaults to the configured preview_max_bytes
+#[command]
+pub async fn read_file_base64(path: String, max_bytes: Option<u64>, state: tauri::State<'_, AppState>) -> Result<reader::Base64File, CommandError> {
+    reader::read_file_base64(&path, max_bytes.unwrap_or_else(|| state.config.current().preview_max_bytes), &state.config.limits())
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn selection_summary(paths: Vec<String>) -> Result<SelectionSummary, CommandError> {
+    metadata::selection_summary(&paths)
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn file_metadata(path: String) -> Result<FileMetadata, CommandError> {
+    metadata::file_metadata(&path)
+        .await
+        .map_err(CommandError::from)
+}
+
+// Project types found at the root, each with suggested exclude patterns
+#[command]
+pub async fn detect_project_type(path: String) -> Result<Vec<DetectedProject>, CommandError> {
+    project::detect_project_type(&path)
+        .map_err(CommandError::from)
+}
+
+// Deepest directory shared by the paths, or None when they have no common root
+#[command]
+pub fn common_root(paths: Vec<String>) -> Option<String> {
+    paths::common_root(&paths).map(|root| root.to_string_lossy().to_string())
+}
+
+#[command]
+pub async fn get_prompts() -> Result<Vec<Prompt>, CommandError> {
     manager::list_prompts()
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn list_prompts_for_workspace(workspace_id: String) -> Result<Vec<Prompt>, CommandError> {
+    manager::list_prompts_for_workspace(&workspace_id)
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn save_prompt(title: String, content: String, tags: Vec<PromptTag>, workspace_id: Option<String>) -> Result<Prompt, CommandError> {
+    manager::add_prompt(&title, &content, tags, workspace_id.as_deref())
+        .await
+        .map_err(CommandError::from)
+}
+
+// `clear_workspace` makes a scoped prompt global again
+#[command]
+pub async fn update_prompt(id: String, title: Option<String>, content: Option<String>, tags: Option<Vec<PromptTag>>, workspace_id: Option<String>, clear_workspace: Option<bool>) -> Result<Prompt, CommandError> {
+    let workspace_id = if clear_workspace.unwrap_or(false) {
+        Some(None)
+    } else {
+        workspace_id.as_deref().map(Some)
+    };
+
+    manager::update_prompt(&id, title.as_deref(), content.as_deref(), tags, workspace_id)
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn set_prompt_tags(id: String, tags: Vec<PromptTag>) -> Result<Prompt, CommandError> {
+    manager::set_prompt_tags(&id, tags)
+        .await
+        .map_err(CommandError::from)
+}
+
+// Both return how many prompts changed
+#[command]
+pub async fn add_tag_to_prompts(tag: PromptTag, ids: Vec<String>) -> Result<usize, CommandError> {
+    manager::add_tag_to_prompts(tag, &ids)
+        .await
+        .map_err(CommandError::from)
 }
 
 #[command]
-pub async fn save_prompt(title: String, content: String, tags: Vec<PromptTag>) -> Result<Prompt, String> {
-    manager::add_prompt(&title, &content, tags)
+pub async fn remove_tag_from_prompts(tag_id: String, ids: Vec<String>) -> Result<usize, CommandError> {
+    manager::remove_tag_from_prompts(&tag_id, &ids)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
 }
 
 #[command]
-pub async fn delete_prompt(id: String) -> Result<(), String> {
+pub async fn delete_prompt(id: String) -> Result<(), CommandError> {
     manager::delete_prompt(&id)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
 }
 
+// Returns how many of the ids matched a prompt
 #[command]
-pub async fn generate_copy_content(files: Vec<String>, prompts: Vec<String>) -> Result<String, String> {
-    let mut content = String::new();
+pub async fn delete_prompts(ids: Vec<String>) -> Result<usize, CommandError> {
+    manager::delete_prompts(&ids)
+        .await
+        .map_err(CommandError::from)
+}
 
-    // Add file contents with clear headers
-    for file_path in &files {
-        let file_content = reader::read_file(file_path)
-            .await
-            .map_err(|e| e.to_string())?;
+#[command]
+pub async fn record_prompt_used(id: String) -> Result<Prompt, CommandError> {
+    manager::record_prompt_used(&id)
+        .await
+        .map_err(CommandError::from)
+}
 
-        content.push_str(&format!("File: {}\n```\n{}\n```\n\n", file_path, file_content));
-    }
+// At most `max_snippets` (default 3) excerpts of about `snippet_chars` (default 120) characters per prompt
+#[command]
+pub async fn search_prompts_with_snippets(query: String, max_snippets: Option<usize>, snippet_chars: Option<usize>) -> Result<Vec<PromptSearchResult>, CommandError> {
+    crate::prompt::search::search_prompts_with_snippets(&query, max_snippets.unwrap_or(3), snippet_chars.unwrap_or(120))
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn list_prompts_by_usage() -> Result<Vec<Prompt>, CommandError> {
+    manager::list_prompts_by_usage()
+        .await
+        .map_err(CommandError::from)
+}
 
-    // Add prompts
-    if !prompts.is_empty() {
-        content.push_str("===== Prompts =====\n\n");
+#[command]
+pub async fn list_tags_with_counts() -> Result<Vec<TagCount>, CommandError> {
+    manager::list_tags_with_counts()
+        .await
+        .map_err(CommandError::from)
+}
 
-        for prompt in &prompts {
-            content.push_str(&format!("{}\n\n", prompt));
-        }
-    }
+#[command]
+pub async fn get_prompt_history(id: String) -> Result<Vec<PromptVersion>, CommandError> {
+    manager::get_prompt_history(&id)
+        .await
+        .map_err(CommandError::from)
+}
+
+// `from` and `to` index into get_prompt_history's list
+#[command]
+pub async fn diff_prompt_versions(id: String, from: usize, to: usize, context_lines: Option<usize>) -> Result<String, CommandError> {
+    manager::diff_prompt_versions(&id, from, to, context_lines.unwrap_or(3))
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn render_prompt(id: String, variables: HashMap<String, String>) -> Result<RenderedPrompt, CommandError> {
+    let prompt = manager::get_prompt(&id)
+        .await
+        .map_err(CommandError::from)?;
+
+    template::render_template(&prompt.content, &variables)
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn list_prompt_variables(id: String) -> Result<Vec<String>, CommandError> {
+    let prompt = manager::get_prompt(&id)
+        .await
+        .map_err(CommandError::from)?;
+
+    template::extract_variables(&prompt.content)
+        .map_err(CommandError::from)
+}
+
+// `overrides` maps model id prefixes to USD per million input tokens
+#[command]
+pub fn estimate_cost(tokens: u64, model: String, overrides: Option<HashMap<String, f64>>) -> CostEstimate {
+    pricing::estimate_cost(&model, tokens, &overrides.unwrap_or_default())
+}
+
+// Emits a `token-progress` event (TokenProgress) per file and returns the final total
+#[command]
+pub async fn estimate_tokens_streaming(app_handle: AppHandle, paths: Vec<String>, state: tauri::State<'_, AppState>) -> Result<TokenEstimate, CommandError> {
+    state.token_estimate_cancelled.store(false, Ordering::Relaxed);
+
+    tokens::estimate_tokens_streaming(&paths, &state.token_estimate_cancelled, |progress| {
+        let _ = app_handle.emit("token-progress", progress);
+    })
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub fn cancel_token_estimate(state: tauri::State<'_, AppState>) {
+    state.token_estimate_cancelled.store(true, Ordering::Relaxed);
+}
+
+// Single entry point for both output modes; mode defaults to Copy
+#[command]
+pub async fn generate_prompt(files: Vec<String>, prompt: String, mode: Option<PromptMode>, options: Option<GenerateOptions>, state: tauri::State<'_, AppState>) -> Result<GeneratedPrompt, CommandError> {
+    generator::generate_prompt(&files, &prompt, mode.unwrap_or_default(), &options.unwrap_or_default(), &state.config.limits())
+        .await
+        .map_err(CommandError::from)
+}
 
-    Ok(content)
+#[command]
+pub async fn generate_copy_content(files: Vec<String>, prompts: Vec<String>, options: Option<GenerateOptions>, state: tauri::State<'_, AppState>) -> Result<GeneratedPrompt, CommandError> {
+    generator::generate_copy_content(&files, &prompts, &options.unwrap_or_default(), &state.config.limits())
+        .await
+        .map_err(CommandError::from)
 }
 
 #[command]
-pub fn copy_to_clipboard(app_handle: AppHandle, content: String) -> Result<(), String> {
+pub fn copy_to_clipboard(app_handle: AppHandle, content: String) -> Result<(), CommandError> {
     clipboard::copy_to_clipboard(&content, &app_handle)
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
 }
 
+// Returns the full clipboard text after appending
 #[command]
-pub async fn generate_xml_prompt(files: Vec<String>, prompt: String) -> Result<String, String> {
-    generator::generate_xml_prompt(&files, &prompt)
+pub fn append_to_clipboard(app_handle: AppHandle, content: String, separator: Option<String>) -> Result<String, CommandError> {
+    let separator = separator.unwrap_or_else(|| "\n\n".to_string());
+
+    clipboard::append_to_clipboard(&content, &separator, &app_handle)
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn generate_xml_prompt(files: Vec<String>, prompt: String, options: Option<GenerateOptions>, state: tauri::State<'_, AppState>) -> Result<GeneratedPrompt, CommandError> {
+    generator::generate_xml_prompt(&files, &prompt, &options.unwrap_or_default(), &state.config.limits())
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
 }
 
+// The XML prompt with instructions to answer in the JSON change format
 #[command]
-pub async fn generate_xml_prompt_for_workspace(workspace_id: String, prompt: String, use_git_ignore: bool) -> Result<String, String> {
-    generator::generate_xml_prompt_for_workspace(&workspace_id, &prompt, use_git_ignore)
+pub async fn generate_json_prompt(files: Vec<String>, prompt: String, options: Option<GenerateOptions>, state: tauri::State<'_, AppState>) -> Result<GeneratedPrompt, CommandError> {
+    generator::generate_json_prompt(&files, &prompt, &options.unwrap_or_default(), &state.config.limits())
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
 }
 
+// Only the file map of the XML prompt, for checking a selection before generating
+#[command]
+pub async fn preview_file_map(files: Vec<String>, options: Option<GenerateOptions>) -> Result<String, CommandError> {
+    generator::preview_file_map(&files, &options.unwrap_or_default())
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn generate_xml_prompt_to_file(files: Vec<String>, prompt: String, output_path: String, options: Option<GenerateOptions>, state: tauri::State<'_, AppState>) -> Result<generator::GeneratedFile, CommandError> {
+    generator::generate_xml_prompt_to_file(&files, &prompt, &output_path, &options.unwrap_or_default(), &state.config.limits())
+        .await
+        .map_err(CommandError::from)
+}
 
 #[command]
-pub async fn parse_xml_response(xml: String) -> Result<Vec<FileChange>, String> {
+pub async fn save_prompt_output(content: String, path: String) -> Result<generator::GeneratedFile, CommandError> {
+    generator::save_prompt_output(&content, &path)
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn generate_xml_prompt_for_workspace(workspace_id: String, prompt: String, use_git_ignore: bool, options: Option<GenerateOptions>, state: tauri::State<'_, AppState>) -> Result<GeneratedPrompt, CommandError> {
+    generator::generate_xml_prompt_for_workspace(&workspace_id, &prompt, use_git_ignore, &options.unwrap_or_default(), &state.config.limits())
+        .await
+        .map_err(CommandError::from)
+}
+
+
+#[command]
+pub async fn parse_xml_response(xml: String) -> Result<Vec<FileChange>, CommandError> {
     parser::parse_xml_diff(&xml)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
 }
 
 #[command]
-pub async fn apply_xml_changes(changes: Vec<FileChange>) -> Result<Vec<ChangeResult>, String> {
+pub fn parse_json_response(json: String) -> Result<Vec<FileChange>, CommandError> {
+    json_diff::parse_json_diff(&json)
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn validate_response_format(xml: String) -> Result<parser::ResponseValidation, CommandError> {
+    Ok(parser::validate_response_format(&xml).await)
+}
+
+// Pass the options the change will be applied with, so the diff shows what will be written
+#[command]
+pub async fn diff_file_change(change: FileChange, context_lines: Option<usize>, options: Option<ApplyOptions>, state: tauri::State<'_, AppState>) -> Result<String, CommandError> {
+    parser::diff_file_change(&change, context_lines.unwrap_or(3), &options.unwrap_or_default(), &state.config.limits())
+        .await
+        .map_err(CommandError::from)
+}
+
+// `context_lines` defaults to 3
+#[command]
+pub async fn preview_modify_context(change: FileChange, context_lines: Option<usize>, state: tauri::State<'_, AppState>) -> Result<Vec<parser::ChangePreview>, CommandError> {
+    parser::preview_modify_context(&change, context_lines.unwrap_or(3), &state.config.limits())
+        .await
+        .map_err(CommandError::from)
+}
+
+// `description` labels the undo change set; by default it summarizes the actions and files
+#[command]
+pub async fn apply_xml_changes(mut changes: Vec<FileChange>, workspace_id: Option<String>, options: Option<ApplyOptions>, description: Option<String>, state: tauri::State<'_, AppState>) -> Result<ApplySummary, CommandError> {
+    // All writes and deletes are sandboxed to the workspace folders
+    let allowed_roots = workspace::get_workspace_roots(workspace_id.as_deref())
+        .await
+        .map_err(CommandError::from)?;
+
+    // Prompts generated with `relative_paths` show folder-relative paths; files are written by absolute path
+    if let Some(workspace_id) = workspace_id.as_deref() {
+        let folders = workspace::get_workspace(workspace_id)
+            .await
+            .map_err(CommandError::from)?
+            .folders;
+
+        for change in changes.iter_mut() {
+            change.path = workspace::resolve_folder_relative_path(&folders, &change.path);
+        }
+    }
+
+    let options = options.unwrap_or_default();
+
+    let description = description
+        .filter(|d| !d.trim().is_empty())
+        .unwrap_or_else(|| parser::summarize_changes(&changes));
+
     // Create a change set for undo
-    let mut change_set = undo::create_change_set("Applied XML changes")
+    let mut change_set = undo::create_change_set(&description)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(CommandError::from)?;
 
-    // Backup files before changing them
+    // Backup files before changing them (paths outside the sandbox are rejected during apply)
     for file_change in &changes {
+        let Some(root) = sandbox::containing_root(&file_change.path, &allowed_roots) else {
+            continue;
+        };
+
+        let project_root = match options.backup_location {
+            BackupLocation::Project => Some(root.as_path()),
+            BackupLocation::Global => None,
+        };
+
         if file_change.action != parser::ChangeAction::Create {
-            undo::add_to_change_set(&mut change_set, &file_change.path)
+            undo::add_to_change_set(&mut change_set, &file_change.path, project_root)
                 .await
-                .map_err(|e| e.to_string())?;
+                .map_err(CommandError::from)?;
         }
     }
 
     // Apply changes
-    let results = parser::apply_changes(&changes)
+    let results = parser::apply_changes(&changes, &allowed_roots, &options, &state.config.limits())
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(CommandError::from)?;
 
     // Save change set for undo
     undo::save_change_set(&change_set)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(CommandError::from)?;
+
+    Ok(ApplySummary::from_results(results))
+}
 
-    Ok(results)
+#[command]
+pub async fn preview_undo_last_change() -> Result<Option<undo::UndoPreview>, CommandError> {
+    undo::preview_undo_last_change()
+        .await
+        .map_err(CommandError::from)
 }
 
 #[command]
-pub async fn undo_last_change() -> Result<Option<String>, String> {
+pub async fn undo_last_change() -> Result<Option<String>, CommandError> {
     undo::undo_last_change()
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
 }
 
 // Add selective undo command
 #[command]
-pub async fn undo_file_change(file_path: String) -> Result<bool, String> {
+pub async fn undo_file_change(file_path: String) -> Result<bool, CommandError> {
     crate::undo::undo_file_change(&file_path)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn list_change_sets() -> Result<Vec<undo::ChangeSet>, CommandError> {
+    undo::list_change_sets()
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn verify_undo_integrity() -> Result<Vec<undo::ChangeSetIntegrity>, CommandError> {
+    undo::verify_undo_integrity()
+        .await
+        .map_err(CommandError::from)
+}
+
+// Round-trips a scratch file through write, backup, change set and undo to check the environment
+#[command]
+pub async fn run_self_test() -> Result<undo::SelfTestReport, CommandError> {
+    undo::run_self_test()
+        .await
+        .map_err(CommandError::from)
+}
+
+// Bundles the undo history and its backups into one .tar.gz
+#[command]
+pub async fn export_undo_archive(output_path: String) -> Result<undo::ExportedUndoArchive, CommandError> {
+    undo::export_undo_archive(&output_path)
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn import_undo_archive(archive_path: String) -> Result<undo::ImportedUndoArchive, CommandError> {
+    undo::import_undo_archive(&archive_path)
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn restore_file_from_change_set(file_path: String, change_set_id: String) -> Result<(), CommandError> {
+    undo::restore_file_from_change_set(&file_path, &change_set_id)
+        .await
+        .map_err(CommandError::from)
 }
 
 // Add workspace commands
 #[command]
-pub async fn list_workspaces() -> Result<Vec<workspace::Workspace>, String> {
+pub async fn list_workspaces() -> Result<Vec<workspace::Workspace>, CommandError> {
     workspace::list_workspaces()
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
 }
 
 #[command]
-pub async fn create_workspace(name: String) -> Result<workspace::Workspace, String> {
+pub async fn create_workspace(name: String) -> Result<workspace::Workspace, CommandError> {
     workspace::create_workspace(&name)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn create_workspace_from_path(path: String) -> Result<workspace::Workspace, CommandError> {
+    workspace::create_workspace_from_path(&path)
+        .await
+        .map_err(CommandError::from)
 }
 
 #[command]
-pub async fn update_workspace(id: String, name: String) -> Result<workspace::Workspace, String> {
+pub async fn update_workspace(id: String, name: String) -> Result<workspace::Workspace, CommandError> {
     workspace::update_workspace(&id, &name)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
 }
 
 #[command]
-pub async fn delete_workspace(id: String) -> Result<(), String> {
+pub async fn delete_workspace(id: String) -> Result<(), CommandError> {
     workspace::delete_workspace(&id)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
 }
 
 #[command]
-pub async fn get_workspace(id: String) -> Result<workspace::Workspace, String> {
+pub async fn get_workspace(id: String) -> Result<workspace::Workspace, CommandError> {
     workspace::get_workspace(&id)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn touch_workspace(id: String) -> Result<workspace::Workspace, CommandError> {
+    workspace::touch_workspace(&id)
+        .await
+        .map_err(CommandError::from)
 }
 
 #[command]
-pub async fn add_folder_to_workspace(workspace_id: String, path: String, name: Option<String>) -> Result<workspace::WorkspaceFolder, String> {
+pub async fn reset_workspace_state(id: String) -> Result<workspace::Workspace, CommandError> {
+    workspace::reset_workspace_state(&id)
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn list_recent_workspaces(limit: Option<usize>) -> Result<Vec<workspace::Workspace>, CommandError> {
+    workspace::list_recent_workspaces(limit.unwrap_or(10))
+        .await
+        .map_err(CommandError::from)
+}
+
+// An empty or missing language removes the override
+#[command]
+pub async fn set_language_override(workspace_id: String, path: String, language: Option<String>) -> Result<workspace::Workspace, CommandError> {
+    workspace::set_language_override(&workspace_id, &path, language.as_deref())
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn pin_file(workspace_id: String, path: String) -> Result<Vec<String>, CommandError> {
+    workspace::pin_file(&workspace_id, &path)
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn unpin_file(workspace_id: String, path: String) -> Result<Vec<String>, CommandError> {
+    workspace::unpin_file(&workspace_id, &path)
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn list_pinned_files(workspace_id: String) -> Result<workspace::PinnedFiles, CommandError> {
+    workspace::list_pinned_files(&workspace_id)
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn add_folder_to_workspace(workspace_id: String, path: String, name: Option<String>) -> Result<workspace::WorkspaceFolder, CommandError> {
     workspace::add_folder_to_workspace(&workspace_id, &path, name.as_deref())
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
 }
 
 #[command]
-pub async fn remove_folder_from_workspace(workspace_id: String, folder_id: String) -> Result<(), String> {
+pub async fn remove_folder_from_workspace(workspace_id: String, folder_id: String) -> Result<(), CommandError> {
     workspace::remove_folder_from_workspace(&workspace_id, &folder_id)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn move_folder_between_workspaces(source_workspace_id: String, target_workspace_id: String, folder_id: String) -> Result<workspace::WorkspaceFolder, CommandError> {
+    workspace::move_folder_between_workspaces(&source_workspace_id, &target_workspace_id, &folder_id)
+        .await
+        .map_err(CommandError::from)
+}
+
+// `auto_sort` also keeps the folders sorted from now on (true) or stops doing so (false)
+#[command]
+pub async fn sort_workspace_folders(workspace_id: String, auto_sort: Option<bool>) -> Result<workspace::Workspace, CommandError> {
+    workspace::sort_workspace_folders(&workspace_id, auto_sort)
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn validate_workspace(workspace_id: String) -> Result<workspace::WorkspaceValidation, CommandError> {
+    workspace::validate_workspace(&workspace_id)
+        .await
+        .map_err(CommandError::from)
+}
+
+// Callers should confirm with the user first; this removes folders from the workspace
+#[command]
+pub async fn prune_invalid_folders(workspace_id: String) -> Result<Vec<workspace::WorkspaceFolder>, CommandError> {
+    workspace::prune_invalid_folders(&workspace_id)
+        .await
+        .map_err(CommandError::from)
 }
 
 #[command]
-pub async fn update_folder(workspace_id: String, folder_id: String, name: String) -> Result<workspace::WorkspaceFolder, String> {
-    workspace::update_folder(&workspace_id, &folder_id, &name)
+pub async fn update_folder(workspace_id: String, folder_id: String, name: Option<String>, use_git_ignore: Option<bool>, encoding: Option<String>) -> Result<workspace::WorkspaceFolder, CommandError> {
+    workspace::update_folder(&workspace_id, &folder_id, name.as_deref(), use_git_ignore, encoding.as_deref())
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
 }
 
 #[command]
-pub async fn get_all_files_in_workspace(workspace_id: String, use_git_ignore: bool) -> Result<Vec<String>, String> {
-    workspace::get_all_files_in_workspace(&workspace_id, use_git_ignore)
+pub async fn get_all_files_in_workspace(workspace_id: String, use_git_ignore: bool, state: tauri::State<'_, AppState>) -> Result<Vec<String>, CommandError> {
+    workspace::get_all_files_in_workspace(&workspace_id, use_git_ignore, &state.config.limits())
         .await
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
 }
 
 #[command]
-pub fn start_watching_filesystem(app_handle: AppHandle, state: tauri::State<'_, AppState>) -> Result<(), String> {
+pub async fn largest_files_in_workspace(workspace_id: String, limit: Option<usize>, use_git_ignore: Option<bool>, state: tauri::State<'_, AppState>) -> Result<workspace::LargestFiles, CommandError> {
+    workspace::largest_files_in_workspace(&workspace_id, limit.unwrap_or(10), use_git_ignore.unwrap_or(true), &state.config.limits())
+        .await
+        .map_err(CommandError::from)
+}
+
+// `since` is in seconds since the Unix epoch
+#[command]
+pub async fn files_changed_since(workspace_id: String, since: i64, use_git_ignore: Option<bool>, state: tauri::State<'_, AppState>) -> Result<Vec<workspace::ChangedFile>, CommandError> {
+    workspace::files_changed_since(&workspace_id, since, use_git_ignore.unwrap_or(true), &state.config.limits())
+        .await
+        .map_err(CommandError::from)
+}
+
+// Follows imports of the selected files up to `depth` levels (default 1), staying inside the workspace
+#[command]
+pub async fn expand_selection_with_imports(workspace_id: String, paths: Vec<String>, depth: Option<usize>, state: tauri::State<'_, AppState>) -> Result<workspace::imports::ExpandedSelection, CommandError> {
+    let roots = workspace::get_workspace_roots(Some(&workspace_id))
+        .await
+        .map_err(CommandError::from)?;
+
+    workspace::imports::expand_selection_with_imports(&paths, &roots, depth.unwrap_or(1), &state.config.limits())
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn select_files_by_glob(workspace_id: String, patterns: Vec<String>, use_git_ignore: bool, state: tauri::State<'_, AppState>) -> Result<Vec<String>, CommandError> {
+    workspace::select_files_by_glob(&workspace_id, &patterns, use_git_ignore, &state.config.limits())
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub async fn search_in_files(workspace_id: String, query: String, is_regex: Option<bool>, use_git_ignore: Option<bool>, max_results: Option<usize>, state: tauri::State<'_, AppState>) -> Result<Vec<search::SearchMatch>, CommandError> {
+    let limits = state.config.limits();
+    let files = workspace::get_all_files_in_workspace(&workspace_id, use_git_ignore.unwrap_or(true), &limits)
+        .await
+        .map_err(CommandError::from)?;
+
+    search::search_in_files(&files, &query, is_regex.unwrap_or(false), max_results.unwrap_or(1000), &limits)
+        .await
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub fn start_watching_filesystem(app_handle: AppHandle, state: tauri::State<'_, AppState>) -> Result<(), CommandError> {
     let mut fs_watcher = state.fs_watcher.lock().unwrap();
     fs_watcher.start(app_handle)
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
 }
 
 #[command]
-pub fn watch_path(path: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+pub fn watch_path(path: String, state: tauri::State<'_, AppState>) -> Result<(), CommandError> {
     let mut fs_watcher = state.fs_watcher.lock().unwrap();
     fs_watcher.add_path(&path)
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
 }
 
 #[command]
-pub fn unwatch_path(path: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+pub fn unwatch_path(path: String, state: tauri::State<'_, AppState>) -> Result<(), CommandError> {
     let mut fs_watcher = state.fs_watcher.lock().unwrap();
     fs_watcher.remove_path(&path)
-        .map_err(|e| e.to_string())
+        .map_err(CommandError::from)
+}
+
+#[command]
+pub fn list_watched_paths(state: tauri::State<'_, AppState>) -> Result<Vec<String>, CommandError> {
+    let fs_watcher = state.fs_watcher.lock().unwrap();
+    Ok(fs_watcher.list_paths())
+}
+
+// Files in the current prompt; a `prompt-stale` event fires when a watched change touches one
+#[command]
+pub async fn set_prompt_selection(paths: Vec<String>, state: tauri::State<'_, AppState>) -> Result<(), CommandError> {
+    // Hash before locking so reading the files doesn't hold up the watcher
+    let snapshot = watcher::snapshot_selection(paths)
+        .await
+        .map_err(CommandError::from)?;
+
+    state.fs_watcher.lock().unwrap().set_selection(snapshot);
+    Ok(())
 }
 
 #[command]
-pub fn stop_watching_filesystem(state: tauri::State<'_, AppState>) -> Result<(), String> {
+pub fn stop_watching_filesystem(state: tauri::State<'_, AppState>) -> Result<(), CommandError> {
     let mut fs_watcher = state.fs_watcher.lock().unwrap();
     fs_watcher.stop();
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan_options(skip_default_noise: bool, skip_dirs: Option<&[&str]>) -> DirectoryScanOptions {
+        DirectoryScanOptions {
+            use_git_ignore: true,
+            include_patterns: None,
+            exclude_patterns: None,
+            skip_default_noise,
+            skip_dirs: skip_dirs.map(|dirs| dirs.iter().map(|d| d.to_string()).collect()),
+            collapse_single_child_dirs: false,
+        }
+    }
+
+    #[test]
+    fn custom_skip_dirs_apply_without_default_noise() {
+        assert_eq!(scan_options(false, Some(&["fixtures"])).skip_dirs(), ["fixtures"]);
+        assert_eq!(scan_options(true, Some(&["fixtures"])).skip_dirs(), ["fixtures"]);
+        assert_eq!(scan_options(true, None).skip_dirs(), browser::default_skip_dirs());
+        assert!(scan_options(false, None).skip_dirs().is_empty());
+    }
+}
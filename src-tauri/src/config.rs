@@ -0,0 +1,162 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::error;
+
+/// Backend limits, tunable per machine and persisted in the app data dir
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    // Concurrent file operations (directory scans, walker threads); keep it low on systems with a small ulimit
+    pub max_concurrency: usize,
+    // Files larger than this are memory-mapped instead of read into a buffer
+    pub mmap_threshold_bytes: u64,
+    // Default size cap for read_file_base64 previews
+    pub preview_max_bytes: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            max_concurrency: 8,
+            mmap_threshold_bytes: 1_048_576,
+            preview_max_bytes: 10 * 1024 * 1024,
+        }
+    }
+}
+
+/// The active config and the limiter sized from it; kept in AppState
+pub struct ConfigState {
+    config: RwLock<Config>,
+    // Shared by every concurrent operation; sized to `max_concurrency`
+    io_permits: Arc<Semaphore>,
+}
+
+/// What file operations need from the config: the settings as they were when the command
+/// started, plus the shared concurrency limiter
+#[derive(Debug, Clone)]
+pub struct IoLimits {
+    pub config: Config,
+    permits: Arc<Semaphore>,
+}
+
+impl IoLimits {
+    /// Waits for a slot under `max_concurrency`; hold the permit for the duration of the operation
+    pub async fn acquire_io_permit(&self) -> Result<OwnedSemaphorePermit> {
+        Arc::clone(&self.permits)
+            .acquire_owned()
+            .await
+            .context("Concurrency limiter closed")
+    }
+}
+
+// The default config with a limiter of its own, for callers that don't go through AppState
+impl Default for IoLimits {
+    fn default() -> Self {
+        let config = Config::default();
+
+        IoLimits {
+            permits: Arc::new(Semaphore::new(config.max_concurrency)),
+            config,
+        }
+    }
+}
+
+impl ConfigState {
+    /// Loads the persisted config, falling back to the defaults when there is none
+    pub fn load() -> Self {
+        let config = load_config().unwrap_or_default();
+
+        ConfigState {
+            io_permits: Arc::new(Semaphore::new(config.max_concurrency)),
+            config: RwLock::new(config),
+        }
+    }
+
+    /// The active config
+    pub fn current(&self) -> Config {
+        self.config.read().unwrap().clone()
+    }
+
+    /// The active config together with the shared limiter
+    pub fn limits(&self) -> IoLimits {
+        IoLimits {
+            config: self.current(),
+            permits: Arc::clone(&self.io_permits),
+        }
+    }
+
+    /// Validates, applies and persists a new config
+    pub async fn set(&self, config: Config) -> Result<Config> {
+        if config.max_concurrency == 0 {
+            return Err(error::invalid_input("max_concurrency must be at least 1"));
+        }
+
+        let previous = std::mem::replace(&mut *self.config.write().unwrap(), config.clone());
+
+        if config.max_concurrency > previous.max_concurrency {
+            self.io_permits.add_permits(config.max_concurrency - previous.max_concurrency);
+        } else if config.max_concurrency < previous.max_concurrency {
+            // Permits in use are only retired once they're released
+            let excess = (previous.max_concurrency - config.max_concurrency) as u32;
+            let permits = Arc::clone(&self.io_permits);
+
+            tokio::spawn(async move {
+                if let Ok(retired) = permits.acquire_many_owned(excess).await {
+                    retired.forget();
+                }
+            });
+        }
+
+        save_config(&config).await?;
+
+        Ok(config)
+    }
+}
+
+fn get_config_path() -> Result<PathBuf> {
+    let app_dir = directories::ProjectDirs::from("com", "mohilcode", "proprompter")
+        .context("Failed to determine app directories")?
+        .data_dir()
+        .to_path_buf();
+
+    if !app_dir.exists() {
+        std::fs::create_dir_all(&app_dir)
+            .context("Failed to create app data directory")?;
+    }
+
+    Ok(app_dir.join("config.json"))
+}
+
+fn load_config() -> Result<Config> {
+    let file_path = get_config_path()?;
+
+    if !file_path.exists() {
+        return Ok(Config::default());
+    }
+
+    let content = std::fs::read_to_string(&file_path)
+        .context("Failed to read config file")?;
+
+    let config: Config = serde_json::from_str(&content)
+        .context("Failed to parse config file")?;
+
+    Ok(Config {
+        max_concurrency: config.max_concurrency.max(1),
+        ..config
+    })
+}
+
+async fn save_config(config: &Config) -> Result<()> {
+    let content = serde_json::to_string_pretty(config)
+        .context("Failed to serialize config")?;
+
+    tokio::fs::write(get_config_path()?, content)
+        .await
+        .context("Failed to write config file")?;
+
+    Ok(())
+}
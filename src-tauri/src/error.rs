@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+
+/// Error returned by commands, serialized as `{ "kind": "NotFound", "message": "..." }`
+/// so the frontend can react to the kind of failure instead of matching on text
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "kind", content = "message")]
+pub enum CommandError {
+    NotFound(String),
+    PermissionDenied(String),
+    InvalidInput(String),
+    Io(String),
+    Parse(String),
+    Other(String),
+}
+
+impl CommandError {
+    pub fn message(&self) -> &str {
+        match self {
+            CommandError::NotFound(message)
+            | CommandError::PermissionDenied(message)
+            | CommandError::InvalidInput(message)
+            | CommandError::Io(message)
+            | CommandError::Parse(message)
+            | CommandError::Other(message) => message,
+        }
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// The category of a failure, decided where the error is raised
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    NotFound,
+    PermissionDenied,
+    InvalidInput,
+    Parse,
+}
+
+/// An error that carries its category, so `CommandError::from` doesn't have to guess it.
+/// Build one with `not_found`, `permission_denied`, `invalid_input` or `parse_error`.
+#[derive(Debug)]
+pub struct CategorizedError {
+    pub kind: ErrorKind,
+    message: String,
+}
+
+impl std::fmt::Display for CategorizedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CategorizedError {}
+
+fn categorized(kind: ErrorKind, message: impl Into<String>) -> anyhow::Error {
+    anyhow::Error::new(CategorizedError { kind, message: message.into() })
+}
+
+pub fn not_found(message: impl Into<String>) -> anyhow::Error {
+    categorized(ErrorKind::NotFound, message)
+}
+
+pub fn permission_denied(message: impl Into<String>) -> anyhow::Error {
+    categorized(ErrorKind::PermissionDenied, message)
+}
+
+pub fn invalid_input(message: impl Into<String>) -> anyhow::Error {
+    categorized(ErrorKind::InvalidInput, message)
+}
+
+pub fn parse_error(message: impl Into<String>) -> anyhow::Error {
+    categorized(ErrorKind::Parse, message)
+}
+
+impl From<anyhow::Error> for CommandError {
+    fn from(error: anyhow::Error) -> Self {
+        let message = error.to_string();
+
+        // The first categorized cause in the context chain decides; uncategorized errors are Other
+        for cause in error.chain() {
+            if let Some(categorized) = cause.downcast_ref::<CategorizedError>() {
+                return match categorized.kind {
+                    ErrorKind::NotFound => CommandError::NotFound(message),
+                    ErrorKind::PermissionDenied => CommandError::PermissionDenied(message),
+                    ErrorKind::InvalidInput => CommandError::InvalidInput(message),
+                    ErrorKind::Parse => CommandError::Parse(message),
+                };
+            }
+
+            if let Some(io_error) = cause.downcast_ref::<std::io::Error>() {
+                return match io_error.kind() {
+                    std::io::ErrorKind::NotFound => CommandError::NotFound(message),
+                    std::io::ErrorKind::PermissionDenied => CommandError::PermissionDenied(message),
+                    _ => CommandError::Io(message),
+                };
+            }
+
+            if cause.is::<serde_json::Error>()
+                || cause.is::<quick_xml::Error>()
+                || cause.is::<quick_xml::events::attributes::AttrError>()
+                || cause.is::<crate::xml::parser::XmlParseError>()
+            {
+                return CommandError::Parse(message);
+            }
+
+            if cause.is::<regex::Error>() || cause.is::<globset::Error>() {
+                return CommandError::InvalidInput(message);
+            }
+        }
+
+        CommandError::Other(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn categorized_errors_keep_their_kind_under_context() {
+        let error = Err::<(), _>(not_found("Workspace not found"))
+            .context("Failed to add folder")
+            .unwrap_err();
+
+        assert!(matches!(CommandError::from(error), CommandError::NotFound(m) if m == "Failed to add folder"));
+        assert!(matches!(CommandError::from(invalid_input("Search query is empty")), CommandError::InvalidInput(_)));
+        assert!(matches!(CommandError::from(permission_denied("Path is outside workspace: /etc")), CommandError::PermissionDenied(_)));
+        assert!(matches!(CommandError::from(parse_error("No JSON array found in the response")), CommandError::Parse(_)));
+    }
+
+    #[test]
+    fn io_errors_map_by_kind() {
+        let missing = std::io::Error::new(std::io::ErrorKind::NotFound, "gone");
+        let error = anyhow::Error::new(missing).context("Failed to read file");
+
+        assert!(matches!(CommandError::from(error), CommandError::NotFound(_)));
+    }
+
+    #[test]
+    fn wording_alone_does_not_pick_a_category() {
+        let error = anyhow::anyhow!("Invalid thing not found: permission denied");
+
+        assert!(matches!(CommandError::from(error), CommandError::Other(_)));
+    }
+}
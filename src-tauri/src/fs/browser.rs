@@ -1,8 +1,30 @@
 use anyhow::{Context, Result};
-use ignore::Walk;
+use ignore::{WalkBuilder, WalkState};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
-use walkdir::WalkDir;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::config::IoLimits;
+use crate::error;
+
+// Directory names skipped by default because they are build output, dependencies or caches
+pub const DEFAULT_SKIP_DIRS: &[&str] = &[
+    "node_modules",
+    "target",
+    "dist",
+    "build",
+    "__pycache__",
+    ".venv",
+    "venv",
+    ".next",
+    ".cache",
+    ".idea",
+    ".proprompter",
+];
+
+// Directory names skipped no matter what options are given
+pub const ALWAYS_SKIP_DIRS: &[&str] = &[".git"];
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum FileType {
@@ -19,16 +41,41 @@ pub struct FileItem {
     pub size: u64,
 }
 
-/// Scans a directory with optional filtering
-pub async fn scan_directory(dir_path: &str, use_git_ignore: bool) -> Result<FileItem> {
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScanOutcome {
+    pub tree: FileItem,
+    // Entries below the root that couldn't be read and were left out of the tree
+    pub skipped: Vec<String>,
+}
+
+/// Returns the default skip list as owned names, for callers that want to extend it
+pub fn default_skip_dirs() -> Vec<String> {
+    DEFAULT_SKIP_DIRS.iter().map(|name| name.to_string()).collect()
+}
+
+/// Scans a directory with optional filtering, skipping the default noise directories
+pub async fn scan_directory(dir_path: &str, use_git_ignore: bool, limits: &IoLimits) -> Result<FileItem> {
+    scan_directory_filtered(dir_path, use_git_ignore, &default_skip_dirs(), limits).await
+}
+
+/// Scans a directory, skipping any directory whose name is in `skip_dirs` (`.git` is always skipped).
+/// Entries that couldn't be read are left out of the tree; `scan_directory_outcome` lists them.
+pub async fn scan_directory_filtered(dir_path: &str, use_git_ignore: bool, skip_dirs: &[String], limits: &IoLimits) -> Result<FileItem> {
+    Ok(scan_directory_outcome(dir_path, use_git_ignore, skip_dirs, limits).await?.tree)
+}
+
+/// Like `scan_directory_filtered`, but also returns the entries that couldn't be read.
+/// A missing or unreadable root still fails; errors below it only drop the affected entries.
+/// Each scan holds one I/O permit and walks with at most `max_concurrency` threads.
+pub async fn scan_directory_outcome(dir_path: &str, use_git_ignore: bool, skip_dirs: &[String], limits: &IoLimits) -> Result<ScanOutcome> {
     let path = Path::new(dir_path);
 
     if !path.exists() {
-        anyhow::bail!("Directory does not exist: {}", dir_path);
+        return Err(error::not_found(format!("Directory does not exist: {}", dir_path)));
     }
 
     if !path.is_dir() {
-        anyhow::bail!("Path is not a directory: {}", dir_path);
+        return Err(error::invalid_input(format!("Path is not a directory: {}", dir_path)));
     }
 
     let root_name = path.file_name()
@@ -37,132 +84,526 @@ pub async fn scan_directory(dir_path: &str, use_git_ignore: bool) -> Result<File
             path.to_string_lossy().to_string()
         });
 
-    let mut root = FileItem {
-        path: path.to_string_lossy().to_string(),
-        name: root_name,
-        file_type: FileType::Directory,
-        children: Some(Vec::new()),
-        size: 0,
-    };
+    // The walk does blocking I/O, so keep it off the async runtime's worker threads
+    let root_path = path.to_path_buf();
+    let skip_dirs = skip_dirs.to_vec();
+    let threads = limits.config.max_concurrency;
+    let _permit = limits.acquire_io_permit().await?;
+
+    let (children, skipped) = tokio::task::spawn_blocking(move || scan_tree(&root_path, use_git_ignore, skip_dirs, threads))
+        .await
+        .context("Directory scan task failed")??;
+
+    Ok(ScanOutcome {
+        tree: FileItem {
+            path: path.to_string_lossy().to_string(),
+            name: root_name,
+            file_type: FileType::Directory,
+            children: Some(children),
+            size: 0,
+        },
+        skipped,
+    })
+}
 
-    // Use different directory traversal based on whether to respect .gitignore
-    if use_git_ignore {
-        scan_with_gitignore(path, &mut root)?;
-    } else {
-        scan_without_gitignore(path, &mut root)?;
+/// Scans several directories concurrently, returning one outcome per input in the same order
+pub async fn scan_directories(dirs: &[String], use_git_ignore: bool, skip_dirs: &[String], limits: &IoLimits) -> Result<Vec<ScanOutcome>> {
+    let handles: Vec<_> = dirs.iter()
+        .map(|dir| {
+            let dir = dir.clone();
+            let skip_dirs = skip_dirs.to_vec();
+            let limits = limits.clone();
+
+            // Each scan waits for its own permit, so at most `max_concurrency` run at once
+            tokio::spawn(async move {
+                scan_directory_outcome(&dir, use_git_ignore, &skip_dirs, &limits).await
+            })
+        })
+        .collect();
+
+    let mut roots = Vec::with_capacity(handles.len());
+
+    // Awaiting in spawn order keeps the results aligned with `dirs`
+    for handle in handles {
+        roots.push(handle.await.context("Directory scan task failed")??);
     }
 
-    Ok(root)
+    Ok(roots)
 }
 
-// Implementation for scanning with .gitignore support
-fn scan_with_gitignore(dir_path: &Path, parent: &mut FileItem) -> Result<()> {
-    let children = parent.children.as_mut().unwrap();
+/// Rescans just the subtree containing `changed_path` so callers can splice it into an existing tree.
+/// Watcher events usually point at files (possibly deleted ones), so the nearest existing
+/// directory is the one rescanned.
+pub async fn rescan_path(changed_path: &str, use_git_ignore: bool, skip_dirs: &[String], limits: &IoLimits) -> Result<ScanOutcome> {
+    let mut dir = Path::new(changed_path);
 
-    for entry in Walk::new(dir_path) {
-        let entry = entry.context("Failed to read directory entry")?;
-        let path = entry.path();
+    while !dir.is_dir() {
+        dir = dir.parent()
+            .with_context(|| format!("No existing directory contains: {}", changed_path))?;
+    }
 
-        // Skip the root directory itself
-        if path == dir_path {
-            continue;
+    scan_directory_outcome(&dir.to_string_lossy(), use_git_ignore, skip_dirs, limits).await
+}
+
+/// Merges chains of directories that each hold exactly one item, itself a directory, into one
+/// node named like `main/java/com`. The merged node keeps the deepest directory's path and children.
+/// The root itself is never merged.
+pub fn collapse_single_child_dirs(root: &mut FileItem) {
+    if let Some(children) = root.children.as_mut() {
+        for child in children.iter_mut() {
+            collapse_item(child);
+        }
+
+        sort_children(children);
+    }
+}
+
+fn collapse_item(item: &mut FileItem) {
+    if !matches!(item.file_type, FileType::Directory) {
+        return;
+    }
+
+    loop {
+        let only_child_dir = match item.children.as_mut() {
+            Some(children) if children.len() == 1 && matches!(children[0].file_type, FileType::Directory) => children.pop(),
+            _ => None,
+        };
+
+        let Some(child) = only_child_dir else {
+            break;
+        };
+
+        item.name = format!("{}/{}", item.name, child.name);
+        item.path = child.path;
+        item.children = child.children;
+    }
+
+    if let Some(children) = item.children.as_mut() {
+        for child in children.iter_mut() {
+            collapse_item(child);
+        }
+
+        sort_children(children);
+    }
+}
+
+// Directories first, then files, both alphabetically
+fn sort_children(children: &mut [FileItem]) {
+    children.sort_by(|a, b| {
+        match (&a.file_type, &b.file_type) {
+            (FileType::Directory, FileType::File) => std::cmp::Ordering::Less,
+            (FileType::File, FileType::Directory) => std::cmp::Ordering::Greater,
+            _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
         }
+    });
+}
+
+/// Sorts file paths the way the scanned tree lists them: at each level directories come first,
+/// then files, both alphabetically (case-insensitive)
+pub fn sort_paths_like_tree(paths: &mut [String]) {
+    paths.sort_by(|a, b| tree_order(Path::new(a), Path::new(b)));
+}
 
-        // Only process immediate children of the parent
-        if path.parent() != Some(dir_path) {
+fn tree_order(a: &Path, b: &Path) -> std::cmp::Ordering {
+    let a_parts: Vec<_> = a.components().collect();
+    let b_parts: Vec<_> = b.components().collect();
+
+    for (i, (a_part, b_part)) in a_parts.iter().zip(&b_parts).enumerate() {
+        if a_part == b_part {
             continue;
         }
 
-        let name = path.file_name()
+        // A component with more after it is a directory at this level
+        let a_is_dir = i + 1 < a_parts.len();
+        let b_is_dir = i + 1 < b_parts.len();
+
+        let a_name = a_part.as_os_str().to_string_lossy();
+        let b_name = b_part.as_os_str().to_string_lossy();
+
+        return match (a_is_dir, b_is_dir) {
+            (true, false) => std::cmp::Ordering::Less,
+            (false, true) => std::cmp::Ordering::Greater,
+            // Fall back to the exact name so names differing only in case still sort stably
+            _ => a_name.to_lowercase().cmp(&b_name.to_lowercase()).then_with(|| a_name.cmp(&b_name)),
+        };
+    }
+
+    a_parts.len().cmp(&b_parts.len())
+}
+
+/// Returns a copy of the tree keeping only files with one of the extensions (case-insensitive,
+/// leading dots optional) and the directories leading to them. The root is always kept.
+pub fn filter_file_tree(root: &FileItem, extensions: &[String]) -> FileItem {
+    let extensions: Vec<String> = extensions.iter()
+        .map(|ext| ext.trim_start_matches('.').to_lowercase())
+        .collect();
+
+    let children = root.children.as_ref()
+        .map(|children| children.iter().filter_map(|child| filter_item(child, &extensions)).collect());
+
+    FileItem {
+        path: root.path.clone(),
+        name: root.name.clone(),
+        file_type: root.file_type.clone(),
+        children,
+        size: root.size,
+    }
+}
+
+// Filters one item, returning None when nothing in it matches
+fn filter_item(item: &FileItem, extensions: &[String]) -> Option<FileItem> {
+    match item.file_type {
+        FileType::File => {
+            let extension = Path::new(&item.path).extension()?.to_string_lossy().to_lowercase();
+
+            extensions.contains(&extension).then(|| item.clone())
+        },
+        FileType::Directory => {
+            let children: Vec<FileItem> = item.children.as_ref()?
+                .iter()
+                .filter_map(|child| filter_item(child, extensions))
+                .collect();
+
+            if children.is_empty() {
+                return None;
+            }
+
+            Some(FileItem {
+                path: item.path.clone(),
+                name: item.name.clone(),
+                file_type: FileType::Directory,
+                children: Some(children),
+                size: item.size,
+            })
+        }
+    }
+}
+
+// Whether a directory should be left out of the tree entirely
+fn is_skipped_dir(name: &str, skip_dirs: &[String]) -> bool {
+    ALWAYS_SKIP_DIRS.contains(&name) || skip_dirs.iter().any(|skip| skip == name)
+}
+
+// An entry found by the walker, before it is placed in the tree
+struct ScannedEntry {
+    path: PathBuf,
+    is_dir: bool,
+    size: u64,
+}
+
+// Walks the tree with the `ignore` crate's parallel walker and returns the root's sorted children,
+// plus the paths that couldn't be read (permission denied, broken links, loops).
+// With `use_git_ignore` the standard filters apply (.gitignore, hidden files); without it everything is listed.
+fn scan_tree(root: &Path, use_git_ignore: bool, skip_dirs: Vec<String>, threads: usize) -> Result<(Vec<FileItem>, Vec<String>)> {
+    let entries = Arc::new(Mutex::new(Vec::new()));
+    let skipped = Arc::new(Mutex::new(Vec::new()));
+    let first_error = Arc::new(Mutex::new(None));
+    let skip_dirs = Arc::new(skip_dirs);
+
+    // Symlinked directories are listed with their contents, as the recursive scan did;
+    // links that loop back up the tree are reported as skipped
+    let walker = WalkBuilder::new(root)
+        .standard_filters(use_git_ignore)
+        .follow_links(true)
+        .threads(threads)
+        .filter_entry(move |entry| {
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            !(is_dir && entry.depth() > 0 && is_skipped_dir(&entry.file_name().to_string_lossy(), &skip_dirs))
+        })
+        .build_parallel();
+
+    walker.run(|| {
+        let entries = Arc::clone(&entries);
+        let skipped = Arc::clone(&skipped);
+        let first_error = Arc::clone(&first_error);
+
+        Box::new(move |result| {
+            let entry = match result {
+                Ok(entry) => entry,
+                Err(e) => {
+                    // Only an error on the root itself loses the whole tree
+                    match error_path(&e) {
+                        Some(path) if path != root => {
+                            skipped.lock().unwrap().push(path.to_string_lossy().to_string());
+                            return WalkState::Continue;
+                        },
+                        _ => {
+                            first_error.lock().unwrap().get_or_insert(e);
+                            return WalkState::Quit;
+                        },
+                    }
+                }
+            };
+
+            // Skip the root directory itself
+            if entry.depth() == 0 {
+                return WalkState::Continue;
+            }
+
+            let path = entry.into_path();
+            let is_dir = path.is_dir();
+            let size = if is_dir { 0 } else { std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0) };
+
+            entries.lock().unwrap().push(ScannedEntry { path, is_dir, size });
+            WalkState::Continue
+        })
+    });
+
+    if let Some(e) = first_error.lock().unwrap().take() {
+        return Err(anyhow::Error::new(e).context("Failed to read directory entry"));
+    }
+
+    let entries = std::mem::take(&mut *entries.lock().unwrap());
+    let mut skipped = std::mem::take(&mut *skipped.lock().unwrap());
+    skipped.sort();
+
+    Ok((build_tree(root, entries), skipped))
+}
+
+// The path a walk error is about, if it names one
+fn error_path(error: &ignore::Error) -> Option<&Path> {
+    match error {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::Loop { child, .. } => Some(child),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => error_path(err),
+        ignore::Error::Partial(errors) => errors.iter().find_map(error_path),
+        _ => None,
+    }
+}
+
+// Assembles walker entries into nested FileItems, deepest first so every directory's
+// children are complete before it is attached to its parent
+fn build_tree(root: &Path, mut entries: Vec<ScannedEntry>) -> Vec<FileItem> {
+    entries.sort_by_key(|e| std::cmp::Reverse(e.path.components().count()));
+
+    let mut children_by_parent: HashMap<PathBuf, Vec<FileItem>> = HashMap::new();
+
+    for entry in entries {
+        let name = entry.path.file_name()
             .map(|n| n.to_string_lossy().to_string())
             .unwrap_or_default();
 
-        if path.is_dir() {
-            let mut dir_item = FileItem {
-                path: path.to_string_lossy().to_string(),
+        let item = if entry.is_dir {
+            let mut children = children_by_parent.remove(&entry.path).unwrap_or_default();
+            sort_children(&mut children);
+
+            FileItem {
+                path: entry.path.to_string_lossy().to_string(),
                 name,
                 file_type: FileType::Directory,
-                children: Some(Vec::new()),
+                children: Some(children),
                 size: 0,
-            };
-
-            // Recursively scan the subdirectory
-            scan_with_gitignore(path, &mut dir_item)?;
-            children.push(dir_item);
+            }
         } else {
-            let size = std::fs::metadata(path)
-                .map(|m| m.len())
-                .unwrap_or(0);
-
-            children.push(FileItem {
-                path: path.to_string_lossy().to_string(),
+            FileItem {
+                path: entry.path.to_string_lossy().to_string(),
                 name,
                 file_type: FileType::File,
                 children: None,
-                size,
-            });
+                size: entry.size,
+            }
+        };
+
+        if let Some(parent) = entry.path.parent() {
+            children_by_parent.entry(parent.to_path_buf()).or_default().push(item);
         }
     }
 
-    // Sort children: directories first, then files, both alphabetically
-    children.sort_by(|a, b| {
-        match (&a.file_type, &b.file_type) {
-            (FileType::Directory, FileType::File) => std::cmp::Ordering::Less,
-            (FileType::File, FileType::Directory) => std::cmp::Ordering::Greater,
-            _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+    let mut children = children_by_parent.remove(root).unwrap_or_default();
+    sort_children(&mut children);
+
+    children
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str) -> FileItem {
+        FileItem {
+            path: path.to_string(),
+            name: Path::new(path).file_name().unwrap().to_string_lossy().to_string(),
+            file_type: FileType::File,
+            children: None,
+            size: 1,
         }
-    });
+    }
 
-    Ok(())
-}
-
-// Implementation for scanning without .gitignore support
-fn scan_without_gitignore(dir_path: &Path, parent: &mut FileItem) -> Result<()> {
-  let children = parent.children.as_mut().unwrap();
-
-  for entry in WalkDir::new(dir_path).max_depth(1).into_iter().skip(1) {
-      let entry = entry.context("Failed to read directory entry")?;
-      let path = entry.path();
-
-      let name = path.file_name()
-          .map(|n| n.to_string_lossy().to_string())
-          .unwrap_or_default();
-
-      if path.is_dir() {
-          let mut dir_item = FileItem {
-              path: path.to_string_lossy().to_string(),
-              name,
-              file_type: FileType::Directory,
-              children: Some(Vec::new()),
-              size: 0,
-          };
-
-          // Recursively scan the subdirectory
-          scan_without_gitignore(path, &mut dir_item)?;
-          children.push(dir_item);
-      } else {
-          let size = std::fs::metadata(path)
-              .map(|m| m.len())
-              .unwrap_or(0);
-
-          children.push(FileItem {
-              path: path.to_string_lossy().to_string(),
-              name,
-              file_type: FileType::File,
-              children: None,
-              size,
-          });
-      }
-  }
-
-  // Sort children: directories first, then files, both alphabetically
-  children.sort_by(|a, b| {
-      match (&a.file_type, &b.file_type) {
-          (FileType::Directory, FileType::File) => std::cmp::Ordering::Less,
-          (FileType::File, FileType::Directory) => std::cmp::Ordering::Greater,
-          _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
-      }
-  });
-
-  Ok(())
-}
\ No newline at end of file
+    fn dir(path: &str, children: Vec<FileItem>) -> FileItem {
+        FileItem {
+            path: path.to_string(),
+            name: Path::new(path).file_name().unwrap().to_string_lossy().to_string(),
+            file_type: FileType::Directory,
+            children: Some(children),
+            size: 0,
+        }
+    }
+
+    // Every file path in the tree, depth first in listing order
+    fn file_paths(item: &FileItem) -> Vec<String> {
+        match &item.children {
+            Some(children) => children.iter().flat_map(file_paths).collect(),
+            None => vec![item.path.clone()],
+        }
+    }
+
+    #[test]
+    fn filter_keeps_matching_files_and_their_directories() {
+        let root = dir("/p", vec![
+            dir("/p/src", vec![file("/p/src/main.RS"), file("/p/src/notes.md")]),
+            dir("/p/docs", vec![file("/p/docs/guide.md")]),
+            file("/p/build.rs"),
+            file("/p/Makefile"),
+        ]);
+
+        let filtered = filter_file_tree(&root, &[".rs".to_string()]);
+
+        assert_eq!(filtered.path, "/p");
+        assert_eq!(file_paths(&filtered), ["/p/src/main.RS", "/p/build.rs"]);
+        let dirs: Vec<_> = filtered.children.unwrap().into_iter().map(|child| child.name).collect();
+        assert_eq!(dirs, ["src", "build.rs"]);
+    }
+
+    #[test]
+    fn filter_without_matches_keeps_an_empty_root() {
+        let root = dir("/p", vec![dir("/p/src", vec![file("/p/src/main.rs")])]);
+
+        let filtered = filter_file_tree(&root, &["py".to_string()]);
+
+        assert_eq!(filtered.name, "p");
+        assert!(filtered.children.unwrap().is_empty());
+    }
+
+    #[test]
+    fn collapses_a_four_level_chain_into_one_node() {
+        let mut root = dir("/p", vec![
+            dir("/p/src", vec![
+                dir("/p/src/main", vec![
+                    dir("/p/src/main/java", vec![
+                        dir("/p/src/main/java/com", vec![file("/p/src/main/java/com/App.java"), file("/p/src/main/java/com/Util.java")]),
+                    ]),
+                ]),
+            ]),
+            file("/p/README.md"),
+        ]);
+
+        collapse_single_child_dirs(&mut root);
+
+        let children = root.children.as_ref().unwrap();
+        assert_eq!(root.name, "p");
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].name, "src/main/java/com");
+        assert_eq!(children[0].path, "/p/src/main/java/com");
+        assert_eq!(file_paths(&children[0]), ["/p/src/main/java/com/App.java", "/p/src/main/java/com/Util.java"]);
+        assert_eq!(children[1].name, "README.md");
+    }
+
+    #[test]
+    fn stops_collapsing_at_a_directory_with_a_file() {
+        let mut root = dir("/p", vec![
+            dir("/p/a", vec![
+                dir("/p/a/b", vec![
+                    dir("/p/a/b/c", vec![file("/p/a/b/c/x.rs")]),
+                    file("/p/a/b/y.rs"),
+                ]),
+            ]),
+        ]);
+
+        collapse_single_child_dirs(&mut root);
+
+        let collapsed = &root.children.as_ref().unwrap()[0];
+        assert_eq!(collapsed.name, "a/b");
+        let names: Vec<_> = collapsed.children.as_ref().unwrap().iter().map(|child| child.name.as_str()).collect();
+        assert_eq!(names, ["c", "y.rs"]);
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("proprompter-{}-{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // A plain walkdir walk, used only to check the parallel scan finds the same files
+    fn sequential_file_paths(root: &Path) -> Vec<String> {
+        let mut paths: Vec<String> = walkdir::WalkDir::new(root)
+            .into_iter()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.path().to_string_lossy().to_string())
+            .collect();
+
+        sort_paths_like_tree(&mut paths);
+        paths
+    }
+
+    #[tokio::test]
+    async fn parallel_scan_matches_a_sequential_walk() {
+        let root = scratch_dir("scan-equivalence");
+        for d in 0..40 {
+            let sub = root.join(format!("dir{:02}", d)).join("nested");
+            std::fs::create_dir_all(&sub).unwrap();
+            for f in 0..50 {
+                std::fs::write(sub.join(format!("file{:02}.txt", f)), "x").unwrap();
+            }
+            std::fs::write(root.join(format!("top{:02}.txt", d)), "x").unwrap();
+        }
+
+        let sequential = sequential_file_paths(&root);
+        let outcome = scan_directory_outcome(&root.to_string_lossy(), false, &[], &IoLimits::default()).await.unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(sequential.len(), 2040);
+        assert_eq!(file_paths(&outcome.tree), sequential);
+        assert!(outcome.skipped.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn follows_directory_links_and_skips_loops() {
+        let root = scratch_dir("scan-links");
+        let real = root.join("real");
+        std::fs::create_dir_all(&real).unwrap();
+        std::fs::write(real.join("a.txt"), "a").unwrap();
+        std::os::unix::fs::symlink(&real, root.join("linked")).unwrap();
+        std::os::unix::fs::symlink(&root, real.join("loop")).unwrap();
+
+        let outcome = scan_directory_outcome(&root.to_string_lossy(), false, &[], &IoLimits::default()).await.unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let files = file_paths(&outcome.tree);
+        assert!(files.contains(&root.join("linked").join("a.txt").to_string_lossy().to_string()));
+        assert!(files.contains(&real.join("a.txt").to_string_lossy().to_string()));
+        assert!(!outcome.skipped.is_empty());
+    }
+
+    #[test]
+    fn sorts_paths_directories_first_and_stably() {
+        let mut paths: Vec<String> = [
+            "/p/zeta.rs",
+            "/p/src/b.rs",
+            "/p/Alpha.rs",
+            "/p/src/util/x.rs",
+            "/p/alpha.rs",
+            "/p/src/A.rs",
+            "/p/docs/guide.md",
+        ].iter().map(|p| p.to_string()).collect();
+
+        let expected = [
+            "/p/docs/guide.md",
+            "/p/src/util/x.rs",
+            "/p/src/A.rs",
+            "/p/src/b.rs",
+            "/p/Alpha.rs",
+            "/p/alpha.rs",
+            "/p/zeta.rs",
+        ];
+
+        sort_paths_like_tree(&mut paths);
+        assert_eq!(paths, expected);
+
+        // The order doesn't depend on the order the paths came in
+        paths.reverse();
+        sort_paths_like_tree(&mut paths);
+        assert_eq!(paths, expected);
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+
+use crate::error;
+use crate::fs::reader::looks_binary;
+use crate::fs::writer::LineEndings;
+
+// How much of a file is sniffed for binary content
+const BINARY_SNIFF_BYTES: usize = 8000;
+
+// Files larger than this get an estimated line count from a sample instead of a full read
+const FULL_LINE_COUNT_MAX_BYTES: u64 = 1024 * 1024;
+const LINE_COUNT_SAMPLE_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SelectionSummary {
+    // Text files that would be included
+    pub file_count: usize,
+    pub total_bytes: u64,
+    pub skipped_binary: usize,
+    pub skipped_missing: usize,
+}
+
+/// Summarizes a selection from file metadata plus a small binary sniff, without reading whole files.
+/// Directories in the selection are ignored.
+pub async fn selection_summary(paths: &[String]) -> Result<SelectionSummary> {
+    let mut summary = SelectionSummary::default();
+
+    for path in paths {
+        let metadata = match tokio::fs::metadata(path).await {
+            Ok(metadata) => metadata,
+            Err(_) => {
+                summary.skipped_missing += 1;
+                continue;
+            }
+        };
+
+        if !metadata.is_file() {
+            continue;
+        }
+
+        if is_binary_file(path).await {
+            summary.skipped_binary += 1;
+            continue;
+        }
+
+        summary.file_count += 1;
+        summary.total_bytes += metadata.len();
+    }
+
+    Ok(summary)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileMetadata {
+    pub path: String,
+    pub size: u64,
+    // Seconds since the Unix epoch, when the platform reports it
+    pub modified: Option<i64>,
+    pub is_binary: bool,
+    pub is_symlink: bool,
+    // None for binary files
+    pub line_count: Option<usize>,
+    // None for binary files
+    #[serde(default)]
+    pub line_endings: Option<LineEndings>,
+    // True when line_count was extrapolated (and line_endings counted) from a sample of a large file
+    pub approximate: bool,
+}
+
+/// Stats a file and sniffs its start, reading the whole file only when it is small enough to count lines cheaply
+pub async fn file_metadata(path: &str) -> Result<FileMetadata> {
+    let link_metadata = tokio::fs::symlink_metadata(path)
+        .await
+        .with_context(|| format!("Failed to read metadata: {}", path))?;
+    let metadata = tokio::fs::metadata(path)
+        .await
+        .with_context(|| format!("Failed to read metadata: {}", path))?;
+
+    if !metadata.is_file() {
+        return Err(error::invalid_input(format!("Path is not a file: {}", path)));
+    }
+
+    let modified = metadata.modified()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs() as i64);
+
+    let size = metadata.len();
+    let is_binary = is_binary_file(path).await;
+
+    let (line_count, line_endings, approximate) = if is_binary {
+        (None, None, false)
+    } else {
+        let (sample, approximate) = read_sample(path, size).await?;
+        (Some(count_lines(&sample, size, approximate)), Some(LineEndings::count(&sample)), approximate)
+    };
+
+    Ok(FileMetadata {
+        path: path.to_string(),
+        size,
+        modified,
+        is_binary,
+        is_symlink: link_metadata.file_type().is_symlink(),
+        line_count,
+        line_endings,
+        approximate,
+    })
+}
+
+// The whole file when it's small, otherwise its first chunk; the flag says which
+async fn read_sample(path: &str, size: u64) -> Result<(Vec<u8>, bool)> {
+    if size == 0 {
+        return Ok((Vec::new(), false));
+    }
+
+    let file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("Failed to open file: {}", path))?;
+
+    let approximate = size > FULL_LINE_COUNT_MAX_BYTES;
+    let limit = if approximate { LINE_COUNT_SAMPLE_BYTES as u64 } else { size };
+
+    let mut sample = Vec::with_capacity(limit as usize);
+    file.take(limit)
+        .read_to_end(&mut sample)
+        .await
+        .with_context(|| format!("Failed to read file: {}", path))?;
+
+    Ok((sample, approximate))
+}
+
+// Counts lines exactly for a whole file and extrapolates from a sample of a larger one
+fn count_lines(sample: &[u8], size: u64, approximate: bool) -> usize {
+    if sample.is_empty() {
+        return 0;
+    }
+
+    let newlines = sample.iter().filter(|&&b| b == b'\n').count();
+
+    if approximate {
+        return (newlines as f64 * size as f64 / sample.len() as f64).round() as usize;
+    }
+
+    // A final line without a trailing newline still counts
+    let trailing = if sample.last() == Some(&b'\n') { 0 } else { 1 };
+
+    newlines + trailing
+}
+
+// Reads only the first few KB of the file to look for NUL bytes
+async fn is_binary_file(path: &str) -> bool {
+    let Ok(file) = tokio::fs::File::open(path).await else {
+        return false;
+    };
+
+    let mut sample = Vec::with_capacity(BINARY_SNIFF_BYTES);
+
+    match file.take(BINARY_SNIFF_BYTES as u64).read_to_end(&mut sample).await {
+        Ok(_) => looks_binary(&sample),
+        Err(_) => false,
+    }
+}
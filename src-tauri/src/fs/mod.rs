@@ -2,8 +2,17 @@ pub mod browser;
 pub mod reader;
 pub mod writer;
 pub mod watcher;
+pub mod sandbox;
+pub mod search;
+pub mod metadata;
+pub mod project;
+pub mod paths;
 
-pub use browser::{scan_directory, FileItem, FileType};
-pub use reader::read_file;
-pub use writer::{write_file, create_backup, restore_from_backup};
-pub use watcher::FileSystemWatcher;
\ No newline at end of file
+pub use browser::{scan_directory, scan_directory_filtered, scan_directory_outcome, scan_directories, FileItem, FileType, ScanOutcome, DEFAULT_SKIP_DIRS};
+pub use reader::{read_file, read_file_with_encoding, read_file_base64, read_file_for_display, read_file_head, read_file_tail, read_file_resolved, Base64File, FilePreview, ResolvedFile, DisplayContent, LongLineMode, looks_binary, has_text_extension, TEXT_EXTENSIONS};
+pub use writer::{write_file, write_file_with_eol, tidy_whitespace, set_aside_corrupt_file, take_recovered_files, create_backup, global_backup_dir, restore_from_backup, verify_backup, BackupLocation, CreatedBackup, RecoveredFile, EolMode, EolStyle, LineEnding, LineEndings, detect_line_endings};
+pub use watcher::FileSystemWatcher;
+pub use sandbox::{resolve_within_roots, containing_root};
+pub use metadata::{file_metadata, selection_summary, FileMetadata, SelectionSummary};
+pub use project::{detect_project_type, DetectedProject, ProjectType};
+pub use paths::common_root;
\ No newline at end of file
@@ -0,0 +1,34 @@
+use std::path::{Component, Path, PathBuf};
+
+/// The deepest directory containing every path. Files count as their parent directory.
+/// Returns None for an empty selection or when the paths share no root, e.g. different
+/// drives on Windows or a mix of absolute and relative paths.
+pub fn common_root(paths: &[String]) -> Option<PathBuf> {
+    let mut directories = paths.iter().map(|path| {
+        let path = Path::new(path);
+
+        if path.is_dir() {
+            path
+        } else {
+            path.parent().unwrap_or(path)
+        }
+    });
+
+    let first = directories.next()?;
+    let mut common: Vec<Component> = first.components().collect();
+
+    for directory in directories {
+        let shared = common.iter()
+            .zip(directory.components())
+            .take_while(|(a, b)| **a == *b)
+            .count();
+
+        common.truncate(shared);
+    }
+
+    if common.is_empty() {
+        return None;
+    }
+
+    Some(common.iter().collect())
+}
@@ -0,0 +1,65 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::error;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ProjectType {
+    Rust,
+    Node,
+    Python,
+    Go,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DetectedProject {
+    pub project_type: ProjectType,
+    // The marker file that was found, relative to the root
+    pub marker: String,
+    // Exclude patterns worth suggesting for this kind of project
+    pub suggested_excludes: Vec<String>,
+}
+
+// Marker files in priority order; the first one present names the type
+const MARKERS: &[(ProjectType, &[&str])] = &[
+    (ProjectType::Rust, &["Cargo.toml"]),
+    (ProjectType::Node, &["package.json"]),
+    (ProjectType::Python, &["pyproject.toml", "requirements.txt", "setup.py"]),
+    (ProjectType::Go, &["go.mod"]),
+];
+
+impl ProjectType {
+    pub fn suggested_excludes(&self) -> &'static [&'static str] {
+        match self {
+            ProjectType::Rust => &["target/**", "Cargo.lock"],
+            ProjectType::Node => &["node_modules/**", "dist/**", "build/**", ".next/**", "coverage/**", "package-lock.json", "yarn.lock", "pnpm-lock.yaml"],
+            ProjectType::Python => &["__pycache__/**", "*.pyc", ".venv/**", "venv/**", ".pytest_cache/**", ".mypy_cache/**", "*.egg-info/**"],
+            ProjectType::Go => &["vendor/**", "go.sum"],
+        }
+    }
+}
+
+/// Detects the project types at the root of a directory from their marker files.
+/// Polyglot repos get one entry per type, in the order of MARKERS.
+pub fn detect_project_type(root: &str) -> Result<Vec<DetectedProject>> {
+    let root = Path::new(root);
+
+    if !root.is_dir() {
+        return Err(error::invalid_input(format!("Not a directory: {}", root.display())));
+    }
+
+    let detected = MARKERS.iter()
+        .filter_map(|(project_type, markers)| {
+            markers.iter()
+                .find(|marker| root.join(marker).is_file())
+                .map(|marker| DetectedProject {
+                    project_type: *project_type,
+                    marker: marker.to_string(),
+                    suggested_excludes: project_type.suggested_excludes().iter().map(|p| p.to_string()).collect(),
+                })
+        })
+        .collect();
+
+    Ok(detected)
+}
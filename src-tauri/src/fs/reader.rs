@@ -1,27 +1,33 @@
 use anyhow::{Context, Result};
+use base64::Engine;
 use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
 use std::fs::File;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-/// Reads a file with memory mapping for large files
-pub async fn read_file(path: &str) -> Result<String> {
+use crate::config::IoLimits;
+use crate::error;
+
+/// Reads a file with memory mapping for large files, holding one of the shared I/O permits meanwhile
+pub async fn read_file(path: &str, limits: &IoLimits) -> Result<String> {
+    let _permit = limits.acquire_io_permit().await?;
     let path = Path::new(path);
 
     if !path.exists() {
-        anyhow::bail!("File does not exist: {}", path.display());
+        return Err(error::not_found(format!("File does not exist: {}", path.display())));
     }
 
     if !path.is_file() {
-        anyhow::bail!("Path is not a file: {}", path.display());
+        return Err(error::invalid_input(format!("Path is not a file: {}", path.display())));
     }
 
     let file = File::open(path)
         .with_context(|| format!("Failed to open file: {}", path.display()))?;
 
-    let file_size = file.metadata()?.len() as usize;
+    let file_size = file.metadata()?.len();
 
     // Use memory mapping for large files
-    if file_size > 1_048_576 { // 1 MB threshold
+    if file_size > limits.config.mmap_threshold_bytes {
         let mmap = unsafe { Mmap::map(&file)? };
 
         // Try to convert to UTF-8, or provide bytes as fallback
@@ -38,4 +44,383 @@ pub async fn read_file(path: &str) -> Result<String> {
             .await
             .with_context(|| format!("Failed to read file: {}", path.display()))
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ResolvedFile {
+    pub content: String,
+    // Canonical path the content was actually read from
+    pub resolved_path: String,
+    // Whether the requested path is itself a symlink
+    pub is_symlink: bool,
+}
+
+/// Reads a file and reports where its content really came from. With `follow_symlinks` off a
+/// symlinked file is refused; when `roots` are given, a path resolving outside all of them is refused.
+pub async fn read_file_resolved(path: &str, follow_symlinks: bool, roots: &[PathBuf], limits: &IoLimits) -> Result<ResolvedFile> {
+    let is_symlink = tokio::fs::symlink_metadata(path)
+        .await
+        .with_context(|| format!("File does not exist: {}", path))?
+        .file_type()
+        .is_symlink();
+
+    if is_symlink && !follow_symlinks {
+        return Err(error::permission_denied(format!("Refusing to follow symlink: {}", path)));
+    }
+
+    let resolved = if roots.is_empty() {
+        Path::new(path).canonicalize()
+            .with_context(|| format!("Failed to resolve path: {}", path))?
+    } else {
+        crate::fs::sandbox::resolve_within_roots(path, roots)?
+    };
+
+    let resolved_path = resolved.to_string_lossy().to_string();
+    let content = read_file(&resolved_path, limits).await?;
+
+    Ok(ResolvedFile {
+        content,
+        resolved_path,
+        is_symlink,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FilePreview {
+    pub content: String,
+    // Size of the whole file in bytes
+    pub size: u64,
+    // True when the preview doesn't cover the whole file
+    pub truncated: bool,
+}
+
+/// Returns at most `max_bytes` (and `max_lines`, when given) from the start of a file
+pub async fn read_file_head(path: &str, max_bytes: usize, max_lines: Option<usize>, limits: &IoLimits) -> Result<FilePreview> {
+    let path = path.to_string();
+    let mmap_threshold = limits.config.mmap_threshold_bytes;
+    let _permit = limits.acquire_io_permit().await?;
+
+    tokio::task::spawn_blocking(move || {
+        preview_bytes(&path, mmap_threshold, |bytes| {
+            let end = utf8_boundary(bytes, bytes.len().min(max_bytes));
+            let mut content = String::from_utf8_lossy(&bytes[..end]).into_owned();
+
+            if let Some(max_lines) = max_lines {
+                let keep = content.split_inclusive('\n').take(max_lines).map(|line| line.len()).sum();
+                content.truncate(keep);
+            }
+
+            content
+        })
+    })
+    .await
+    .context("File read task failed")?
+}
+
+/// Returns at most `max_bytes` (and `max_lines`, when given) from the end of a file
+pub async fn read_file_tail(path: &str, max_bytes: usize, max_lines: Option<usize>, limits: &IoLimits) -> Result<FilePreview> {
+    let path = path.to_string();
+    let mmap_threshold = limits.config.mmap_threshold_bytes;
+    let _permit = limits.acquire_io_permit().await?;
+
+    tokio::task::spawn_blocking(move || {
+        preview_bytes(&path, mmap_threshold, |bytes| {
+            let mut start = bytes.len().saturating_sub(max_bytes);
+
+            // Never start in the middle of a multibyte character
+            while start < bytes.len() && is_utf8_continuation(bytes[start]) {
+                start += 1;
+            }
+
+            let mut content = String::from_utf8_lossy(&bytes[start..]).into_owned();
+
+            if let Some(max_lines) = max_lines {
+                let lines: Vec<&str> = content.split_inclusive('\n').collect();
+                let skip: usize = lines[..lines.len().saturating_sub(max_lines)].iter().map(|line| line.len()).sum();
+                content.drain(..skip);
+            }
+
+            content
+        })
+    })
+    .await
+    .context("File read task failed")?
+}
+
+// Hands the file's bytes to `take`, memory-mapping files above `mmap_threshold` bytes
+// so only the pages the preview touches are read
+fn preview_bytes(path: &str, mmap_threshold: u64, take: impl FnOnce(&[u8]) -> String) -> Result<FilePreview> {
+    if !Path::new(path).is_file() {
+        return Err(error::invalid_input(format!("Path is not a file: {}", path)));
+    }
+
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open file: {}", path))?;
+
+    let size = file.metadata()?.len();
+
+    let content = if size > mmap_threshold {
+        let mmap = unsafe { Mmap::map(&file)? };
+        take(&mmap[..])
+    } else {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read file: {}", path))?;
+        take(&bytes)
+    };
+
+    Ok(FilePreview {
+        truncated: (content.len() as u64) < size,
+        content,
+        size,
+    })
+}
+
+// Moves `end` back so `bytes[..end]` doesn't end partway through a multibyte character
+fn utf8_boundary(bytes: &[u8], end: usize) -> usize {
+    let mut lead = end;
+
+    while lead > 0 && end - lead < 4 && is_utf8_continuation(bytes[lead - 1]) {
+        lead -= 1;
+    }
+
+    if lead == 0 {
+        return end;
+    }
+
+    let char_len = match bytes[lead - 1] {
+        0xF0.. => 4,
+        0xE0.. => 3,
+        0xC0.. => 2,
+        _ => 1,
+    };
+
+    if lead - 1 + char_len > end { lead - 1 } else { end }
+}
+
+fn is_utf8_continuation(byte: u8) -> bool {
+    byte & 0b1100_0000 == 0b1000_0000
+}
+
+// Encoding labels offered to users; `encoding_rs` also accepts the other WHATWG aliases
+pub const SUPPORTED_ENCODINGS: &[&str] = &[
+    "utf-8", "utf-16le", "utf-16be", "windows-1252", "iso-8859-1", "iso-8859-2", "iso-8859-15",
+    "shift_jis", "euc-jp", "iso-2022-jp", "gbk", "gb18030", "big5", "euc-kr", "koi8-r", "windows-1251",
+];
+
+/// Reads a file and decodes it with the given encoding label (e.g. `shift_jis`, `latin1`)
+pub async fn read_file_with_encoding(path: &str, encoding_label: &str, limits: &IoLimits) -> Result<String> {
+    let _permit = limits.acquire_io_permit().await?;
+    let encoding = encoding_rs::Encoding::for_label(encoding_label.trim().as_bytes())
+        .ok_or_else(|| error::invalid_input(format!(
+            "Unknown encoding: {}. Supported encodings: {}",
+            encoding_label,
+            SUPPORTED_ENCODINGS.join(", ")
+        )))?;
+
+    let file_path = Path::new(path);
+
+    if !file_path.is_file() {
+        return Err(error::invalid_input(format!("Path is not a file: {}", path)));
+    }
+
+    let bytes = tokio::fs::read(file_path)
+        .await
+        .with_context(|| format!("Failed to read file: {}", path))?;
+
+    // A byte order mark, if present, overrides the requested encoding
+    let (decoded, _, _) = encoding.decode(&bytes);
+
+    Ok(decoded.into_owned())
+}
+
+// Extensions treated as text when content inclusion is limited to an allowlist
+pub const TEXT_EXTENSIONS: &[&str] = &[
+    "rs", "toml", "lock",
+    "js", "jsx", "ts", "tsx", "mjs", "cjs", "json", "html", "css", "scss", "vue", "svelte",
+    "py", "rb", "go", "java", "kt", "swift", "c", "h", "cpp", "hpp", "cs", "php", "sh", "sql",
+    "md", "txt", "yaml", "yml", "xml", "ini", "cfg", "conf", "env", "gitignore",
+];
+
+// Extensionless file names that are text as well
+pub const TEXT_FILE_NAMES: &[&str] = &["Makefile", "Dockerfile", "LICENSE", "README"];
+
+/// Whether a path's extension (or bare file name) is in the given text allowlist.
+/// Dotfiles such as `.env` or `.gitignore` have no extension, so their name is matched instead.
+pub fn has_text_extension<S: AsRef<str>>(path: &str, extensions: &[S]) -> bool {
+    let path = Path::new(path);
+    let allowed = |ext: &str| extensions.iter().any(|allowed| allowed.as_ref().trim_start_matches('.').eq_ignore_ascii_case(ext));
+
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => allowed(ext),
+        None => path.file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| name.strip_prefix('.').is_some_and(allowed) || TEXT_FILE_NAMES.contains(&name))
+            .unwrap_or(false),
+    }
+}
+
+/// Heuristic binary check: a NUL byte near the start of the content
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(8000)];
+    sample.contains(&0)
+}
+
+/// What to do with lines too long to render comfortably
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum LongLineMode {
+    // Cut the line and append LONG_LINE_MARKER
+    #[default]
+    Truncate,
+    // Break the line into chunks of the maximum length
+    Wrap,
+}
+
+/// Appended to truncated lines
+pub const LONG_LINE_MARKER: &str = " … [line truncated]";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DisplayContent {
+    pub content: String,
+    // True when any line was truncated or wrapped; the content must not be used for edits then
+    pub altered: bool,
+    pub long_lines: usize,
+}
+
+/// Reads a file for display, shortening lines longer than `max_line_length` characters
+/// so minified files don't freeze the UI. The file itself is never touched.
+pub async fn read_file_for_display(path: &str, max_line_length: usize, mode: LongLineMode, limits: &IoLimits) -> Result<DisplayContent> {
+    let content = read_file(path, limits).await?;
+
+    Ok(limit_line_length(&content, max_line_length, mode))
+}
+
+pub fn limit_line_length(content: &str, max_line_length: usize, mode: LongLineMode) -> DisplayContent {
+    let max_line_length = max_line_length.max(1);
+    let mut long_lines = 0;
+
+    let lines: Vec<String> = content.split('\n')
+        .map(|line| {
+            let Some((cut, _)) = line.char_indices().nth(max_line_length) else {
+                return line.to_string();
+            };
+
+            long_lines += 1;
+
+            match mode {
+                LongLineMode::Truncate => format!("{}{}", &line[..cut], LONG_LINE_MARKER),
+                LongLineMode::Wrap => {
+                    let chars: Vec<char> = line.chars().collect();
+                    chars.chunks(max_line_length)
+                        .map(|chunk| chunk.iter().collect::<String>())
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                },
+            }
+        })
+        .collect();
+
+    DisplayContent {
+        content: if long_lines > 0 { lines.join("\n") } else { content.to_string() },
+        altered: long_lines > 0,
+        long_lines,
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Base64File {
+    pub content: String,
+    pub mime_type: String,
+    pub size: u64,
+}
+
+/// Reads a file's raw bytes as base64 for previews, refusing files larger than `max_bytes`
+pub async fn read_file_base64(path: &str, max_bytes: u64, limits: &IoLimits) -> Result<Base64File> {
+    let _permit = limits.acquire_io_permit().await?;
+    let file_path = Path::new(path);
+
+    if !file_path.is_file() {
+        return Err(error::invalid_input(format!("Path is not a file: {}", path)));
+    }
+
+    let size = tokio::fs::metadata(file_path)
+        .await
+        .with_context(|| format!("Failed to read metadata: {}", path))?
+        .len();
+
+    if size > max_bytes {
+        return Err(error::invalid_input(format!("File is too large to preview ({} bytes, limit {}): {}", size, max_bytes, path)));
+    }
+
+    let bytes = tokio::fs::read(file_path)
+        .await
+        .with_context(|| format!("Failed to read file: {}", path))?;
+
+    Ok(Base64File {
+        content: base64::engine::general_purpose::STANDARD.encode(&bytes),
+        mime_type: mime_type(path, &bytes).to_string(),
+        size,
+    })
+}
+
+/// Detects a MIME type from magic bytes, falling back to the extension
+pub fn mime_type(path: &str, bytes: &[u8]) -> &'static str {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"BM", "image/bmp"),
+        (b"\x00\x00\x01\x00", "image/x-icon"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+    ];
+
+    if let Some((_, mime)) = SIGNATURES.iter().find(|(magic, _)| bytes.starts_with(magic)) {
+        return mime;
+    }
+
+    // RIFF containers carry their format at offset 8
+    if bytes.len() >= 12 && bytes.starts_with(b"RIFF") && &bytes[8..12] == b"WEBP" {
+        return "image/webp";
+    }
+
+    let extension = Path::new(path).extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "bmp" => "image/bmp",
+        "pdf" => "application/pdf",
+        "json" => "application/json",
+        _ if !looks_binary(bytes) => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_extensions_case_insensitively() {
+        assert!(has_text_extension("/p/src/main.RS", TEXT_EXTENSIONS));
+        assert!(has_text_extension("/p/notes.md", &[".md"]));
+        assert!(!has_text_extension("/p/logo.png", TEXT_EXTENSIONS));
+    }
+
+    #[test]
+    fn matches_dotfiles_by_name() {
+        assert!(has_text_extension("/p/.env", TEXT_EXTENSIONS));
+        assert!(has_text_extension("/p/.gitignore", TEXT_EXTENSIONS));
+        assert!(!has_text_extension("/p/.DS_Store", TEXT_EXTENSIONS));
+        assert!(has_text_extension("/p/Makefile", TEXT_EXTENSIONS));
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::{Context, Result};
+use std::path::{Component, Path, PathBuf};
+
+use crate::error;
+
+/// Resolves a path and ensures it stays inside one of the allowed roots.
+///
+/// `..` components are collapsed and symlinks in the existing part of the path are
+/// followed, so the returned path is the real location that would be touched on disk.
+pub fn resolve_within_roots(path: &str, roots: &[PathBuf]) -> Result<PathBuf> {
+    if roots.is_empty() {
+        return Err(error::permission_denied(format!("Path is outside workspace (no workspace folders available): {}", path)));
+    }
+
+    let resolved = resolve_path(Path::new(path))?;
+
+    let inside = roots.iter()
+        .filter_map(|root| root.canonicalize().ok())
+        .any(|root| resolved != root && resolved.starts_with(&root));
+
+    if !inside {
+        return Err(error::permission_denied(format!("Path is outside workspace: {}", path)));
+    }
+
+    Ok(resolved)
+}
+
+/// Returns the allowed root that contains the path, if any
+pub fn containing_root<'a>(path: &str, roots: &'a [PathBuf]) -> Option<&'a PathBuf> {
+    let resolved = resolve_path(Path::new(path)).ok()?;
+
+    roots.iter().find(|root| {
+        root.canonicalize()
+            .map(|canonical| resolved != canonical && resolved.starts_with(&canonical))
+            .unwrap_or(false)
+    })
+}
+
+// Makes a path absolute, collapses `.`/`..` and canonicalizes its longest existing ancestor
+fn resolve_path(path: &Path) -> Result<PathBuf> {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .context("Failed to determine current directory")?
+            .join(path)
+    };
+
+    let normalized = normalize_lexically(&absolute);
+
+    // Walk up until we find a part of the path that exists, then re-append the rest
+    let mut existing = normalized.as_path();
+    let mut remainder = Vec::new();
+
+    while !existing.exists() {
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                remainder.push(name.to_os_string());
+                existing = parent;
+            },
+            _ => break,
+        }
+    }
+
+    let mut resolved = existing.canonicalize()
+        .with_context(|| format!("Failed to resolve path: {}", path.display()))?;
+
+    for name in remainder.into_iter().rev() {
+        resolved.push(name);
+    }
+
+    Ok(resolved)
+}
+
+// Collapses `.` and `..` components without touching the filesystem
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => {
+                normalized.pop();
+            },
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_root() -> PathBuf {
+        let root = std::env::temp_dir().join(format!("proprompter-sandbox-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        root
+    }
+
+    #[test]
+    fn accepts_paths_inside_the_root() {
+        let root = scratch_root();
+        let path = root.join("src").join("new.rs").to_string_lossy().to_string();
+
+        let resolved = resolve_within_roots(&path, std::slice::from_ref(&root));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(resolved.is_ok());
+    }
+
+    #[test]
+    fn rejects_parent_traversal() {
+        let root = scratch_root();
+        let path = root.join("src").join("..").join("..").join("escaped.txt").to_string_lossy().to_string();
+
+        let resolved = resolve_within_roots(&path, std::slice::from_ref(&root));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(resolved.unwrap_err().to_string().contains("outside workspace"));
+    }
+
+    #[test]
+    fn rejects_absolute_paths_elsewhere() {
+        let root = scratch_root();
+        let outside = std::env::temp_dir().join("passwd").to_string_lossy().to_string();
+
+        let resolved = resolve_within_roots(&outside, std::slice::from_ref(&root));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(resolved.is_err());
+    }
+
+    #[test]
+    fn rejects_the_root_itself() {
+        let root = scratch_root();
+
+        let resolved = resolve_within_roots(&root.to_string_lossy(), std::slice::from_ref(&root));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(resolved.is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_out_of_the_root() {
+        let root = scratch_root();
+        let outside = scratch_root();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+
+        let path = root.join("link").join("file.txt").to_string_lossy().to_string();
+        let resolved = resolve_within_roots(&path, std::slice::from_ref(&root));
+        std::fs::remove_dir_all(&root).unwrap();
+        std::fs::remove_dir_all(&outside).unwrap();
+
+        assert!(resolved.is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::task::JoinSet;
+
+use crate::config::IoLimits;
+use crate::error;
+use crate::fs::reader::looks_binary;
+
+// Long lines (minified files) are cut down in results
+const MAX_LINE_TEXT_CHARS: usize = 500;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchMatch {
+    pub path: String,
+    pub line_number: usize,
+    pub line_text: String,
+}
+
+/// Searches the given files for a plain string or regex, returning at most `max_matches` hits.
+/// Files are read concurrently, each holding one of the shared I/O permits.
+pub async fn search_in_files(paths: &[String], query: &str, is_regex: bool, max_matches: usize, limits: &IoLimits) -> Result<Vec<SearchMatch>> {
+    if query.is_empty() {
+        return Err(error::invalid_input("Search query is empty"));
+    }
+
+    let pattern = if is_regex { query.to_string() } else { regex::escape(query) };
+    let matcher = Regex::new(&pattern)
+        .with_context(|| format!("Invalid search pattern: {}", query))?;
+    let matcher = Arc::new(matcher);
+
+    let mut tasks = JoinSet::new();
+
+    for (index, path) in paths.iter().enumerate() {
+        let path = path.clone();
+        let matcher = Arc::clone(&matcher);
+        let limits = limits.clone();
+
+        tasks.spawn(async move {
+            let _permit = limits.acquire_io_permit().await.ok()?;
+            let bytes = tokio::fs::read(&path).await.ok()?;
+
+            if looks_binary(&bytes) {
+                return None;
+            }
+
+            let content = String::from_utf8_lossy(&bytes);
+            let matches = find_matches(&path, &content, &matcher, max_matches);
+
+            Some((index, matches))
+        });
+    }
+
+    let mut per_file = Vec::new();
+
+    while let Some(result) = tasks.join_next().await {
+        if let Some(file_matches) = result.context("Search task failed")? {
+            per_file.push(file_matches);
+        }
+    }
+
+    // Report matches in the order the files were given
+    per_file.sort_by_key(|(index, _)| *index);
+
+    let mut matches: Vec<SearchMatch> = per_file.into_iter()
+        .flat_map(|(_, file_matches)| file_matches)
+        .collect();
+    matches.truncate(max_matches);
+
+    Ok(matches)
+}
+
+fn find_matches(path: &str, content: &str, matcher: &Regex, limit: usize) -> Vec<SearchMatch> {
+    content.lines()
+        .enumerate()
+        .filter(|(_, line)| matcher.is_match(line))
+        .take(limit)
+        .map(|(index, line)| SearchMatch {
+            path: path.to_string(),
+            line_number: index + 1,
+            line_text: line.trim_end_matches('\r').chars().take(MAX_LINE_TEXT_CHARS).collect(),
+        })
+        .collect()
+}
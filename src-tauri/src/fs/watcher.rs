@@ -1,12 +1,52 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use notify::{Watcher, RecursiveMode};
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tauri::{AppHandle, Emitter};
 
+use crate::fs::writer::set_aside_corrupt_file_blocking;
+
+// Quiet period after the last change before a batch is emitted
+const BATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+// Files included in the current prompt, keyed by canonical path
+type Selection = Arc<Mutex<SelectionSnapshot>>;
+
+/// Selected files keyed by canonical path, so watcher events match however the path was written
+pub type SelectionSnapshot = HashMap<PathBuf, SelectedFile>;
+
+#[derive(Debug, Clone)]
+pub struct SelectedFile {
+    // The path as the frontend gave it, reported back in `prompt-stale`
+    path: String,
+    // Content hash when last seen; None when the file couldn't be read
+    hash: Option<u64>,
+}
+
+/// Hashes the selected files off the async runtime; pass the result to `set_selection`
+pub async fn snapshot_selection(paths: Vec<String>) -> Result<SelectionSnapshot> {
+    tokio::task::spawn_blocking(move || {
+        paths.into_iter()
+            .map(|path| {
+                let hash = content_hash(&path);
+                (canonical_key(&path), SelectedFile { path, hash })
+            })
+            .collect()
+    })
+    .await
+    .context("Selection hashing task failed")
+}
+
 pub struct FileSystemWatcher {
     watcher: Option<notify::RecommendedWatcher>,
     paths: Arc<Mutex<Vec<String>>>,
+    selection: Selection,
 }
 
 impl FileSystemWatcher {
@@ -14,12 +54,22 @@ impl FileSystemWatcher {
         Ok(FileSystemWatcher {
             watcher: None,
             paths: Arc::new(Mutex::new(Vec::new())),
+            selection: Arc::new(Mutex::new(HashMap::new())),
         })
     }
 
     pub fn start(&mut self, app_handle: AppHandle) -> Result<()> {
+        self.restore_paths()?;
+
         let paths = Arc::clone(&self.paths);
 
+        // Changes are also collected into debounced batches on a separate thread, which
+        // exits once the watcher (and with it the sender) is dropped
+        let (batch_sender, batch_receiver) = mpsc::channel();
+        let batch_handle = app_handle.clone();
+        let selection = Arc::clone(&self.selection);
+        std::thread::spawn(move || emit_batches(batch_receiver, batch_handle, selection));
+
         let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
             match res {
                 Ok(event) => {
@@ -29,6 +79,8 @@ impl FileSystemWatcher {
                         let path_str = event.paths.first().map(|p| p.to_string_lossy().to_string());
 
                         if let Some(path) = path_str {
+                            let _ = batch_sender.send(path.clone());
+
                             // Emit an event that the frontend can listen for
                             let _ = app_handle.emit("file-system-change", path);
                         }
@@ -40,9 +92,9 @@ impl FileSystemWatcher {
             }
         })?;
 
-        // Watch all the registered paths
+        // Watch the registered paths; nested ones are already covered recursively by an ancestor
         let paths_guard = paths.lock().unwrap();
-        for path in paths_guard.iter() {
+        for path in watch_roots(&paths_guard) {
             let _ = watcher.watch(Path::new(path), RecursiveMode::Recursive);
         }
 
@@ -55,27 +107,58 @@ impl FileSystemWatcher {
         let mut paths_guard = self.paths.lock().unwrap();
 
         // Check if path is already watched
-        if !paths_guard.contains(&path.to_string()) {
-            paths_guard.push(path.to_string());
+        if paths_guard.contains(&path.to_string()) {
+            return Ok(());
+        }
 
-            // If watcher is active, start watching the new path
-            if let Some(watcher) = &mut self.watcher {
+        // Registered paths stay in the list even when covered by an ancestor, so removing
+        // the ancestor later can hand the watch back to them
+        let covered = is_covered(path, &paths_guard);
+        let redundant: Vec<String> = if covered {
+            Vec::new()
+        } else {
+            watch_roots(&paths_guard).into_iter()
+                .filter(|p| is_descendant(p, path))
+                .cloned()
+                .collect()
+        };
+
+        paths_guard.push(path.to_string());
+
+        // If watcher is active, watch the new path and drop watches it now covers
+        if let Some(watcher) = &mut self.watcher {
+            if !covered {
                 watcher.watch(Path::new(path), RecursiveMode::Recursive)?;
+
+                for descendant in &redundant {
+                    let _ = watcher.unwatch(Path::new(descendant));
+                }
             }
         }
 
+        save_watched_paths(&paths_guard)?;
+
         Ok(())
     }
 
     pub fn remove_path(&mut self, path: &str) -> Result<()> {
         let mut paths_guard = self.paths.lock().unwrap();
 
+        let was_root = paths_guard.contains(&path.to_string()) && !is_covered(path, &paths_guard);
+
         // Remove the path from the list
         paths_guard.retain(|p| p != path);
+        save_watched_paths(&paths_guard)?;
 
-        // If watcher is active, unwatch the path
+        // If watcher is active, unwatch the path and re-watch descendants it was covering
         if let Some(watcher) = &mut self.watcher {
-            watcher.unwatch(Path::new(path))?;
+            if was_root {
+                watcher.unwatch(Path::new(path))?;
+
+                for descendant in watch_roots(&paths_guard).into_iter().filter(|p| is_descendant(p, path)) {
+                    watcher.watch(Path::new(descendant), RecursiveMode::Recursive)?;
+                }
+            }
         }
 
         Ok(())
@@ -84,4 +167,194 @@ impl FileSystemWatcher {
     pub fn stop(&mut self) {
         self.watcher = None;
     }
+
+    pub fn list_paths(&self) -> Vec<String> {
+        self.paths.lock().unwrap().clone()
+    }
+
+    /// Sets the files the current prompt was generated from; batches touching them emit `prompt-stale`.
+    /// An empty snapshot clears the selection.
+    pub fn set_selection(&mut self, snapshot: SelectionSnapshot) {
+        *self.selection.lock().unwrap() = snapshot;
+    }
+
+    // Merges the paths saved by a previous session, dropping any that no longer exist
+    fn restore_paths(&mut self) -> Result<()> {
+        let mut paths_guard = self.paths.lock().unwrap();
+
+        let saved = load_watched_paths()?;
+
+        for path in saved {
+            if !paths_guard.contains(&path) {
+                paths_guard.push(path);
+            }
+        }
+
+        paths_guard.retain(|p| Path::new(p).exists());
+        save_watched_paths(&paths_guard)
+    }
+}
+
+// Collects changed paths until nothing arrives for BATCH_DEBOUNCE, then emits them as one
+// `file-system-change-batch` and a `prompt-stale` listing selected files whose content changed
+fn emit_batches(receiver: Receiver<String>, app_handle: AppHandle, selection: Selection) {
+    while let Ok(first) = receiver.recv() {
+        let mut batch = vec![first];
+
+        loop {
+            match receiver.recv_timeout(BATCH_DEBOUNCE) {
+                Ok(path) => {
+                    if !batch.contains(&path) {
+                        batch.push(path);
+                    }
+                },
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        let stale = stale_selected_paths(&batch, &selection);
+
+        let _ = app_handle.emit("file-system-change-batch", batch);
+
+        if !stale.is_empty() {
+            let _ = app_handle.emit("prompt-stale", stale);
+        }
+    }
+}
+
+// Re-reads only the changed files that are selected, keeping those whose content really differs
+fn stale_selected_paths(changed: &[String], selection: &Selection) -> Vec<String> {
+    let mut selection = selection.lock().unwrap();
+    let mut stale = Vec::new();
+
+    for path in changed {
+        if let Some(selected) = selection.get_mut(&canonical_key(path)) {
+            let current = content_hash(path);
+
+            if current != selected.hash {
+                selected.hash = current;
+                stale.push(selected.path.clone());
+            }
+        }
+    }
+
+    stale
+}
+
+// Deleted files can't be canonicalized; their path is used as given
+fn canonical_key(path: &str) -> PathBuf {
+    Path::new(path).canonicalize().unwrap_or_else(|_| PathBuf::from(path))
+}
+
+// None when the file can't be read (e.g. it was deleted)
+fn content_hash(path: &str) -> Option<u64> {
+    let bytes = std::fs::read(path).ok()?;
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+// True when `path` is strictly inside `ancestor` (compared by path components, not string prefix)
+fn is_descendant(path: &str, ancestor: &str) -> bool {
+    let path = Path::new(path);
+    path != Path::new(ancestor) && path.starts_with(ancestor)
+}
+
+// True when another registered path already watches `path` recursively
+fn is_covered(path: &str, paths: &[String]) -> bool {
+    paths.iter().any(|p| is_descendant(path, p))
+}
+
+// The registered paths that actually need an OS watch
+fn watch_roots(paths: &[String]) -> Vec<&String> {
+    paths.iter().filter(|p| !is_covered(p, paths)).collect()
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct WatcherState {
+    #[serde(default)]
+    paths: Vec<String>,
+}
+
+// Get the path to the persisted watcher state
+fn get_watcher_state_path() -> Result<PathBuf> {
+    let app_dir = directories::ProjectDirs::from("com", "mohilcode", "proprompter")
+        .context("Failed to determine app directories")?
+        .data_dir()
+        .to_path_buf();
+
+    if !app_dir.exists() {
+        std::fs::create_dir_all(&app_dir)
+            .context("Failed to create app data directory")?;
+    }
+
+    Ok(app_dir.join("watcher_state.json"))
+}
+
+fn load_watched_paths() -> Result<Vec<String>> {
+    let file_path = get_watcher_state_path()?;
+
+    if !file_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = std::fs::read_to_string(&file_path)
+        .context("Failed to read watcher state file")?;
+
+    match serde_json::from_str::<WatcherState>(&content) {
+        Ok(state) => Ok(state.paths),
+        Err(err) => {
+            // Keep the unparseable file for the user instead of overwriting it with the next save
+            set_aside_corrupt_file_blocking(&file_path, &format!("Failed to parse watcher state file: {}", err))?;
+
+            Ok(Vec::new())
+        },
+    }
+}
+
+fn save_watched_paths(paths: &[String]) -> Result<()> {
+    let file_path = get_watcher_state_path()?;
+
+    let content = serde_json::to_string_pretty(&WatcherState { paths: paths.to_vec() })
+        .context("Failed to serialize watcher state")?;
+
+    std::fs::write(file_path, content)
+        .context("Failed to write watcher state file")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owned(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn descendants_are_compared_by_component() {
+        assert!(is_descendant("/work/app/src", "/work/app"));
+        assert!(!is_descendant("/work/app", "/work/app"));
+        assert!(!is_descendant("/work/application", "/work/app"));
+        assert!(!is_descendant("/work", "/work/app"));
+    }
+
+    #[test]
+    fn nested_paths_share_their_ancestors_watch() {
+        let paths = owned(&["/work/app/src", "/work/app", "/work/app/src/lib", "/work/other"]);
+
+        assert_eq!(watch_roots(&paths), ["/work/app", "/work/other"]);
+        assert!(is_covered("/work/app/src", &paths));
+        assert!(!is_covered("/work/app", &paths));
+    }
+
+    #[test]
+    fn removing_an_ancestor_hands_the_watch_back() {
+        let mut paths = owned(&["/work/app", "/work/app/src", "/work/app/src/lib", "/work/app/docs"]);
+        paths.retain(|p| p != "/work/app");
+
+        assert_eq!(watch_roots(&paths), ["/work/app/src", "/work/app/docs"]);
+    }
 }
@@ -1,7 +1,208 @@
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use uuid::Uuid;
 
+use crate::error;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn native() -> Self {
+        if cfg!(windows) { LineEnding::Crlf } else { LineEnding::Lf }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
+/// How line endings are handled when writing a file
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum EolMode {
+    // Match the existing file's dominant style; new files use the platform native style
+    #[default]
+    Auto,
+    Lf,
+    Crlf,
+}
+
+/// Overall line ending style, serialized as "lf", "crlf" or "mixed"
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EolStyle {
+    Lf,
+    Crlf,
+    Mixed,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LineEndings {
+    // None when there are no line breaks
+    pub style: Option<EolStyle>,
+    pub crlf_count: usize,
+    // Bare `\n`s, not counting the ones in `\r\n`
+    pub lf_count: usize,
+}
+
+impl LineEndings {
+    pub fn count(content: &[u8]) -> Self {
+        let newlines = content.iter().filter(|&&b| b == b'\n').count();
+        let crlf_count = content.windows(2).filter(|pair| pair == b"\r\n").count();
+        let lf_count = newlines - crlf_count;
+
+        let style = match (crlf_count, lf_count) {
+            (0, 0) => None,
+            (_, 0) => Some(EolStyle::Crlf),
+            (0, _) => Some(EolStyle::Lf),
+            _ => Some(EolStyle::Mixed),
+        };
+
+        LineEndings {
+            style,
+            crlf_count,
+            lf_count,
+        }
+    }
+
+    /// The more common ending (LF on a tie), or None without line breaks
+    pub fn dominant(&self) -> Option<LineEnding> {
+        if self.crlf_count == 0 && self.lf_count == 0 {
+            None
+        } else if self.crlf_count > self.lf_count {
+            Some(LineEnding::Crlf)
+        } else {
+            Some(LineEnding::Lf)
+        }
+    }
+}
+
+/// Returns the dominant line ending of the content, or None if it has no line breaks
+pub fn detect_line_ending(content: &str) -> Option<LineEnding> {
+    LineEndings::count(content.as_bytes()).dominant()
+}
+
+/// Scans a whole file and reports its line ending style, including whether it mixes LF and CRLF
+pub async fn detect_line_endings(path: &str) -> Result<LineEndings> {
+    let content = tokio::fs::read(path)
+        .await
+        .with_context(|| format!("Failed to read file: {}", path))?;
+
+    Ok(LineEndings::count(&content))
+}
+
+/// Rewrites every line break in the content to the given style
+pub fn normalize_line_endings(content: &str, ending: LineEnding) -> String {
+    let lf_only = content.replace("\r\n", "\n");
+
+    match ending {
+        LineEnding::Lf => lf_only,
+        LineEnding::Crlf => lf_only.replace('\n', ending.as_str()),
+    }
+}
+
+/// Strips spaces and tabs from line ends and/or ensures the content ends with exactly one newline.
+/// Line breaks themselves (LF or CRLF) are kept as they are.
+pub fn tidy_whitespace(content: &str, trim_trailing: bool, final_newline: bool) -> String {
+    let mut tidied = if trim_trailing {
+        content.split('\n')
+            .map(|line| match line.strip_suffix('\r') {
+                Some(line) => format!("{}\r", line.trim_end_matches([' ', '\t'])),
+                None => line.trim_end_matches([' ', '\t']).to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else {
+        content.to_string()
+    };
+
+    if final_newline && !tidied.is_empty() {
+        let ending = match detect_line_ending(&tidied) {
+            Some(LineEnding::Crlf) => "\r\n",
+            _ => "\n",
+        };
+
+        tidied.truncate(tidied.trim_end_matches(['\r', '\n']).len());
+        tidied.push_str(ending);
+    }
+
+    tidied
+}
+
+/// A data file that failed to parse and was moved aside
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecoveredFile {
+    pub path: String,
+    // Where the unparseable contents were moved
+    pub moved_to: String,
+    // The parse error
+    pub reason: String,
+}
+
+// Recoveries the UI hasn't picked up yet
+static RECOVERED_FILES: Mutex<Vec<RecoveredFile>> = Mutex::new(Vec::new());
+
+/// Renames a file that failed to parse to `<name>.corrupt-<timestamp>` next to it, so the app
+/// can start fresh without losing the data, and records it for `take_recovered_files`.
+/// Returns the new path.
+pub async fn set_aside_corrupt_file(path: &Path, reason: &str) -> Result<PathBuf> {
+    let corrupt_path = corrupt_file_path(path);
+
+    tokio::fs::rename(path, &corrupt_path)
+        .await
+        .with_context(|| format!("Failed to move aside corrupt file: {}", path.display()))?;
+
+    record_recovered_file(path, &corrupt_path, reason);
+
+    Ok(corrupt_path)
+}
+
+/// `set_aside_corrupt_file` for synchronous callers, such as state loaded from a sync command
+pub fn set_aside_corrupt_file_blocking(path: &Path, reason: &str) -> Result<PathBuf> {
+    let corrupt_path = corrupt_file_path(path);
+
+    std::fs::rename(path, &corrupt_path)
+        .with_context(|| format!("Failed to move aside corrupt file: {}", path.display()))?;
+
+    record_recovered_file(path, &corrupt_path, reason);
+
+    Ok(corrupt_path)
+}
+
+fn corrupt_file_path(path: &Path) -> PathBuf {
+    let file_name = path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    path.with_file_name(format!(
+        "{}.corrupt-{}",
+        file_name,
+        chrono::Utc::now().format("%Y%m%dT%H%M%S")
+    ))
+}
+
+fn record_recovered_file(path: &Path, corrupt_path: &Path, reason: &str) {
+    RECOVERED_FILES.lock().unwrap().push(RecoveredFile {
+        path: path.to_string_lossy().to_string(),
+        moved_to: corrupt_path.to_string_lossy().to_string(),
+        reason: reason.to_string(),
+    });
+}
+
+/// Returns the files set aside since the last call, so each recovery is reported once
+pub fn take_recovered_files() -> Vec<RecoveredFile> {
+    std::mem::take(&mut *RECOVERED_FILES.lock().unwrap())
+}
+
 /// Writes content to a file, creating parent directories if needed
 pub async fn write_file(path: &str, content: &str) -> Result<()> {
     let path = Path::new(path);
@@ -22,21 +223,79 @@ pub async fn write_file(path: &str, content: &str) -> Result<()> {
     Ok(())
 }
 
-/// Creates a backup of a file before modifying it
-pub async fn create_backup(path: &str) -> Result<PathBuf> {
-    let source_path = Path::new(path);
+/// Where backups are stored
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum BackupLocation {
+    // The shared app data directory
+    #[default]
+    Global,
+    // A `.proprompter/backups` directory inside the workspace folder being edited
+    Project,
+}
 
-    if !source_path.exists() {
-        anyhow::bail!("File does not exist: {}", path);
+/// Writes content to a file, normalizing its line endings according to `mode`
+pub async fn write_file_with_eol(path: &str, content: &str, mode: EolMode) -> Result<()> {
+    let target = match mode {
+        EolMode::Lf => Some(LineEnding::Lf),
+        EolMode::Crlf => Some(LineEnding::Crlf),
+        EolMode::Auto => {
+            if Path::new(path).is_file() {
+                // Preserve the existing file's style; leave content alone if it has no line breaks
+                let existing = tokio::fs::read(path)
+                    .await
+                    .with_context(|| format!("Failed to read file: {}", path))?;
+                LineEndings::count(&existing).dominant()
+            } else {
+                Some(LineEnding::native())
+            }
+        }
+    };
+
+    match target {
+        Some(ending) => write_file(path, &normalize_line_endings(content, ending)).await,
+        None => write_file(path, content).await,
     }
+}
 
-    // Create a backup directory if it doesn't exist
+#[derive(Debug, Clone)]
+pub struct CreatedBackup {
+    pub path: PathBuf,
+    // SHA-256 of the backup contents, checked again before restoring
+    pub checksum: String,
+    // True when an identical backup already existed and was reused instead of copied
+    pub deduplicated: bool,
+}
+
+// Subdirectory of the backup dir holding file backups named by their SHA-256
+const BACKUP_OBJECTS_DIR: &str = "objects";
+
+/// Where backups without a project root are stored, in the app data dir
+pub fn global_backup_dir() -> Result<PathBuf> {
     let app_dir = directories::ProjectDirs::from("com", "mohilcode", "proprompter")
         .context("Failed to determine app directories")?
         .data_dir()
         .to_path_buf();
 
-    let backup_dir = app_dir.join("backups");
+    Ok(app_dir.join("backups"))
+}
+
+/// Creates a backup of a file (or a whole directory tree) before modifying it.
+/// With a `target_root` the backup goes to `<root>/.proprompter/backups`, otherwise to the app data dir.
+/// File backups are content-addressed (`objects/<sha256>`), so backing up unchanged content again
+/// reuses the existing copy; the undo history records which file each object came from.
+/// Directory trees are copied under a readable `{timestamp}-{uuid}-{path}` name.
+pub async fn create_backup(path: &str, target_root: Option<&Path>) -> Result<CreatedBackup> {
+    let source_path = Path::new(path);
+
+    if !source_path.exists() {
+        return Err(error::not_found(format!("File does not exist: {}", path)));
+    }
+
+    // Create a backup directory if it doesn't exist
+    let backup_dir = match target_root {
+        Some(root) => root.join(".proprompter").join("backups"),
+        None => global_backup_dir()?,
+    };
 
     if !backup_dir.exists() {
         tokio::fs::create_dir_all(&backup_dir)
@@ -44,34 +303,302 @@ pub async fn create_backup(path: &str) -> Result<PathBuf> {
             .context("Failed to create backup directory")?;
     }
 
-    // Generate a unique backup filename
-    let uuid = Uuid::new_v4();
-    let file_name = source_path.file_name()
-        .context("Failed to get file name")?
-        .to_string_lossy();
+    if !source_path.is_dir() {
+        return create_file_backup(source_path, &backup_dir.join(BACKUP_OBJECTS_DIR)).await;
+    }
 
-    let backup_path = backup_dir.join(format!("{}-{}", uuid, file_name));
+    let backup_path = backup_dir.join(directory_backup_name(source_path, target_root)?);
 
-    // Copy the file to the backup location
-    tokio::fs::copy(source_path, &backup_path)
-        .await
+    copy_dir_all(source_path, &backup_path)
         .with_context(|| format!("Failed to create backup of {}", path))?;
 
-    Ok(backup_path)
+    let checksum = compute_checksum(&backup_path).await?;
+
+    Ok(CreatedBackup {
+        path: backup_path,
+        checksum,
+        deduplicated: false,
+    })
 }
 
-/// Restores a file from backup
-pub async fn restore_from_backup(backup_path: &Path, destination_path: &str) -> Result<()> {
-    let dest_path = Path::new(destination_path);
+// Stores the file as `<objects_dir>/<sha256>`, reusing an intact existing object with that hash
+async fn create_file_backup(source_path: &Path, objects_dir: &Path) -> Result<CreatedBackup> {
+    let checksum = compute_checksum(source_path).await?;
+    let backup_path = objects_dir.join(&checksum);
+
+    // An object that no longer matches its name (edited or truncated) is replaced
+    if backup_path.is_file() && compute_checksum(&backup_path).await.ok().as_ref() == Some(&checksum) {
+        return Ok(CreatedBackup {
+            path: backup_path,
+            checksum,
+            deduplicated: true,
+        });
+    }
+
+    tokio::fs::create_dir_all(objects_dir)
+        .await
+        .context("Failed to create backup directory")?;
+
+    // Copy under a temporary name first so a partial copy never sits at the object's path
+    let temp_path = objects_dir.join(format!("{}.{}.tmp", checksum, Uuid::new_v4()));
+
+    tokio::fs::copy(source_path, &temp_path)
+        .await
+        .with_context(|| format!("Failed to create backup of {}", source_path.display()))?;
+
+    // The file may have changed between hashing and copying, so the copy is named by its own hash
+    let checksum = compute_checksum(&temp_path).await?;
+    let backup_path = objects_dir.join(&checksum);
+
+    tokio::fs::rename(&temp_path, &backup_path)
+        .await
+        .with_context(|| format!("Failed to create backup of {}", source_path.display()))?;
+
+    Ok(CreatedBackup {
+        path: backup_path,
+        checksum,
+        deduplicated: false,
+    })
+}
 
+// Longest sanitized path kept in a backup name; longer paths keep their tail, which holds the directory name
+const MAX_BACKUP_PATH_CHARS: usize = 120;
+
+// `{timestamp}-{uuid}-{sanitized_path}`: sorts by time and shows which directory it was, while the
+// uuid keeps two backups in the same second apart. File backups don't use this; they are named by hash.
+fn directory_backup_name(source_path: &Path, target_root: Option<&Path>) -> Result<String> {
+    source_path.file_name().context("Failed to get directory name")?;
+
+    // Relative to the project when backups live inside it, otherwise the full path
+    let shown_path = target_root
+        .and_then(|root| source_path.strip_prefix(root).ok())
+        .unwrap_or(source_path);
+
+    let sanitized: Vec<char> = shown_path.to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' { c } else { '_' })
+        .collect();
+    let sanitized: String = sanitized[sanitized.len().saturating_sub(MAX_BACKUP_PATH_CHARS)..]
+        .iter()
+        .collect();
+
+    Ok(format!(
+        "{}-{}-{}",
+        chrono::Utc::now().format("%Y%m%dT%H%M%S"),
+        Uuid::new_v4(),
+        sanitized.trim_start_matches('_'),
+    ))
+}
+
+/// Checks that a backup still exists and, when a checksum was recorded, that its contents match
+pub async fn verify_backup(backup_path: &Path, expected_checksum: Option<&str>) -> Result<()> {
     if !backup_path.exists() {
-        anyhow::bail!("Backup file does not exist: {}", backup_path.display());
+        return Err(error::not_found(format!("Backup file does not exist: {}", backup_path.display())));
+    }
+
+    if let Some(expected) = expected_checksum {
+        let actual = compute_checksum(backup_path).await?;
+
+        if actual != expected {
+            anyhow::bail!(
+                "Backup is corrupted (checksum mismatch), refusing to restore: {}",
+                backup_path.display()
+            );
+        }
     }
 
+    Ok(())
+}
+
+/// Restores a file from backup. Backups recorded without a checksum are restored unverified.
+pub async fn restore_from_backup(backup_path: &Path, destination_path: &str, expected_checksum: Option<&str>) -> Result<()> {
+    let dest_path = Path::new(destination_path);
+
+    verify_backup(backup_path, expected_checksum).await?;
+
     // Copy the backup back to the original location
-    tokio::fs::copy(backup_path, dest_path)
-        .await
-        .with_context(|| format!("Failed to restore backup to {}", destination_path))?;
+    if backup_path.is_dir() {
+        copy_dir_all(backup_path, dest_path)
+            .with_context(|| format!("Failed to restore backup to {}", destination_path))?;
+    } else {
+        tokio::fs::copy(backup_path, dest_path)
+            .await
+            .with_context(|| format!("Failed to restore backup to {}", destination_path))?;
+    }
+
+    Ok(())
+}
+
+// SHA-256 of a file, or of every relative path and file body in a directory tree
+async fn compute_checksum(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+
+    if path.is_dir() {
+        let entries = walkdir::WalkDir::new(path)
+            .min_depth(1)
+            .sort_by_file_name();
+
+        for entry in entries {
+            let entry = entry.context("Failed to read directory entry")?;
+            let relative = entry.path().strip_prefix(path)?;
+
+            hasher.update(relative.to_string_lossy().as_bytes());
+
+            if entry.file_type().is_file() {
+                let bytes = tokio::fs::read(entry.path())
+                    .await
+                    .with_context(|| format!("Failed to read {}", entry.path().display()))?;
+                hasher.update(&bytes);
+            }
+        }
+    } else {
+        let bytes = tokio::fs::read(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        hasher.update(&bytes);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// Recursively copies a directory tree, creating the destination as needed
+fn copy_dir_all(source: &Path, destination: &Path) -> Result<()> {
+    std::fs::create_dir_all(destination)
+        .with_context(|| format!("Failed to create directory: {}", destination.display()))?;
+
+    for entry in walkdir::WalkDir::new(source).min_depth(1) {
+        let entry = entry.context("Failed to read directory entry")?;
+        let relative = entry.path().strip_prefix(source)?;
+        let target = destination.join(relative);
+
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&target)
+                .with_context(|| format!("Failed to create directory: {}", target.display()))?;
+        } else {
+            std::fs::copy(entry.path(), &target)
+                .with_context(|| format!("Failed to copy {}", entry.path().display()))?;
+        }
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_lf_content_to_crlf_and_back() {
+        let crlf = normalize_line_endings("a\nb\r\nc\n", LineEnding::Crlf);
+
+        assert_eq!(crlf, "a\r\nb\r\nc\r\n");
+        assert_eq!(normalize_line_endings(&crlf, LineEnding::Lf), "a\nb\nc\n");
+    }
+
+    #[tokio::test]
+    async fn auto_eol_keeps_the_file_style() {
+        let path = std::env::temp_dir().join(format!("proprompter-eol-{}.txt", uuid::Uuid::new_v4()));
+        let path_str = path.to_string_lossy().to_string();
+        std::fs::write(&path, "one\r\ntwo\r\n").unwrap();
+
+        write_file_with_eol(&path_str, "one\ntwo\nthree\n", EolMode::Auto).await.unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(written, "one\r\ntwo\r\nthree\r\n");
+    }
+
+    #[test]
+    fn tidy_trims_lf_lines() {
+        assert_eq!(tidy_whitespace("a  \nb\t\nc", true, false), "a\nb\nc");
+    }
+
+    #[test]
+    fn tidy_keeps_crlf_line_breaks() {
+        assert_eq!(tidy_whitespace("a \r\nb\t\r\n", true, false), "a\r\nb\r\n");
+        assert_eq!(tidy_whitespace("a\r\nb", false, true), "a\r\nb\r\n");
+    }
+
+    #[test]
+    fn tidy_leaves_empty_content_empty() {
+        assert_eq!(tidy_whitespace("", true, true), "");
+    }
+
+    #[test]
+    fn tidy_collapses_repeated_trailing_newlines() {
+        assert_eq!(tidy_whitespace("a\n\n\n", false, true), "a\n");
+        assert_eq!(tidy_whitespace("a\r\n\r\n", false, true), "a\r\n");
+        assert_eq!(tidy_whitespace("a \n \n", true, true), "a\n");
+    }
+
+    #[tokio::test]
+    async fn two_backups_of_the_same_path_get_distinct_names() {
+        let root = std::env::temp_dir().join(format!("proprompter-backup-{}", uuid::Uuid::new_v4()));
+        let dir = root.join("src").join("module");
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("lib.rs");
+        std::fs::write(&file, "one").unwrap();
+
+        let first_dir = create_backup(&dir.to_string_lossy(), Some(&root)).await.unwrap();
+        let second_dir = create_backup(&dir.to_string_lossy(), Some(&root)).await.unwrap();
+
+        let first_file = create_backup(&file.to_string_lossy(), Some(&root)).await.unwrap();
+        std::fs::write(&file, "two").unwrap();
+        let second_file = create_backup(&file.to_string_lossy(), Some(&root)).await.unwrap();
+
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_ne!(first_dir.path, second_dir.path);
+        let dir_name = first_dir.path.file_name().unwrap().to_string_lossy().to_string();
+        assert!(dir_name.ends_with("-src_module"), "{}", dir_name);
+
+        assert_ne!(first_file.path, second_file.path);
+    }
+
+    #[tokio::test]
+    async fn unchanged_file_backups_share_one_object() {
+        let root = std::env::temp_dir().join(format!("proprompter-dedup-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let file = root.join("lib.rs");
+        std::fs::write(&file, "same").unwrap();
+
+        let first = create_backup(&file.to_string_lossy(), Some(&root)).await.unwrap();
+        let second = create_backup(&file.to_string_lossy(), Some(&root)).await.unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(!first.deduplicated);
+        assert!(second.deduplicated);
+        assert_eq!(first.path, second.path);
+        assert_eq!(first.path.file_name().unwrap().to_string_lossy(), first.checksum);
+    }
+
+    #[tokio::test]
+    async fn corrupt_files_are_moved_aside_and_reported_once() {
+        let dir = std::env::temp_dir().join(format!("proprompter-corrupt-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("prompts.json");
+        std::fs::write(&path, "{ truncated").unwrap();
+
+        let state_path = dir.join("watcher_state.json");
+        std::fs::write(&state_path, "[").unwrap();
+
+        let moved_to = set_aside_corrupt_file(&path, "bad json").await.unwrap();
+        let state_moved_to = set_aside_corrupt_file_blocking(&state_path, "bad state").unwrap();
+        let recovered = take_recovered_files();
+        let kept = std::fs::read_to_string(&moved_to).unwrap();
+        let state_kept = std::fs::read_to_string(&state_moved_to).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let entry = recovered.iter().find(|r| r.path == path.to_string_lossy()).unwrap();
+        assert_eq!(entry.moved_to, moved_to.to_string_lossy());
+        assert_eq!(entry.reason, "bad json");
+        assert_eq!(kept, "{ truncated");
+        assert!(!path.exists());
+
+        let state_entry = recovered.iter().find(|r| r.path == state_path.to_string_lossy()).unwrap();
+        assert_eq!(state_entry.moved_to, state_moved_to.to_string_lossy());
+        assert_eq!(state_kept, "[");
+
+        assert!(take_recovered_files().iter().all(|r| r.path != path.to_string_lossy()));
+    }
+}
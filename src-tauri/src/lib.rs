@@ -4,14 +4,21 @@ pub mod xml;
 pub mod clipboard;
 pub mod undo;
 pub mod commands;
+pub mod error;
 pub mod workspace;
+pub mod config;
 
 use commands::*;
 use std::sync::Mutex;
+use std::sync::atomic::AtomicBool;
 
 // Add this struct for state management
 pub struct AppState {
     fs_watcher: Mutex<fs::watcher::FileSystemWatcher>,
+    // Set to stop a running estimate_tokens_streaming
+    token_estimate_cancelled: AtomicBool,
+    // Backend limits and the concurrency limiter shared by file operations
+    config: config::ConfigState,
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -22,6 +29,8 @@ pub fn run() {
 
     let state = AppState {
         fs_watcher: Mutex::new(fs_watcher),
+        token_estimate_cancelled: AtomicBool::new(false),
+        config: config::ConfigState::load(),
     };
 
     tauri::Builder::default()
@@ -31,44 +40,116 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .manage(state)  // Add state management
         .invoke_handler(tauri::generate_handler![
+            // Config commands
+            get_config,
+            set_config,
+
             // File system commands
             scan_directory,
+            scan_directories,
+            rescan_path,
+            filter_file_tree,
+            flatten_file_tree,
             read_file_content,
+            read_file_resolved,
+            read_file_safe,
+            read_file_with_encoding,
+            read_file_head,
+            read_file_tail,
+            read_file_base64,
+            selection_summary,
+            file_metadata,
+            detect_project_type,
+            common_root,
+            take_recovered_files,
 
             // Prompt commands
             get_prompts,
+            list_prompts_for_workspace,
             save_prompt,
+            update_prompt,
+            set_prompt_tags,
+            add_tag_to_prompts,
+            remove_tag_from_prompts,
             delete_prompt,
+            delete_prompts,
+            record_prompt_used,
+            list_prompts_by_usage,
+            search_prompts_with_snippets,
+            list_tags_with_counts,
+            get_prompt_history,
+            diff_prompt_versions,
+            render_prompt,
+            list_prompt_variables,
+            estimate_cost,
+            estimate_tokens_streaming,
+            cancel_token_estimate,
 
             // Copy mode commands
+            generate_prompt,
             generate_copy_content,
             copy_to_clipboard,
+            append_to_clipboard,
 
             // XML mode commands
             generate_xml_prompt,
             generate_xml_prompt_for_workspace, // Add this command
+            generate_json_prompt,
+            preview_file_map,
+            generate_xml_prompt_to_file,
+            save_prompt_output,
             parse_xml_response,
+            parse_json_response,
+            validate_response_format,
+            diff_file_change,
+            preview_modify_context,
             apply_xml_changes,
 
             // Undo commands
+            preview_undo_last_change,
             undo_last_change,
             undo_file_change,
+            list_change_sets,
+            verify_undo_integrity,
+            run_self_test,
+            export_undo_archive,
+            import_undo_archive,
+            restore_file_from_change_set,
 
             // Workspace commands
             list_workspaces,
             create_workspace,
+            create_workspace_from_path,
             update_workspace,
             delete_workspace,
             get_workspace,
+            touch_workspace,
+            reset_workspace_state,
+            list_recent_workspaces,
+            set_language_override,
+            pin_file,
+            unpin_file,
+            list_pinned_files,
             add_folder_to_workspace,
             remove_folder_from_workspace,
+            move_folder_between_workspaces,
+            sort_workspace_folders,
             update_folder,
+            validate_workspace,
+            prune_invalid_folders,
             get_all_files_in_workspace,
+            largest_files_in_workspace,
+            files_changed_since,
+            select_files_by_glob,
+            expand_selection_with_imports,
+            search_in_files,
 
             // File system watching commands
             start_watching_filesystem,
             watch_path,
             unwatch_path,
+            list_watched_paths,
+            set_prompt_selection,
             stop_watching_filesystem,
         ])
         .run(tauri::generate_context!())
@@ -1,9 +1,15 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use std::collections::HashMap;
 use uuid::Uuid;
 
+use crate::error;
 use super::storage::{load_prompts, save_prompts};
 
+// How many previous contents are kept per prompt
+const MAX_PROMPT_HISTORY: usize = 10;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PromptTag {
     pub id: String,
@@ -18,13 +24,58 @@ pub struct Prompt {
     pub tags: Vec<PromptTag>,
     pub created_at: i64,
     pub updated_at: i64,
+    // Scopes the prompt to one workspace; prompts without it are global
+    #[serde(default)]
+    pub workspace_id: Option<String>,
+    // Previous contents, oldest first, capped at MAX_PROMPT_HISTORY
+    #[serde(default)]
+    pub history: Vec<PromptVersion>,
+    // How many generated outputs have included this prompt
+    #[serde(default)]
+    pub usage_count: u64,
+    #[serde(default)]
+    pub last_used_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TagCount {
+    pub tag: PromptTag,
+    // Number of prompts carrying the tag
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PromptVersion {
+    pub content: String,
+    // When this content was saved
+    pub timestamp: i64,
 }
 
 pub async fn list_prompts() -> Result<Vec<Prompt>> {
     load_prompts().await
 }
 
-pub async fn add_prompt(title: &str, content: &str, tags: Vec<PromptTag>) -> Result<Prompt> {
+pub async fn get_prompt(id: &str) -> Result<Prompt> {
+    let prompts = load_prompts().await?;
+
+    let prompt = prompts.iter()
+        .find(|p| p.id == id)
+        .ok_or_else(|| error::not_found("Prompt not found"))?;
+
+    Ok(prompt.clone())
+}
+
+// Global prompts plus the ones scoped to the given workspace
+pub async fn list_prompts_for_workspace(workspace_id: &str) -> Result<Vec<Prompt>> {
+    let prompts = load_prompts().await?
+        .into_iter()
+        .filter(|p| p.workspace_id.as_deref().map(|id| id == workspace_id).unwrap_or(true))
+        .collect();
+
+    Ok(prompts)
+}
+
+pub async fn add_prompt(title: &str, content: &str, tags: Vec<PromptTag>, workspace_id: Option<&str>) -> Result<Prompt> {
     let mut prompts = load_prompts().await?;
 
     let now = chrono::Utc::now().timestamp();
@@ -36,6 +87,10 @@ pub async fn add_prompt(title: &str, content: &str, tags: Vec<PromptTag>) -> Res
         tags,
         created_at: now,
         updated_at: now,
+        workspace_id: workspace_id.map(|id| id.to_string()),
+        history: Vec::new(),
+        usage_count: 0,
+        last_used_at: None,
     };
 
     prompts.push(new_prompt.clone());
@@ -44,11 +99,12 @@ pub async fn add_prompt(title: &str, content: &str, tags: Vec<PromptTag>) -> Res
     Ok(new_prompt)
 }
 
-pub async fn update_prompt(id: &str, title: Option<&str>, content: Option<&str>, tags: Option<Vec<PromptTag>>) -> Result<Prompt> {
+// `workspace_id` of `Some(None)` makes the prompt global again
+pub async fn update_prompt(id: &str, title: Option<&str>, content: Option<&str>, tags: Option<Vec<PromptTag>>, workspace_id: Option<Option<&str>>) -> Result<Prompt> {
     let mut prompts = load_prompts().await?;
 
     let prompt_index = prompts.iter().position(|p| p.id == id)
-        .ok_or_else(|| anyhow::anyhow!("Prompt not found"))?;
+        .ok_or_else(|| error::not_found("Prompt not found"))?;
 
     let now = chrono::Utc::now().timestamp();
 
@@ -58,13 +114,28 @@ pub async fn update_prompt(id: &str, title: Option<&str>, content: Option<&str>,
     }
 
     if let Some(new_content) = content {
-        prompts[prompt_index].content = new_content.to_string();
+        let prompt = &mut prompts[prompt_index];
+
+        // Keep the replaced content so edits can be compared later
+        if prompt.content != new_content {
+            prompt.history.push(PromptVersion {
+                content: std::mem::replace(&mut prompt.content, new_content.to_string()),
+                timestamp: prompt.updated_at,
+            });
+
+            let excess = prompt.history.len().saturating_sub(MAX_PROMPT_HISTORY);
+            prompt.history.drain(..excess);
+        }
     }
 
     if let Some(new_tags) = tags {
         prompts[prompt_index].tags = new_tags;
     }
 
+    if let Some(new_workspace_id) = workspace_id {
+        prompts[prompt_index].workspace_id = new_workspace_id.map(|id| id.to_string());
+    }
+
     prompts[prompt_index].updated_at = now;
 
     let updated_prompt = prompts[prompt_index].clone();
@@ -74,6 +145,146 @@ pub async fn update_prompt(id: &str, title: Option<&str>, content: Option<&str>,
     Ok(updated_prompt)
 }
 
+// Replaces a prompt's tags. Tags live on the prompts themselves (there is no separate tag
+// store to check ids against), so this only rejects blank and duplicate ids.
+pub async fn set_prompt_tags(id: &str, tags: Vec<PromptTag>) -> Result<Prompt> {
+    for (index, tag) in tags.iter().enumerate() {
+        if tag.id.trim().is_empty() || tag.name.trim().is_empty() {
+            return Err(error::invalid_input("Tag ids and names can't be empty"));
+        }
+
+        if tags[..index].iter().any(|t| t.id == tag.id) {
+            return Err(error::invalid_input(format!("Duplicate tag id: {}", tag.id)));
+        }
+    }
+
+    update_prompt(id, None, None, Some(tags), None).await
+}
+
+/// Adds the tag to each listed prompt that doesn't already carry its id, in one save.
+/// Returns how many prompts changed.
+pub async fn add_tag_to_prompts(tag: PromptTag, ids: &[String]) -> Result<usize> {
+    if tag.id.trim().is_empty() || tag.name.trim().is_empty() {
+        return Err(error::invalid_input("Tag ids and names can't be empty"));
+    }
+
+    update_prompt_tags(ids, |tags| {
+        if tags.iter().any(|t| t.id == tag.id) {
+            return false;
+        }
+
+        tags.push(tag.clone());
+        true
+    }).await
+}
+
+/// Removes the tag from each listed prompt carrying it, in one save. Returns how many prompts changed.
+pub async fn remove_tag_from_prompts(tag_id: &str, ids: &[String]) -> Result<usize> {
+    update_prompt_tags(ids, |tags| {
+        let before = tags.len();
+        tags.retain(|t| t.id != tag_id);
+        tags.len() != before
+    }).await
+}
+
+// Applies `change` to the tags of each listed prompt; it returns whether it changed anything.
+// Unknown ids are ignored.
+async fn update_prompt_tags(ids: &[String], mut change: impl FnMut(&mut Vec<PromptTag>) -> bool) -> Result<usize> {
+    let mut prompts = load_prompts().await?;
+    let now = chrono::Utc::now().timestamp();
+    let mut modified = 0;
+
+    for prompt in prompts.iter_mut().filter(|p| ids.contains(&p.id)) {
+        if change(&mut prompt.tags) {
+            prompt.updated_at = now;
+            modified += 1;
+        }
+    }
+
+    if modified > 0 {
+        save_prompts(&prompts).await?;
+    }
+
+    Ok(modified)
+}
+
+// Counts a use of the prompt without touching `updated_at`, which tracks edits
+pub async fn record_prompt_used(id: &str) -> Result<Prompt> {
+    let mut prompts = load_prompts().await?;
+
+    let prompt = prompts.iter_mut()
+        .find(|p| p.id == id)
+        .ok_or_else(|| error::not_found("Prompt not found"))?;
+
+    prompt.usage_count += 1;
+    prompt.last_used_at = Some(chrono::Utc::now().timestamp());
+
+    let used_prompt = prompt.clone();
+
+    save_prompts(&prompts).await?;
+
+    Ok(used_prompt)
+}
+
+// Most used first; ties go to the most recently used
+pub async fn list_prompts_by_usage() -> Result<Vec<Prompt>> {
+    let mut prompts = load_prompts().await?;
+
+    prompts.sort_by_key(|p| std::cmp::Reverse((p.usage_count, p.last_used_at)));
+
+    Ok(prompts)
+}
+
+// Tags in use across all prompts, most used first. Tags sharing an id are merged under
+// the name most prompts use for it.
+pub async fn list_tags_with_counts() -> Result<Vec<TagCount>> {
+    let prompts = load_prompts().await?;
+
+    // Tag id -> (prompt count, name -> prompts using that name), in first-seen order
+    let mut tags: Vec<(String, usize, HashMap<String, usize>)> = Vec::new();
+
+    for prompt in &prompts {
+        let mut seen_ids: Vec<&str> = Vec::new();
+
+        for tag in &prompt.tags {
+            if seen_ids.contains(&tag.id.as_str()) {
+                continue;
+            }
+            seen_ids.push(&tag.id);
+
+            let index = match tags.iter().position(|(id, _, _)| *id == tag.id) {
+                Some(index) => index,
+                None => {
+                    tags.push((tag.id.clone(), 0, HashMap::new()));
+                    tags.len() - 1
+                }
+            };
+
+            tags[index].1 += 1;
+            *tags[index].2.entry(tag.name.clone()).or_insert(0) += 1;
+        }
+    }
+
+    let mut counts: Vec<TagCount> = tags.into_iter()
+        .map(|(id, count, names)| {
+            // Ties go to the alphabetically first name so the result is stable
+            let name = names.into_iter()
+                .max_by(|(a_name, a_count), (b_name, b_count)| a_count.cmp(b_count).then_with(|| b_name.cmp(a_name)))
+                .map(|(name, _)| name)
+                .unwrap_or_default();
+
+            TagCount {
+                tag: PromptTag { id, name },
+                count,
+            }
+        })
+        .collect();
+
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.name.to_lowercase().cmp(&b.tag.name.to_lowercase())));
+
+    Ok(counts)
+}
+
 pub async fn delete_prompt(id: &str) -> Result<()> {
     let mut prompts = load_prompts().await?;
 
@@ -82,4 +293,59 @@ pub async fn delete_prompt(id: &str) -> Result<()> {
     save_prompts(&prompts).await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Deletes every prompt whose id is listed, in one save. Unknown ids are ignored;
+/// returns how many prompts were actually removed.
+pub async fn delete_prompts(ids: &[String]) -> Result<usize> {
+    let mut prompts = load_prompts().await?;
+    let before = prompts.len();
+
+    prompts.retain(|p| !ids.contains(&p.id));
+
+    let deleted = before - prompts.len();
+
+    if deleted > 0 {
+        save_prompts(&prompts).await?;
+    }
+
+    Ok(deleted)
+}
+
+/// All known versions of a prompt, oldest first; the last entry is the current content
+pub async fn get_prompt_history(id: &str) -> Result<Vec<PromptVersion>> {
+    let prompt = get_prompt(id).await?;
+
+    Ok(prompt_versions(&prompt))
+}
+
+/// Unified diff between two versions, indexed as returned by `get_prompt_history`
+pub async fn diff_prompt_versions(id: &str, from: usize, to: usize, context_lines: usize) -> Result<String> {
+    let prompt = get_prompt(id).await?;
+    let versions = prompt_versions(&prompt);
+
+    let version = |index: usize| {
+        versions.get(index)
+            .ok_or_else(|| error::invalid_input(format!("Invalid prompt version {} (prompt has {} versions)", index, versions.len())))
+    };
+
+    let old = version(from)?;
+    let new = version(to)?;
+
+    let diff = TextDiff::from_lines(&old.content, &new.content);
+    let mut unified = diff.unified_diff();
+    unified.context_radius(context_lines).header(&format!("version {}", from), &format!("version {}", to));
+
+    Ok(unified.to_string())
+}
+
+fn prompt_versions(prompt: &Prompt) -> Vec<PromptVersion> {
+    let mut versions = prompt.history.clone();
+
+    versions.push(PromptVersion {
+        content: prompt.content.clone(),
+        timestamp: prompt.updated_at,
+    });
+
+    versions
+}
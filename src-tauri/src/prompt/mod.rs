@@ -1,6 +1,16 @@
 // prompt/mod.rs
 pub mod storage;
 pub mod manager;
+pub mod template;
+pub mod redact;
+pub mod pricing;
+pub mod tokens;
+pub mod search;
 
 pub use storage::load_prompts;  // Remove save_prompt
-pub use manager::{list_prompts, add_prompt, update_prompt, delete_prompt, Prompt, PromptTag};
\ No newline at end of file
+pub use manager::{list_prompts, list_prompts_for_workspace, get_prompt, add_prompt, update_prompt, set_prompt_tags, add_tag_to_prompts, remove_tag_from_prompts, delete_prompt, delete_prompts, record_prompt_used, list_prompts_by_usage, list_tags_with_counts, get_prompt_history, diff_prompt_versions, Prompt, PromptTag, PromptVersion, TagCount};
+pub use template::{extract_variables, render_template, RenderedPrompt};
+pub use redact::{redact_secrets, Redaction, REDACTED};
+pub use pricing::{estimate_cost, CostEstimate};
+pub use tokens::{estimate_tokens, estimate_tokens_streaming, TokenEstimate, TokenProgress};
+pub use search::{search_prompts_with_snippets, PromptSearchResult, Snippet};
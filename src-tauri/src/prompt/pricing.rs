@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Input price in USD per million tokens, keyed by model id prefix so dated
+// releases (`claude-3-5-sonnet-20241022`) match their family. List prices, update as they change.
+const INPUT_PRICES_PER_MILLION: &[(&str, f64)] = &[
+    ("gpt-4o", 2.50),
+    ("gpt-4o-mini", 0.15),
+    ("gpt-4.1", 2.00),
+    ("gpt-4.1-mini", 0.40),
+    ("gpt-4.1-nano", 0.10),
+    ("o1", 15.00),
+    ("o1-mini", 1.10),
+    ("o3-mini", 1.10),
+    ("claude-3-opus", 15.00),
+    ("claude-3-5-sonnet", 3.00),
+    ("claude-3-7-sonnet", 3.00),
+    ("claude-3-5-haiku", 0.80),
+    ("claude-sonnet-4", 3.00),
+    ("claude-opus-4", 15.00),
+    ("gemini-1.5-pro", 1.25),
+    ("gemini-1.5-flash", 0.075),
+    ("gemini-2.0-flash", 0.10),
+    ("deepseek-chat", 0.27),
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CostEstimate {
+    pub model: String,
+    pub tokens: u64,
+    // False when the model isn't in the table or the overrides; price and cost are then None
+    pub known_model: bool,
+    pub input_price_per_million: Option<f64>,
+    // Estimated input cost in USD
+    pub estimated_cost: Option<f64>,
+}
+
+/// Looks up the input price for a model, preferring `overrides` (USD per million tokens, same prefix
+/// matching) over the built-in table. The longest matching prefix wins, so `gpt-4o-mini` isn't priced as `gpt-4o`.
+pub fn input_price_per_million(model: &str, overrides: &HashMap<String, f64>) -> Option<f64> {
+    let model = model.trim().to_lowercase();
+
+    longest_prefix_match(&model, overrides.iter().map(|(prefix, price)| (prefix.as_str(), *price)))
+        .or_else(|| longest_prefix_match(&model, INPUT_PRICES_PER_MILLION.iter().copied()))
+}
+
+fn longest_prefix_match<'a>(model: &str, prices: impl Iterator<Item = (&'a str, f64)>) -> Option<f64> {
+    prices.filter(|(prefix, _)| model.starts_with(&prefix.to_lowercase()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, price)| price)
+}
+
+/// Estimates what sending `tokens` input tokens to the model would cost
+pub fn estimate_cost(model: &str, tokens: u64, overrides: &HashMap<String, f64>) -> CostEstimate {
+    let price = input_price_per_million(model, overrides);
+
+    CostEstimate {
+        model: model.to_string(),
+        tokens,
+        known_model: price.is_some(),
+        input_price_per_million: price,
+        estimated_cost: price.map(|price| price * tokens as f64 / 1_000_000.0),
+    }
+}
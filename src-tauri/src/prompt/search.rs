@@ -0,0 +1,106 @@
+use anyhow::Result;
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+
+use super::manager::Prompt;
+use super::storage::load_prompts;
+
+// Marks text cut from either side of a snippet
+const ELLIPSIS: &str = "…";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Snippet {
+    pub text: String,
+    // Character offsets of the match within `text`, end exclusive
+    pub match_start: usize,
+    pub match_end: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PromptSearchResult {
+    pub prompt: Prompt,
+    pub title_match: bool,
+    // Occurrences in the content, including those without a snippet
+    pub match_count: usize,
+    // The first few content matches, in order
+    pub snippets: Vec<Snippet>,
+}
+
+/// Case-insensitive search over prompt titles and contents. Title matches rank first, then
+/// prompts with more content matches. Each result carries up to `max_snippets` excerpts of about
+/// `snippet_chars` characters centred on a match.
+pub async fn search_prompts_with_snippets(query: &str, max_snippets: usize, snippet_chars: usize) -> Result<Vec<PromptSearchResult>> {
+    let query = query.trim();
+
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let pattern = RegexBuilder::new(&regex::escape(query))
+        .case_insensitive(true)
+        .build()?;
+
+    let mut results: Vec<PromptSearchResult> = load_prompts().await?
+        .into_iter()
+        .filter_map(|prompt| {
+            let title_match = pattern.is_match(&prompt.title);
+            let matches: Vec<(usize, usize)> = pattern.find_iter(&prompt.content)
+                .map(|m| (m.start(), m.end()))
+                .collect();
+
+            if !title_match && matches.is_empty() {
+                return None;
+            }
+
+            let snippets = matches.iter()
+                .take(max_snippets)
+                .map(|&(start, end)| snippet(&prompt.content, start, end, snippet_chars))
+                .collect();
+
+            Some(PromptSearchResult {
+                match_count: matches.len(),
+                title_match,
+                snippets,
+                prompt,
+            })
+        })
+        .collect();
+
+    results.sort_by_key(|r| std::cmp::Reverse((r.title_match, r.match_count)));
+
+    Ok(results)
+}
+
+// An excerpt around the match at `start..end` (byte offsets), on one line of whitespace
+fn snippet(content: &str, start: usize, end: usize, snippet_chars: usize) -> Snippet {
+    let match_chars = content[start..end].chars().count();
+    let context = snippet_chars.saturating_sub(match_chars) / 2;
+
+    let before: Vec<char> = content[..start].chars().rev().take(context + 1).collect();
+    let after: Vec<char> = content[end..].chars().take(context + 1).collect();
+
+    let mut text = String::new();
+
+    if before.len() > context {
+        text.push_str(ELLIPSIS);
+    }
+    text.extend(before.iter().take(context).rev());
+
+    let match_start = text.chars().count();
+    text.push_str(&content[start..end]);
+    let match_end = text.chars().count();
+
+    text.extend(after.iter().take(context));
+    if after.len() > context {
+        text.push_str(ELLIPSIS);
+    }
+
+    // Newlines become spaces so each snippet renders as one line; offsets are unaffected
+    let text = text.chars().map(|c| if c == '\n' || c == '\r' || c == '\t' { ' ' } else { c }).collect();
+
+    Snippet {
+        text,
+        match_start,
+        match_end,
+    }
+}
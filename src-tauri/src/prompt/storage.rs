@@ -3,6 +3,7 @@ use serde_json;
 use std::path::PathBuf;
 use tokio::fs;
 
+use crate::fs::writer::set_aside_corrupt_file;
 use super::manager::Prompt;
 
 fn get_prompts_file_path() -> Result<PathBuf> {
@@ -30,14 +31,19 @@ pub async fn load_prompts() -> Result<Vec<Prompt>> {
         return Ok(Vec::new());
     }
 
-    let content = fs::read_to_string(file_path)
+    let content = fs::read_to_string(&file_path)
         .await
         .context("Failed to read prompts file")?;
 
-    let prompts = serde_json::from_str(&content)
-        .context("Failed to parse prompts file")?;
+    match serde_json::from_str(&content) {
+        Ok(prompts) => Ok(prompts),
+        Err(err) => {
+            // A truncated or corrupted file would otherwise fail every command that loads it
+            set_aside_corrupt_file(&file_path, &format!("Failed to parse prompts file: {}", err)).await?;
 
-    Ok(prompts)
+            Ok(Vec::new())
+        },
+    }
 }
 
 pub async fn save_prompts(prompts: &[Prompt]) -> Result<()> {
@@ -0,0 +1,57 @@
+use anyhow::Result;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RenderedPrompt {
+    pub content: String,
+    // Placeholders with no value supplied; they are left in the content as-is
+    pub unresolved: Vec<String>,
+}
+
+// Matches `{{name}}`, allowing whitespace inside the braces
+fn placeholder_pattern() -> Result<Regex> {
+    Ok(Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_.-]*)\s*\}\}")?)
+}
+
+/// Returns the distinct placeholder names in the content, in order of first appearance
+pub fn extract_variables(content: &str) -> Result<Vec<String>> {
+    let pattern = placeholder_pattern()?;
+    let mut variables: Vec<String> = Vec::new();
+
+    for captures in pattern.captures_iter(content) {
+        let name = &captures[1];
+
+        if !variables.iter().any(|v| v == name) {
+            variables.push(name.to_string());
+        }
+    }
+
+    Ok(variables)
+}
+
+/// Substitutes `{{name}}` placeholders with the supplied values
+pub fn render_template(content: &str, variables: &HashMap<String, String>) -> Result<RenderedPrompt> {
+    let pattern = placeholder_pattern()?;
+    let mut unresolved: Vec<String> = Vec::new();
+
+    let rendered = pattern.replace_all(content, |captures: &Captures| {
+        let name = &captures[1];
+
+        match variables.get(name) {
+            Some(value) => value.clone(),
+            None => {
+                if !unresolved.iter().any(|v| v == name) {
+                    unresolved.push(name.to_string());
+                }
+                captures[0].to_string()
+            }
+        }
+    });
+
+    Ok(RenderedPrompt {
+        content: rendered.into_owned(),
+        unresolved,
+    })
+}
@@ -0,0 +1,74 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::fs::reader::looks_binary;
+
+// Same rough ratio the UI uses: one token per four characters
+const CHARS_PER_TOKEN: usize = 4;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenProgress {
+    pub path: String,
+    // Tokens in this file
+    pub tokens: u64,
+    // Tokens counted so far, this file included
+    pub running_total: u64,
+    pub files_done: usize,
+    pub files_total: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenEstimate {
+    pub total: u64,
+    // Files counted; missing, binary and unreadable files are left out
+    pub file_count: usize,
+    // True when counting stopped early, in which case `total` only covers the files before that
+    pub cancelled: bool,
+}
+
+/// Rough token count for a piece of text
+pub fn estimate_tokens(text: &str) -> u64 {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN) as u64
+}
+
+/// Counts tokens file by file, reporting each one through `on_progress`.
+/// Stops before the next file once `cancel` is set.
+pub async fn estimate_tokens_streaming(
+    paths: &[String],
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(&TokenProgress),
+) -> Result<TokenEstimate> {
+    let mut estimate = TokenEstimate {
+        total: 0,
+        file_count: 0,
+        cancelled: false,
+    };
+
+    for (index, path) in paths.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            estimate.cancelled = true;
+            break;
+        }
+
+        let tokens = match tokio::fs::read(path).await {
+            Ok(bytes) if !looks_binary(&bytes) => {
+                estimate.file_count += 1;
+                estimate_tokens(&String::from_utf8_lossy(&bytes))
+            },
+            _ => 0,
+        };
+
+        estimate.total += tokens;
+
+        on_progress(&TokenProgress {
+            path: path.clone(),
+            tokens,
+            running_total: estimate.total,
+            files_done: index + 1,
+            files_total: paths.len(),
+        });
+    }
+
+    Ok(estimate)
+}
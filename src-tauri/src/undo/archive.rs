@@ -0,0 +1,223 @@
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Component, Path};
+use uuid::Uuid;
+
+use crate::fs::writer::{global_backup_dir, BackupLocation};
+use super::{load_undo_history, save_undo_history, UndoHistory};
+
+// Archive layout: the history at the root, with backup paths rewritten to point into `backups/`
+const HISTORY_ENTRY: &str = "undo_history.json";
+const BACKUPS_DIR: &str = "backups";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportedUndoArchive {
+    pub path: String,
+    pub change_sets: usize,
+    // Backup files or trees written to the archive
+    pub backups: usize,
+    // Backups the history refers to that no longer exist; their entries are exported as they are
+    pub missing: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportedUndoArchive {
+    pub imported: usize,
+    // Change sets already in the history (same id), left untouched
+    pub skipped: usize,
+}
+
+/// Writes the undo history and every backup it refers to into one `.tar.gz` at `output_path`
+pub async fn export_undo_archive(output_path: &str) -> Result<ExportedUndoArchive> {
+    let history = load_undo_history().await?;
+    let output_path = output_path.to_string();
+
+    tokio::task::spawn_blocking(move || write_archive(history, &output_path))
+        .await
+        .context("Undo archive task failed")?
+}
+
+/// Extracts an archive from `export_undo_archive` into the global backup dir and adds its change
+/// sets to the history, skipping ones already present. Returns how many were added. Imported sets
+/// are kept out of the undo stack: they can only be undone by id, inside the workspace folders.
+pub async fn import_undo_archive(archive_path: &str) -> Result<ImportedUndoArchive> {
+    let import_dir = global_backup_dir()?.join("imported").join(Uuid::new_v4().to_string());
+
+    let archive_path = archive_path.to_string();
+    let extract_dir = import_dir.clone();
+
+    let extracted = tokio::task::spawn_blocking(move || extract_archive(&archive_path, &extract_dir))
+        .await
+        .context("Undo archive task failed")?;
+
+    let archived_history = match extracted {
+        Ok(history) => history,
+        Err(e) => {
+            let _ = tokio::fs::remove_dir_all(&import_dir).await;
+            return Err(e);
+        },
+    };
+
+    let mut history = load_undo_history().await?;
+    let mut imported = 0;
+    let mut skipped = 0;
+
+    for mut change_set in archived_history.change_sets {
+        if history.change_sets.iter().any(|c| c.id == change_set.id) {
+            skipped += 1;
+            continue;
+        }
+
+        change_set.imported = true;
+
+        for backup in &mut change_set.backups {
+            let path = match archived_backup_path(&backup.backup_path) {
+                Some(relative) => import_dir.join(relative),
+                // Backups missing at export time keep a path from the other machine, and a crafted
+                // archive could name any file; point both somewhere that can't exist so they show as missing
+                None => import_dir.join("missing").join(Uuid::new_v4().to_string()),
+            };
+
+            backup.backup_path = path.to_string_lossy().to_string();
+            backup.location = BackupLocation::Global;
+        }
+
+        history.change_sets.push(change_set);
+        imported += 1;
+    }
+
+    if imported == 0 {
+        let _ = tokio::fs::remove_dir_all(&import_dir).await;
+    } else {
+        // Imported sets go below the local ones (oldest first), so they never become the last change
+        let (mut imported_sets, local_sets): (Vec<_>, Vec<_>) = history.change_sets.into_iter().partition(|c| c.imported);
+        imported_sets.sort_by_key(|c| c.timestamp);
+        history.change_sets = imported_sets.into_iter().chain(local_sets).collect();
+
+        save_undo_history(&history).await?;
+    }
+
+    Ok(ImportedUndoArchive {
+        imported,
+        skipped,
+    })
+}
+
+fn write_archive(mut history: UndoHistory, output_path: &str) -> Result<ExportedUndoArchive> {
+    let file = File::create(output_path)
+        .with_context(|| format!("Failed to create archive: {}", output_path))?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    // Deduplicated backups are shared between change sets; store each one once
+    let mut archived: HashMap<String, String> = HashMap::new();
+    let mut missing = Vec::new();
+
+    for change_set in &mut history.change_sets {
+        for backup in &mut change_set.backups {
+            if let Some(name) = archived.get(&backup.backup_path) {
+                backup.backup_path = name.clone();
+                continue;
+            }
+
+            let source = Path::new(&backup.backup_path);
+
+            if !source.exists() {
+                missing.push(backup.backup_path.clone());
+                continue;
+            }
+
+            let file_name = source.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let name = format!("{}/{}-{}", BACKUPS_DIR, archived.len(), file_name);
+
+            if source.is_dir() {
+                builder.append_dir_all(&name, source)
+            } else {
+                builder.append_path_with_name(source, &name)
+            }
+            .with_context(|| format!("Failed to archive backup: {}", backup.backup_path))?;
+
+            archived.insert(backup.backup_path.clone(), name.clone());
+            backup.backup_path = name;
+        }
+    }
+
+    let json = serde_json::to_vec_pretty(&history)
+        .context("Failed to serialize undo history")?;
+
+    let mut header = tar::Header::new_gnu();
+    header.set_size(json.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+
+    builder.append_data(&mut header, HISTORY_ENTRY, json.as_slice())
+        .context("Failed to archive undo history")?;
+
+    builder.into_inner()
+        .and_then(|encoder| encoder.finish())
+        .context("Failed to finish archive")?;
+
+    Ok(ExportedUndoArchive {
+        path: output_path.to_string(),
+        change_sets: history.change_sets.len(),
+        backups: archived.len(),
+        missing,
+    })
+}
+
+// The archived backup path if it is a plain relative path below `backups/` (no `..`, no root)
+fn archived_backup_path(path: &str) -> Option<&Path> {
+    let path = Path::new(path);
+    let plain = path.components().all(|component| matches!(component, Component::Normal(_)));
+    let below_backups = path.strip_prefix(BACKUPS_DIR).is_ok_and(|rest| rest.components().next().is_some());
+
+    (plain && below_backups).then_some(path)
+}
+
+// Unpacks the archive into `dir` (entries escaping it are skipped) and returns its history
+fn extract_archive(archive_path: &str, dir: &Path) -> Result<UndoHistory> {
+    std::fs::create_dir_all(dir)
+        .context("Failed to create import directory")?;
+
+    let file = File::open(archive_path)
+        .with_context(|| format!("Failed to open archive: {}", archive_path))?;
+
+    tar::Archive::new(GzDecoder::new(file))
+        .unpack(dir)
+        .context("Failed to extract undo archive")?;
+
+    let history_path = dir.join(HISTORY_ENTRY);
+
+    let content = std::fs::read(&history_path)
+        .context("Archive does not contain an undo history")?;
+    let history = serde_json::from_slice(&content)
+        .context("Failed to parse archived undo history")?;
+
+    std::fs::remove_file(&history_path)
+        .context("Failed to clean up archived undo history")?;
+
+    Ok(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_only_plain_paths_below_the_backups_dir() {
+        assert_eq!(archived_backup_path("backups/0-main.rs"), Some(Path::new("backups/0-main.rs")));
+        assert_eq!(archived_backup_path("backups/1-src/lib.rs"), Some(Path::new("backups/1-src/lib.rs")));
+
+        assert_eq!(archived_backup_path("backups"), None);
+        assert_eq!(archived_backup_path("backups/../../etc/passwd"), None);
+        assert_eq!(archived_backup_path("/home/user/.proprompter/backups/main.rs"), None);
+        assert_eq!(archived_backup_path("other/0-main.rs"), None);
+    }
+}
@@ -1,14 +1,31 @@
 use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
-use crate::fs::writer::{create_backup, restore_from_backup};
+pub mod archive;
+pub mod self_test;
+
+pub use archive::{export_undo_archive, import_undo_archive, ExportedUndoArchive, ImportedUndoArchive};
+pub use self_test::{run_self_test, SelfTestReport, SelfTestStep};
+
+use crate::error;
+use crate::fs::writer::{create_backup, restore_from_backup, verify_backup, BackupLocation};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BackupFile {
     pub original_path: String,
     pub backup_path: String,
+    // Older history entries predate per-project backups and were all stored globally
+    #[serde(default)]
+    pub location: BackupLocation,
+    // SHA-256 of the backup; missing on entries written before checksums were recorded
+    #[serde(default)]
+    pub checksum: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -17,6 +34,10 @@ pub struct ChangeSet {
     pub backups: Vec<BackupFile>,
     pub timestamp: i64,
     pub description: String,
+    // Came from import_undo_archive: never undone implicitly, only by id, and only inside
+    // the current workspace folders
+    #[serde(default)]
+    pub imported: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -34,16 +55,30 @@ async fn load_undo_history() -> Result<UndoHistory> {
         });
     }
 
-    let content = tokio::fs::read_to_string(file_path)
+    let bytes = tokio::fs::read(file_path)
         .await
         .context("Failed to read undo history file")?;
 
-    let history = serde_json::from_str(&content)
+    // The history is gzipped on write; files from older versions are still plain JSON
+    let content = if bytes.starts_with(&GZIP_MAGIC) {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(bytes.as_slice())
+            .read_to_end(&mut decompressed)
+            .context("Failed to decompress undo history file")?;
+        decompressed
+    } else {
+        bytes
+    };
+
+    let history = serde_json::from_slice(&content)
         .context("Failed to parse undo history file")?;
 
     Ok(history)
 }
 
+// First bytes of every gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
 fn get_undo_history_path() -> Result<PathBuf> {
     let app_dir = directories::ProjectDirs::from("com", "mohilcode", "proprompter")
         .context("Failed to determine app directories")?
@@ -67,16 +102,18 @@ pub async fn create_change_set(description: &str) -> Result<ChangeSet> {
         backups: Vec::new(),
         timestamp: chrono::Utc::now().timestamp(),
         description: description.to_string(),
+        imported: false,
     };
 
     Ok(change_set)
 }
 
-pub async fn add_to_change_set(change_set: &mut ChangeSet, path: &str) -> Result<()> {
+// Backs up a file into the change set; `project_root` selects per-project backup storage
+pub async fn add_to_change_set(change_set: &mut ChangeSet, path: &str, project_root: Option<&Path>) -> Result<()> {
     let path_obj = Path::new(path);
 
     if !path_obj.exists() {
-        anyhow::bail!("File does not exist: {}", path);
+        return Err(error::not_found(format!("File does not exist: {}", path)));
     }
 
     // Skip if this file is already backed up in this change set
@@ -85,12 +122,20 @@ pub async fn add_to_change_set(change_set: &mut ChangeSet, path: &str) -> Result
     }
 
     // Create backup
-    let backup_path = create_backup(path).await?;
+    let backup = create_backup(path, project_root).await?;
+
+    let location = if project_root.is_some() {
+        BackupLocation::Project
+    } else {
+        BackupLocation::Global
+    };
 
     // Add to change set
     change_set.backups.push(BackupFile {
         original_path: path.to_string(),
-        backup_path: backup_path.to_string_lossy().to_string(),
+        backup_path: backup.path.to_string_lossy().to_string(),
+        location,
+        checksum: Some(backup.checksum),
     });
 
     Ok(())
@@ -116,9 +161,15 @@ pub async fn save_change_set(change_set: &ChangeSet) -> Result<()> {
 async fn save_undo_history(history: &UndoHistory) -> Result<()> {
     let file_path = get_undo_history_path()?;
 
-    let content = serde_json::to_string_pretty(history)
+    let json = serde_json::to_vec(history)
         .context("Failed to serialize undo history")?;
 
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&json)
+        .context("Failed to compress undo history")?;
+    let content = encoder.finish()
+        .context("Failed to compress undo history")?;
+
     tokio::fs::write(file_path, content)
         .await
         .context("Failed to write undo history file")?;
@@ -126,19 +177,37 @@ async fn save_undo_history(history: &UndoHistory) -> Result<()> {
     Ok(())
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UndoPreview {
+    pub description: String,
+    pub timestamp: i64,
+    pub paths: Vec<String>,
+}
+
+// Describe what `undo_last_change` would restore, without restoring anything
+pub async fn preview_undo_last_change() -> Result<Option<UndoPreview>> {
+    let history = load_undo_history().await?;
+
+    let preview = history.change_sets.iter().rfind(|c| !c.imported).map(|change_set| UndoPreview {
+        description: change_set.description.clone(),
+        timestamp: change_set.timestamp,
+        paths: change_set.backups.iter().map(|b| b.original_path.clone()).collect(),
+    });
+
+    Ok(preview)
+}
+
+// Imported change sets are skipped; they can only be undone by id
 pub async fn undo_last_change() -> Result<Option<String>> {
     let mut history = load_undo_history().await?;
 
-    if history.change_sets.is_empty() {
+    let Some(index) = history.change_sets.iter().rposition(|c| !c.imported) else {
         return Ok(None);
-    }
+    };
 
-    let last_change = history.change_sets.pop()
-        .context("Failed to get last change set")?;
+    let last_change = history.change_sets.remove(index);
 
-    for backup in &last_change.backups {
-        restore_from_backup(Path::new(&backup.backup_path), &backup.original_path).await?;
-    }
+    restore_change_set(&last_change).await?;
 
     // Update history
     save_undo_history(&history).await?;
@@ -146,19 +215,83 @@ pub async fn undo_last_change() -> Result<Option<String>> {
     Ok(Some(last_change.description))
 }
 
+/// Undoes one change set by id, wherever it is in the history, and removes it
+pub async fn undo_change_set(change_set_id: &str) -> Result<String> {
+    let mut history = load_undo_history().await?;
+
+    let index = history.change_sets.iter().position(|c| c.id == change_set_id)
+        .ok_or_else(|| error::not_found(format!("Change set not found: {}", change_set_id)))?;
+
+    restore_change_set(&history.change_sets[index]).await?;
+
+    let change_set = history.change_sets.remove(index);
+    save_undo_history(&history).await?;
+
+    Ok(change_set.description)
+}
+
+/// Drops a change set from the history without restoring anything; false when it wasn't there
+pub async fn discard_change_set(change_set_id: &str) -> Result<bool> {
+    let mut history = load_undo_history().await?;
+    let before = history.change_sets.len();
+
+    history.change_sets.retain(|c| c.id != change_set_id);
+
+    if history.change_sets.len() == before {
+        return Ok(false);
+    }
+
+    save_undo_history(&history).await?;
+
+    Ok(true)
+}
+
+async fn restore_change_set(change_set: &ChangeSet) -> Result<()> {
+    // Check every backup first so a corrupted one doesn't leave the change half undone
+    for backup in &change_set.backups {
+        check_restore_target(change_set, &backup.original_path).await?;
+
+        verify_backup(Path::new(&backup.backup_path), backup.checksum.as_deref())
+            .await
+            .with_context(|| format!("Cannot restore {}", backup.original_path))?;
+    }
+
+    for backup in &change_set.backups {
+        restore_from_backup(Path::new(&backup.backup_path), &backup.original_path, backup.checksum.as_deref()).await?;
+    }
+
+    Ok(())
+}
+
+// Imported change sets name paths from another machine or history; only restore them over
+// files inside the current workspace folders
+async fn check_restore_target(change_set: &ChangeSet, original_path: &str) -> Result<()> {
+    if change_set.imported {
+        let roots = crate::workspace::get_workspace_roots(None).await?;
+        crate::fs::sandbox::resolve_within_roots(original_path, &roots)
+            .with_context(|| format!("Cannot restore imported backup of {}", original_path))?;
+    }
+
+    Ok(())
+}
+
 // Add this function to undo/mod.rs
 
 // Undo changes for a specific file
 pub async fn undo_file_change(file_path: &str) -> Result<bool> {
   let history = load_undo_history().await?;
 
-  // Find the most recent change set that includes this file
+  // Find the most recent change set that includes this file, leaving imported ones alone
   for i in (0..history.change_sets.len()).rev() {
       let change_set = &history.change_sets[i];
 
+      if change_set.imported {
+          continue;
+      }
+
       if let Some(backup) = change_set.backups.iter().find(|b| b.original_path == file_path) {
           // Restore just this file
-          restore_from_backup(Path::new(&backup.backup_path), file_path).await?;
+          restore_from_backup(Path::new(&backup.backup_path), file_path, backup.checksum.as_deref()).await?;
 
           // We could update the change set to indicate this file was undone
           // but for simplicity, we'll leave the history as is
@@ -168,4 +301,77 @@ pub async fn undo_file_change(file_path: &str) -> Result<bool> {
   }
 
   Ok(false) // No backup found for this file
-}
\ No newline at end of file
+}
+
+// All recorded change sets, newest first
+pub async fn list_change_sets() -> Result<Vec<ChangeSet>> {
+    let mut change_sets = load_undo_history().await?.change_sets;
+    change_sets.reverse();
+
+    Ok(change_sets)
+}
+
+// Restore one file from the backup recorded in a specific change set
+pub async fn restore_file_from_change_set(file_path: &str, change_set_id: &str) -> Result<()> {
+    let history = load_undo_history().await?;
+
+    let change_set = history.change_sets.iter()
+        .find(|c| c.id == change_set_id)
+        .ok_or_else(|| error::not_found(format!("Change set not found: {}", change_set_id)))?;
+
+    let backup = change_set.backups.iter()
+        .find(|b| b.original_path == file_path)
+        .ok_or_else(|| error::not_found(format!("File not found in change set {}: {}", change_set_id, file_path)))?;
+
+    check_restore_target(change_set, file_path).await?;
+
+    restore_from_backup(Path::new(&backup.backup_path), file_path, backup.checksum.as_deref()).await
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupProblem {
+    pub original_path: String,
+    pub backup_path: String,
+    // Missing backup or checksum mismatch
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChangeSetIntegrity {
+    pub id: String,
+    pub description: String,
+    pub timestamp: i64,
+    // False when any backup is missing or corrupted, so undo would refuse this change set
+    pub restorable: bool,
+    pub problems: Vec<BackupProblem>,
+}
+
+// Checks every recorded backup (existence, and checksum when recorded), newest change set first
+pub async fn verify_undo_integrity() -> Result<Vec<ChangeSetIntegrity>> {
+    let history = load_undo_history().await?;
+    let mut report = Vec::with_capacity(history.change_sets.len());
+
+    for change_set in history.change_sets.iter().rev() {
+        let mut problems = Vec::new();
+
+        for backup in &change_set.backups {
+            if let Err(e) = verify_backup(Path::new(&backup.backup_path), backup.checksum.as_deref()).await {
+                problems.push(BackupProblem {
+                    original_path: backup.original_path.clone(),
+                    backup_path: backup.backup_path.clone(),
+                    reason: e.to_string(),
+                });
+            }
+        }
+
+        report.push(ChangeSetIntegrity {
+            id: change_set.id.clone(),
+            description: change_set.description.clone(),
+            timestamp: change_set.timestamp,
+            restorable: problems.is_empty(),
+            problems,
+        });
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::fs::writer::write_file;
+use crate::undo::{add_to_change_set, create_change_set, discard_change_set, save_change_set, undo_change_set};
+
+const ORIGINAL_CONTENT: &str = "proprompter self-test: original content\n";
+const MODIFIED_CONTENT: &str = "proprompter self-test: modified content\n";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SelfTestStep {
+    pub name: String,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SelfTestReport {
+    pub passed: bool,
+    // Steps in the order they ran; the run stops at the first failure
+    pub steps: Vec<SelfTestStep>,
+}
+
+impl SelfTestReport {
+    // Runs a step and records its outcome, returning its value when it succeeded
+    async fn step<T>(&mut self, name: &str, run: impl Future<Output = Result<T>>) -> Option<T> {
+        let result = run.await;
+
+        self.steps.push(SelfTestStep {
+            name: name.to_string(),
+            ok: result.is_ok(),
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        });
+
+        result.ok()
+    }
+}
+
+/// Exercises the write, backup, change set and undo pipeline on a scratch file in the
+/// temp directory. Backups go to the app data dir, as they do for real changes, so this
+/// also checks that directory can be resolved and written.
+pub async fn run_self_test() -> Result<SelfTestReport> {
+    let dir = std::env::temp_dir().join(format!("proprompter-self-test-{}", Uuid::new_v4()));
+    let file = dir.join("self-test.txt");
+    let file_path = file.to_string_lossy().to_string();
+
+    let mut report = SelfTestReport {
+        passed: false,
+        steps: Vec::new(),
+    };
+
+    report.passed = run_steps(&mut report, &file_path).await.is_some();
+
+    // Leftovers in the temp dir are harmless, so a failed cleanup doesn't fail the test
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+
+    Ok(report)
+}
+
+async fn run_steps(report: &mut SelfTestReport, file_path: &str) -> Option<()> {
+    report.step("Create test file", write_file(file_path, ORIGINAL_CONTENT)).await?;
+
+    let mut change_set = report.step("Create change set", create_change_set("Self-test")).await?;
+
+    report.step("Back up test file", add_to_change_set(&mut change_set, file_path, None)).await?;
+    report.step("Rewrite test file", write_file(file_path, MODIFIED_CONTENT)).await?;
+    report.step("Save change set", save_change_set(&change_set)).await?;
+
+    // A failed undo would leave the scratch change set on top of the real undo history
+    if report.step("Undo change set", undo_change_set(&change_set.id)).await.is_none() {
+        let _ = discard_change_set(&change_set.id).await;
+        return None;
+    }
+
+    report.step("Verify original content", expect_content(file_path, ORIGINAL_CONTENT)).await?;
+
+    Some(())
+}
+
+async fn expect_content(path: &str, expected: &str) -> Result<()> {
+    let content = tokio::fs::read_to_string(Path::new(path))
+        .await
+        .with_context(|| format!("Failed to read {}", path))?;
+
+    if content != expected {
+        anyhow::bail!("Restored content doesn't match the original");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,378 @@
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::config::IoLimits;
+use crate::fs::reader::read_file;
+
+/// Languages whose imports can be followed. Each one needs an extractor and a resolver below.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportLanguage {
+    Rust,
+    JavaScript,
+    Python,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UnresolvedImport {
+    // The file containing the import
+    pub file: String,
+    pub import: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExpandedSelection {
+    // The original selection followed by the files its imports led to
+    pub paths: Vec<String>,
+    // Only the newly added files, in discovery order
+    pub added: Vec<String>,
+    // Local-looking imports that didn't resolve to a file inside the workspace
+    pub unresolved: Vec<UnresolvedImport>,
+}
+
+const JS_EXTENSIONS: &[&str] = &["ts", "tsx", "js", "jsx", "mjs", "cjs", "json"];
+
+pub fn import_language(path: &str) -> Option<ImportLanguage> {
+    let extension = Path::new(path).extension()?.to_str()?;
+
+    match extension {
+        "rs" => Some(ImportLanguage::Rust),
+        "ts" | "tsx" | "js" | "jsx" | "mjs" | "cjs" => Some(ImportLanguage::JavaScript),
+        "py" => Some(ImportLanguage::Python),
+        _ => None,
+    }
+}
+
+/// Adds the files imported by the selection, following imports up to `depth` levels.
+/// Resolution is conservative: only relative or crate-local imports are followed, package imports
+/// are ignored, and anything resolving outside `roots` is dropped.
+pub async fn expand_selection_with_imports(selected: &[String], roots: &[PathBuf], depth: usize, limits: &IoLimits) -> Result<ExpandedSelection> {
+    let roots: Vec<PathBuf> = roots.iter().filter_map(|root| root.canonicalize().ok()).collect();
+
+    let mut paths: Vec<String> = selected.to_vec();
+    let mut seen: Vec<PathBuf> = selected.iter()
+        .map(|path| Path::new(path).canonicalize().unwrap_or_else(|_| PathBuf::from(path)))
+        .collect();
+    let mut added = Vec::new();
+    let mut unresolved = Vec::new();
+    let mut frontier = selected.to_vec();
+
+    for _ in 0..depth {
+        let mut next = Vec::new();
+
+        for file in &frontier {
+            let Some(language) = import_language(file) else {
+                continue;
+            };
+
+            // Unreadable files just contribute no imports
+            let Ok(content) = read_file(file, limits).await else {
+                continue;
+            };
+
+            for import in extract_imports(language, &content)? {
+                let Some(candidates) = import_candidates(language, Path::new(file), &import) else {
+                    continue;
+                };
+
+                let resolved = candidates.into_iter()
+                    .filter(|candidate| candidate.is_file())
+                    .filter_map(|candidate| candidate.canonicalize().ok())
+                    .find(|candidate| roots.iter().any(|root| candidate.starts_with(root)));
+
+                match resolved {
+                    Some(path) if !seen.contains(&path) => {
+                        let path_str = path.to_string_lossy().to_string();
+                        seen.push(path);
+                        paths.push(path_str.clone());
+                        added.push(path_str.clone());
+                        next.push(path_str);
+                    },
+                    Some(_) => {},
+                    None => unresolved.push(UnresolvedImport {
+                        file: file.clone(),
+                        import,
+                    }),
+                }
+            }
+        }
+
+        if next.is_empty() {
+            break;
+        }
+
+        frontier = next;
+    }
+
+    Ok(ExpandedSelection {
+        paths,
+        added,
+        unresolved,
+    })
+}
+
+// Names from the list after `import`, without `as` aliases or comments
+fn imported_names(list: &str) -> Vec<&str> {
+    list.lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .flat_map(|line| line.split(','))
+        .filter_map(|item| item.split_whitespace().next())
+        .collect()
+}
+
+/// The import specifiers in a file, as written (`./util`, `crate::fs::reader`, `.models`)
+pub fn extract_imports(language: ImportLanguage, content: &str) -> Result<Vec<String>> {
+    let mut imports = Vec::new();
+
+    match language {
+        ImportLanguage::JavaScript => {
+            let patterns = [
+                r#"(?m)^\s*(?:import|export)\s+(?:[^'";]*?\s+from\s+)?['"]([^'"]+)['"]"#,
+                r#"\b(?:require|import)\(\s*['"]([^'"]+)['"]\s*\)"#,
+            ];
+
+            for pattern in patterns {
+                for captures in Regex::new(pattern)?.captures_iter(content) {
+                    imports.push(captures[1].to_string());
+                }
+            }
+        },
+        ImportLanguage::Python => {
+            // The names are either on the same line or in parentheses, which may span several lines
+            let from_import = Regex::new(r"(?m)^[ \t]*from[ \t]+(\.*[\w.]*)[ \t]+import[ \t]*(?:\(([^)]*)\)|([\w \t,]+))")?;
+            let plain_import = Regex::new(r"(?m)^\s*import\s+([\w.]+(?:\s*,\s*[\w.]+)*)")?;
+
+            for captures in from_import.captures_iter(content) {
+                let module = &captures[1];
+
+                // `from . import a, b` imports sibling modules
+                if module.chars().all(|c| c == '.') {
+                    let names = captures.get(2).or_else(|| captures.get(3)).map(|m| m.as_str()).unwrap_or("");
+
+                    for name in imported_names(names) {
+                        imports.push(format!("{}{}", module, name));
+                    }
+                } else {
+                    imports.push(module.to_string());
+                }
+            }
+
+            for captures in plain_import.captures_iter(content) {
+                imports.extend(captures[1].split(',').map(|m| m.trim().to_string()));
+            }
+        },
+        ImportLanguage::Rust => {
+            let mod_declaration = Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+(\w+)\s*;")?;
+            let use_declaration = Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?use\s+([^;]+);")?;
+
+            for captures in mod_declaration.captures_iter(content) {
+                imports.push(format!("mod {}", &captures[1]));
+            }
+
+            for captures in use_declaration.captures_iter(content) {
+                imports.extend(expand_use_tree(&captures[1]));
+            }
+        },
+    }
+
+    let mut unique: Vec<String> = Vec::new();
+
+    for import in imports {
+        if !unique.contains(&import) {
+            unique.push(import);
+        }
+    }
+
+    Ok(unique)
+}
+
+// Flattens one level of `a::{b, c::d}` into `a::b` and `a::c::d`
+fn expand_use_tree(tree: &str) -> Vec<String> {
+    // Renames (`a::b as c`) don't change which module is used
+    let tree: String = tree.split(',')
+        .map(|item| item.split(" as ").next().unwrap_or(item))
+        .collect::<Vec<_>>()
+        .join(",");
+    let tree: String = tree.split_whitespace().collect();
+
+    match (tree.find('{'), tree.rfind('}')) {
+        (Some(open), Some(close)) if open < close => {
+            let prefix = &tree[..open];
+            split_top_level(&tree[open + 1..close])
+                .into_iter()
+                .filter(|item| !item.is_empty())
+                .map(|item| format!("{}{}", prefix, item))
+                .collect()
+        },
+        _ => vec![tree],
+    }
+}
+
+// Splits on commas that aren't inside nested braces
+fn split_top_level(items: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in items.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&items[start..i]);
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+
+    parts.push(&items[start..]);
+    parts
+}
+
+// Files an import could refer to, most likely first. None means the import isn't local
+// (a package or the standard library) and shouldn't be reported as unresolved.
+fn import_candidates(language: ImportLanguage, file: &Path, import: &str) -> Option<Vec<PathBuf>> {
+    let dir = file.parent()?;
+
+    match language {
+        ImportLanguage::JavaScript => {
+            if !import.starts_with("./") && !import.starts_with("../") {
+                return None;
+            }
+
+            let base = dir.join(import);
+            let mut candidates = vec![base.clone()];
+
+            for ext in JS_EXTENSIONS {
+                candidates.push(PathBuf::from(format!("{}.{}", base.display(), ext)));
+            }
+
+            for ext in JS_EXTENSIONS {
+                candidates.push(base.join(format!("index.{}", ext)));
+            }
+
+            Some(candidates)
+        },
+        ImportLanguage::Python => {
+            let dots = import.chars().take_while(|&c| c == '.').count();
+            let module: Vec<&str> = import[dots..].split('.').filter(|p| !p.is_empty()).collect();
+
+            let base = if dots > 0 {
+                // One dot is the current package, each further dot goes up a level
+                let mut base = dir.to_path_buf();
+                for _ in 1..dots {
+                    base = base.parent()?.to_path_buf();
+                }
+                base
+            } else {
+                dir.to_path_buf()
+            };
+
+            let mut candidates = python_module_files(&base, &module);
+
+            // Absolute imports may also be rooted at a package above this file
+            if dots == 0 {
+                for ancestor in dir.ancestors().skip(1) {
+                    candidates.extend(python_module_files(ancestor, &module));
+                }
+
+                // Not found anywhere nearby: most likely stdlib or a third-party package
+                if !candidates.iter().any(|c| c.is_file()) {
+                    return None;
+                }
+            }
+
+            Some(candidates)
+        },
+        ImportLanguage::Rust => rust_candidates(file, import),
+    }
+}
+
+fn python_module_files(base: &Path, module: &[&str]) -> Vec<PathBuf> {
+    if module.is_empty() {
+        return vec![base.join("__init__.py")];
+    }
+
+    let path = module.iter().fold(base.to_path_buf(), |path, part| path.join(part));
+
+    vec![path.with_extension("py"), path.join("__init__.py")]
+}
+
+fn rust_candidates(file: &Path, import: &str) -> Option<Vec<PathBuf>> {
+    // Directory holding this module's child modules
+    let module_dir = match file.file_name()?.to_str()? {
+        "mod.rs" | "lib.rs" | "main.rs" => file.parent()?.to_path_buf(),
+        _ => file.with_extension(""),
+    };
+
+    if let Some(name) = import.strip_prefix("mod ") {
+        return Some(vec![module_dir.join(format!("{}.rs", name)), module_dir.join(name).join("mod.rs")]);
+    }
+
+    let segments: Vec<&str> = import.split("::").collect();
+
+    let crate_src = crate_src_dir(file);
+
+    let (base, rest) = match segments.first().copied()? {
+        "crate" => (crate_src.clone()?, &segments[1..]),
+        "self" => (module_dir, &segments[1..]),
+        "super" => {
+            let supers = segments.iter().take_while(|s| **s == "super").count();
+            let mut base = module_dir;
+            for _ in 0..supers {
+                base = base.parent()?.to_path_buf();
+            }
+            (base, &segments[supers..])
+        },
+        // Another crate
+        _ => return None,
+    };
+
+    // The path may end in an item (`reader::read_file`) rather than a module, so try
+    // the longest module path first and back off one segment at a time
+    let mut candidates = Vec::new();
+
+    for len in (0..=rest.len()).rev() {
+        let module = rest[..len].iter().fold(base.clone(), |path, part| path.join(part));
+
+        // Items defined at the crate root
+        if crate_src.as_ref() == Some(&module) {
+            candidates.push(module.join("lib.rs"));
+            candidates.push(module.join("main.rs"));
+            continue;
+        }
+
+        candidates.push(module.with_extension("rs"));
+        candidates.push(module.join("mod.rs"));
+    }
+
+    Some(candidates)
+}
+
+// The `src` directory of the crate containing the file
+fn crate_src_dir(file: &Path) -> Option<PathBuf> {
+    file.ancestors()
+        .find(|dir| dir.join("Cargo.toml").is_file())
+        .map(|dir| dir.join("src"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn python_from_imports_stay_on_their_line() {
+        let content = "from . import a, b\nx = 1\n";
+
+        assert_eq!(extract_imports(ImportLanguage::Python, content).unwrap(), vec![".a", ".b"]);
+    }
+
+    #[test]
+    fn python_parenthesized_from_imports() {
+        let content = "from .. import (\n    models,  # the ORM\n    views as v,\n)\nimport os\n";
+
+        assert_eq!(extract_imports(ImportLanguage::Python, content).unwrap(), vec!["..models", "..views", "os"]);
+    }
+}
@@ -1,8 +1,14 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
+use crate::config::IoLimits;
+use crate::error;
+
+pub mod imports;
 pub mod storage;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -10,6 +16,12 @@ pub struct WorkspaceFolder {
     pub id: String,
     pub path: String,
     pub name: String,
+    // Overrides the workspace-level gitignore preference for this folder when set
+    #[serde(default)]
+    pub use_git_ignore: Option<bool>,
+    // Encoding label used to decode this folder's files in generated prompts (UTF-8 when unset)
+    #[serde(default)]
+    pub encoding: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -19,6 +31,41 @@ pub struct Workspace {
     pub folders: Vec<WorkspaceFolder>,
     pub created_at: i64,
     pub updated_at: i64,
+    // When the workspace was last opened; tracks usage, unlike `updated_at`
+    #[serde(default)]
+    pub last_opened_at: Option<i64>,
+    // Files the user almost always includes, for quick selection
+    #[serde(default)]
+    pub pinned_paths: Vec<String>,
+    // Pins dropped on load because their files no longer exist, until list_pinned_files reports them
+    #[serde(default)]
+    pub pruned_pins: usize,
+    // Fence language for specific files (path -> language), for extensions the generator guesses wrong
+    #[serde(default)]
+    pub language_overrides: HashMap<String, String>,
+    // Keep folders sorted by name as they're added or renamed
+    #[serde(default)]
+    pub auto_sort_folders: bool,
+}
+
+impl Workspace {
+    // Appends the folder, or inserts it in name order when auto-sorting
+    fn add_folder(&mut self, folder: WorkspaceFolder) {
+        if self.auto_sort_folders {
+            let name = folder.name.to_lowercase();
+            let index = self.folders.partition_point(|f| f.name.to_lowercase() <= name);
+            self.folders.insert(index, folder);
+        } else {
+            self.folders.push(folder);
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PinnedFiles {
+    pub paths: Vec<String>,
+    // How many pins were dropped because their files no longer exist
+    pub pruned: usize,
 }
 
 // Create a new workspace
@@ -31,6 +78,11 @@ pub async fn create_workspace(name: &str) -> Result<Workspace> {
         folders: Vec::new(),
         created_at: now,
         updated_at: now,
+        last_opened_at: None,
+        pinned_paths: Vec::new(),
+        pruned_pins: 0,
+        language_overrides: HashMap::new(),
+        auto_sort_folders: false,
     };
 
     let mut workspaces = storage::load_workspaces().await?;
@@ -40,12 +92,35 @@ pub async fn create_workspace(name: &str) -> Result<Workspace> {
     Ok(workspace)
 }
 
+// Create a workspace named after a directory, with that directory as its first folder
+pub async fn create_workspace_from_path(path: &str) -> Result<Workspace> {
+    let path_obj = Path::new(path);
+
+    // Validate before creating anything so a bad path doesn't leave an empty workspace behind
+    if !path_obj.exists() {
+        return Err(error::not_found(format!("Path does not exist: {}", path)));
+    }
+
+    if !path_obj.is_dir() {
+        return Err(error::invalid_input(format!("Path is not a directory: {}", path)));
+    }
+
+    let name = path_obj.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string());
+
+    let workspace = create_workspace(&name).await?;
+    add_folder_to_workspace(&workspace.id, path, None).await?;
+
+    get_workspace(&workspace.id).await
+}
+
 // Update an existing workspace
 pub async fn update_workspace(id: &str, name: &str) -> Result<Workspace> {
     let mut workspaces = storage::load_workspaces().await?;
 
     let workspace_index = workspaces.iter().position(|w| w.id == id)
-        .ok_or_else(|| anyhow::anyhow!("Workspace not found"))?;
+        .ok_or_else(|| error::not_found("Workspace not found"))?;
 
     workspaces[workspace_index].name = name.to_string();
     workspaces[workspace_index].updated_at = chrono::Utc::now().timestamp();
@@ -77,21 +152,172 @@ pub async fn get_workspace(id: &str) -> Result<Workspace> {
 
     let workspace = workspaces.iter()
         .find(|w| w.id == id)
-        .ok_or_else(|| anyhow::anyhow!("Workspace not found"))?;
+        .ok_or_else(|| error::not_found("Workspace not found"))?;
 
     Ok(workspace.clone())
 }
 
+// Clear the pins and usage timestamp, keeping the name and folders
+pub async fn reset_workspace_state(id: &str) -> Result<Workspace> {
+    let mut workspaces = storage::load_workspaces().await?;
+
+    let workspace_index = workspaces.iter().position(|w| w.id == id)
+        .ok_or_else(|| error::not_found("Workspace not found"))?;
+
+    let workspace = &mut workspaces[workspace_index];
+    workspace.pinned_paths.clear();
+    workspace.pruned_pins = 0;
+    workspace.last_opened_at = None;
+    workspace.updated_at = chrono::Utc::now().timestamp();
+
+    let reset_workspace = workspace.clone();
+    storage::save_workspaces(&workspaces).await?;
+
+    Ok(reset_workspace)
+}
+
+// Set the fence language used for a file in generated prompts; None or an empty language removes the override
+pub async fn set_language_override(workspace_id: &str, path: &str, language: Option<&str>) -> Result<Workspace> {
+    let mut workspaces = storage::load_workspaces().await?;
+
+    let workspace_index = workspaces.iter().position(|w| w.id == workspace_id)
+        .ok_or_else(|| error::not_found("Workspace not found"))?;
+
+    let workspace = &mut workspaces[workspace_index];
+
+    // Stored canonically so a differently spelled path replaces or clears the same entry
+    let path = Path::new(path).canonicalize()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| path.to_string());
+
+    match language.map(|l| l.trim()).filter(|l| !l.is_empty()) {
+        Some(language) => workspace.language_overrides.insert(path, language.to_string()),
+        None => workspace.language_overrides.remove(&path),
+    };
+
+    workspace.updated_at = chrono::Utc::now().timestamp();
+
+    let updated_workspace = workspace.clone();
+    storage::save_workspaces(&workspaces).await?;
+
+    Ok(updated_workspace)
+}
+
+// Mark a workspace as opened now without touching `updated_at`
+pub async fn touch_workspace(id: &str) -> Result<Workspace> {
+    let mut workspaces = storage::load_workspaces().await?;
+
+    let workspace_index = workspaces.iter().position(|w| w.id == id)
+        .ok_or_else(|| error::not_found("Workspace not found"))?;
+
+    workspaces[workspace_index].last_opened_at = Some(chrono::Utc::now().timestamp());
+
+    let touched_workspace = workspaces[workspace_index].clone();
+    storage::save_workspaces(&workspaces).await?;
+
+    Ok(touched_workspace)
+}
+
+// Get the most recently opened workspaces, newest first
+pub async fn list_recent_workspaces(limit: usize) -> Result<Vec<Workspace>> {
+    let mut workspaces: Vec<Workspace> = storage::load_workspaces().await?
+        .into_iter()
+        .filter(|w| w.last_opened_at.is_some())
+        .collect();
+
+    workspaces.sort_by_key(|w| std::cmp::Reverse(w.last_opened_at));
+    workspaces.truncate(limit);
+
+    Ok(workspaces)
+}
+
+// Pin a file in a workspace
+pub async fn pin_file(workspace_id: &str, path: &str) -> Result<Vec<String>> {
+    if !Path::new(path).is_file() {
+        return Err(error::invalid_input(format!("Path is not a file: {}", path)));
+    }
+
+    let mut workspaces = storage::load_workspaces().await?;
+
+    let workspace_index = workspaces.iter().position(|w| w.id == workspace_id)
+        .ok_or_else(|| error::not_found("Workspace not found"))?;
+
+    if !workspaces[workspace_index].pinned_paths.iter().any(|p| p == path) {
+        workspaces[workspace_index].pinned_paths.push(path.to_string());
+        workspaces[workspace_index].updated_at = chrono::Utc::now().timestamp();
+        storage::save_workspaces(&workspaces).await?;
+    }
+
+    Ok(workspaces[workspace_index].pinned_paths.clone())
+}
+
+// Unpin a file in a workspace
+pub async fn unpin_file(workspace_id: &str, path: &str) -> Result<Vec<String>> {
+    let mut workspaces = storage::load_workspaces().await?;
+
+    let workspace_index = workspaces.iter().position(|w| w.id == workspace_id)
+        .ok_or_else(|| error::not_found("Workspace not found"))?;
+
+    let pinned = &mut workspaces[workspace_index].pinned_paths;
+    let before = pinned.len();
+    pinned.retain(|p| p != path);
+
+    if pinned.len() < before {
+        workspaces[workspace_index].updated_at = chrono::Utc::now().timestamp();
+        storage::save_workspaces(&workspaces).await?;
+    }
+
+    Ok(workspaces[workspace_index].pinned_paths.clone())
+}
+
+// Get the pinned files of a workspace, with how many missing pins were dropped since the last call
+pub async fn list_pinned_files(workspace_id: &str) -> Result<PinnedFiles> {
+    let mut workspaces = storage::load_workspaces().await?;
+
+    let workspace_index = workspaces.iter().position(|w| w.id == workspace_id)
+        .ok_or_else(|| error::not_found("Workspace not found"))?;
+
+    // Report each pruned pin once
+    let pruned = std::mem::take(&mut workspaces[workspace_index].pruned_pins);
+
+    if pruned > 0 {
+        storage::save_workspaces(&workspaces).await?;
+    }
+
+    Ok(PinnedFiles {
+        paths: workspaces[workspace_index].pinned_paths.clone(),
+        pruned,
+    })
+}
+
+// Drops pins whose files no longer exist, counting them on their workspace. Returns whether any were dropped.
+fn prune_missing_pins(workspaces: &mut [Workspace]) -> bool {
+    let mut pruned_any = false;
+
+    for workspace in workspaces.iter_mut() {
+        let before = workspace.pinned_paths.len();
+        workspace.pinned_paths.retain(|p| Path::new(p).is_file());
+        let pruned = before - workspace.pinned_paths.len();
+
+        if pruned > 0 {
+            workspace.pruned_pins += pruned;
+            pruned_any = true;
+        }
+    }
+
+    pruned_any
+}
+
 // Add a folder to a workspace
 pub async fn add_folder_to_workspace(workspace_id: &str, path: &str, name: Option<&str>) -> Result<WorkspaceFolder> {
     let path_obj = Path::new(path);
 
     if !path_obj.exists() {
-        anyhow::bail!("Path does not exist: {}", path);
+        return Err(error::not_found(format!("Path does not exist: {}", path)));
     }
 
     if !path_obj.is_dir() {
-        anyhow::bail!("Path is not a directory: {}", path);
+        return Err(error::invalid_input(format!("Path is not a directory: {}", path)));
     }
 
     let folder_name = name.map(|n| n.to_string()).unwrap_or_else(|| {
@@ -104,14 +330,16 @@ pub async fn add_folder_to_workspace(workspace_id: &str, path: &str, name: Optio
         id: Uuid::new_v4().to_string(),
         path: path.to_string(),
         name: folder_name,
+        use_git_ignore: None,
+        encoding: None,
     };
 
     let mut workspaces = storage::load_workspaces().await?;
 
     let workspace_index = workspaces.iter().position(|w| w.id == workspace_id)
-        .ok_or_else(|| anyhow::anyhow!("Workspace not found"))?;
+        .ok_or_else(|| error::not_found("Workspace not found"))?;
 
-    workspaces[workspace_index].folders.push(folder.clone());
+    workspaces[workspace_index].add_folder(folder.clone());
     workspaces[workspace_index].updated_at = chrono::Utc::now().timestamp();
 
     storage::save_workspaces(&workspaces).await?;
@@ -124,7 +352,7 @@ pub async fn remove_folder_from_workspace(workspace_id: &str, folder_id: &str) -
     let mut workspaces = storage::load_workspaces().await?;
 
     let workspace_index = workspaces.iter().position(|w| w.id == workspace_id)
-        .ok_or_else(|| anyhow::anyhow!("Workspace not found"))?;
+        .ok_or_else(|| error::not_found("Workspace not found"))?;
 
     workspaces[workspace_index].folders.retain(|f| f.id != folder_id);
     workspaces[workspace_index].updated_at = chrono::Utc::now().timestamp();
@@ -134,48 +362,484 @@ pub async fn remove_folder_from_workspace(workspace_id: &str, folder_id: &str) -
     Ok(())
 }
 
+/// Moves a folder (keeping its name and settings) from one workspace to another in a single save.
+/// The folder gets a new id if the target already has a folder with the same one.
+pub async fn move_folder_between_workspaces(source_id: &str, target_id: &str, folder_id: &str) -> Result<WorkspaceFolder> {
+    if source_id == target_id {
+        return Err(error::invalid_input("Source and target workspace are the same"));
+    }
+
+    let mut workspaces = storage::load_workspaces().await?;
+
+    let source_index = workspaces.iter().position(|w| w.id == source_id)
+        .ok_or_else(|| error::not_found("Source workspace not found"))?;
+    let target_index = workspaces.iter().position(|w| w.id == target_id)
+        .ok_or_else(|| error::not_found("Target workspace not found"))?;
+
+    let folder_index = workspaces[source_index].folders.iter().position(|f| f.id == folder_id)
+        .ok_or_else(|| error::not_found("Folder not found"))?;
+
+    let mut folder = workspaces[source_index].folders.remove(folder_index);
+
+    if workspaces[target_index].folders.iter().any(|f| f.id == folder.id) {
+        folder.id = Uuid::new_v4().to_string();
+    }
+
+    let now = chrono::Utc::now().timestamp();
+
+    workspaces[source_index].updated_at = now;
+    workspaces[target_index].add_folder(folder.clone());
+    workspaces[target_index].updated_at = now;
+
+    storage::save_workspaces(&workspaces).await?;
+
+    Ok(folder)
+}
+
 // Update a folder in a workspace
-pub async fn update_folder(workspace_id: &str, folder_id: &str, name: &str) -> Result<WorkspaceFolder> {
+// An empty `encoding` clears the folder's encoding back to UTF-8
+pub async fn update_folder(workspace_id: &str, folder_id: &str, name: Option<&str>, use_git_ignore: Option<bool>, encoding: Option<&str>) -> Result<WorkspaceFolder> {
     let mut workspaces = storage::load_workspaces().await?;
 
     let workspace_index = workspaces.iter().position(|w| w.id == workspace_id)
-        .ok_or_else(|| anyhow::anyhow!("Workspace not found"))?;
+        .ok_or_else(|| error::not_found("Workspace not found"))?;
 
     let folder_index = workspaces[workspace_index].folders.iter().position(|f| f.id == folder_id)
-        .ok_or_else(|| anyhow::anyhow!("Folder not found"))?;
+        .ok_or_else(|| error::not_found("Folder not found"))?;
 
-    workspaces[workspace_index].folders[folder_index].name = name.to_string();
-    workspaces[workspace_index].updated_at = chrono::Utc::now().timestamp();
+    if let Some(new_name) = name {
+        workspaces[workspace_index].folders[folder_index].name = new_name.to_string();
+    }
+
+    if let Some(git_ignore) = use_git_ignore {
+        workspaces[workspace_index].folders[folder_index].use_git_ignore = Some(git_ignore);
+    }
+
+    if let Some(label) = encoding {
+        let label = label.trim();
+
+        if !label.is_empty() && encoding_rs::Encoding::for_label(label.as_bytes()).is_none() {
+            return Err(error::invalid_input(format!(
+                "Unknown encoding: {}. Supported encodings: {}",
+                label,
+                crate::fs::reader::SUPPORTED_ENCODINGS.join(", ")
+            )));
+        }
+
+        workspaces[workspace_index].folders[folder_index].encoding = Some(label.to_string()).filter(|l| !l.is_empty());
+    }
 
     let updated_folder = workspaces[workspace_index].folders[folder_index].clone();
 
+    // A rename can move the folder out of order
+    if name.is_some() && workspaces[workspace_index].auto_sort_folders {
+        sort_folders(&mut workspaces[workspace_index].folders);
+    }
+
+    workspaces[workspace_index].updated_at = chrono::Utc::now().timestamp();
+
     storage::save_workspaces(&workspaces).await?;
 
     Ok(updated_folder)
 }
 
+/// Sorts a workspace's folders by name (case-insensitive, stable). `auto_sort`, when given,
+/// also sets whether folders added or renamed later are kept in order.
+pub async fn sort_workspace_folders(workspace_id: &str, auto_sort: Option<bool>) -> Result<Workspace> {
+    let mut workspaces = storage::load_workspaces().await?;
+
+    let workspace_index = workspaces.iter().position(|w| w.id == workspace_id)
+        .ok_or_else(|| error::not_found("Workspace not found"))?;
+
+    let workspace = &mut workspaces[workspace_index];
+
+    sort_folders(&mut workspace.folders);
+
+    if let Some(auto_sort) = auto_sort {
+        workspace.auto_sort_folders = auto_sort;
+    }
+
+    workspace.updated_at = chrono::Utc::now().timestamp();
+
+    let sorted_workspace = workspace.clone();
+    storage::save_workspaces(&workspaces).await?;
+
+    Ok(sorted_workspace)
+}
+
+fn sort_folders(folders: &mut [WorkspaceFolder]) {
+    folders.sort_by_cached_key(|f| f.name.to_lowercase());
+}
+
+// Why a workspace folder can no longer be used
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum FolderIssue {
+    Missing,
+    NotADirectory,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InvalidFolder {
+    pub folder: WorkspaceFolder,
+    pub issue: FolderIssue,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WorkspaceValidation {
+    pub workspace_id: String,
+    pub valid_folders: Vec<WorkspaceFolder>,
+    pub invalid_folders: Vec<InvalidFolder>,
+}
+
+// Check a folder path on disk
+fn check_folder(folder: &WorkspaceFolder) -> Option<FolderIssue> {
+    let path = Path::new(&folder.path);
+
+    if !path.exists() {
+        Some(FolderIssue::Missing)
+    } else if !path.is_dir() {
+        Some(FolderIssue::NotADirectory)
+    } else {
+        None
+    }
+}
+
+// Report which folders of a workspace are missing or not directories, without changing anything
+pub async fn validate_workspace(workspace_id: &str) -> Result<WorkspaceValidation> {
+    let workspace = get_workspace(workspace_id).await?;
+
+    let mut valid_folders = Vec::new();
+    let mut invalid_folders = Vec::new();
+
+    for folder in workspace.folders {
+        match check_folder(&folder) {
+            Some(issue) => invalid_folders.push(InvalidFolder { folder, issue }),
+            None => valid_folders.push(folder),
+        }
+    }
+
+    Ok(WorkspaceValidation {
+        workspace_id: workspace.id,
+        valid_folders,
+        invalid_folders,
+    })
+}
+
+// Remove folders that are missing or not directories, returning the removed ones
+pub async fn prune_invalid_folders(workspace_id: &str) -> Result<Vec<WorkspaceFolder>> {
+    let mut workspaces = storage::load_workspaces().await?;
+
+    let workspace_index = workspaces.iter().position(|w| w.id == workspace_id)
+        .ok_or_else(|| error::not_found("Workspace not found"))?;
+
+    let (removed, kept): (Vec<WorkspaceFolder>, Vec<WorkspaceFolder>) = workspaces[workspace_index].folders
+        .drain(..)
+        .partition(|f| check_folder(f).is_some());
+
+    workspaces[workspace_index].folders = kept;
+
+    if !removed.is_empty() {
+        workspaces[workspace_index].updated_at = chrono::Utc::now().timestamp();
+        storage::save_workspaces(&workspaces).await?;
+    }
+
+    Ok(removed)
+}
+
+// Maps a folder-relative path as shown in prompts (`folder-name/src/main.rs`) back to an absolute
+// path. Absolute paths and paths not starting with a folder name are returned unchanged.
+pub fn resolve_folder_relative_path(folders: &[WorkspaceFolder], path: &str) -> String {
+    let relative = Path::new(path);
+
+    if relative.is_absolute() {
+        return path.to_string();
+    }
+
+    let mut components = relative.components();
+    let Some(first) = components.next() else {
+        return path.to_string();
+    };
+
+    folders.iter()
+        .find(|folder| folder.name.as_str() == first.as_os_str())
+        .map(|folder| Path::new(&folder.path).join(components.as_path()).to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+// Get the folder roots of a workspace, or of every workspace when no ID is given
+pub async fn get_workspace_roots(workspace_id: Option<&str>) -> Result<Vec<PathBuf>> {
+    let workspaces = match workspace_id {
+        Some(id) => vec![get_workspace(id).await?],
+        None => storage::load_workspaces().await?,
+    };
+
+    let roots = workspaces.iter()
+        .flat_map(|w| w.folders.iter())
+        .map(|f| PathBuf::from(&f.path))
+        .collect();
+
+    Ok(roots)
+}
+
 // Get all files from all folders in a workspace
-pub async fn get_all_files_in_workspace(workspace_id: &str, use_git_ignore: bool) -> Result<Vec<String>> {
+// `use_git_ignore` is the workspace-level default; folders can override it individually
+pub async fn get_all_files_in_workspace(workspace_id: &str, use_git_ignore: bool, limits: &IoLimits) -> Result<Vec<String>> {
     let workspace = get_workspace(workspace_id).await?;
     let mut all_files = Vec::new();
 
-    for folder in workspace.folders {
-        let file_tree = crate::fs::browser::scan_directory(&folder.path, use_git_ignore).await?;
-        collect_file_paths(&file_tree, &mut all_files);
+    for (_, file_tree) in scan_workspace_folders(&workspace, use_git_ignore, limits).await? {
+        collect_file_paths(&file_tree, false, &mut all_files);
+    }
+
+    Ok(dedup_by_canonical_path(all_files))
+}
+
+// Nested folders or symlinks can reach the same file more than once; keep the first path seen
+fn dedup_by_canonical_path(paths: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+
+    paths.into_iter()
+        .filter(|path| {
+            let key = Path::new(path).canonicalize().unwrap_or_else(|_| PathBuf::from(path));
+            seen.insert(key)
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileSize {
+    pub path: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LargestFiles {
+    // The biggest files, largest first
+    pub files: Vec<FileSize>,
+    // Size of every file in the workspace, so callers can show each file's share
+    pub total_bytes: u64,
+}
+
+// Get the `limit` largest files in a workspace using the sizes from the scan
+pub async fn largest_files_in_workspace(workspace_id: &str, limit: usize, use_git_ignore: bool, limits: &IoLimits) -> Result<LargestFiles> {
+    let workspace = get_workspace(workspace_id).await?;
+    let mut files = Vec::new();
+
+    for (_, file_tree) in scan_workspace_folders(&workspace, use_git_ignore, limits).await? {
+        collect_file_sizes(&file_tree, &mut files);
+    }
+
+    let total_bytes = files.iter().map(|f| f.size).sum();
+
+    files.sort_by_key(|f| std::cmp::Reverse(f.size));
+    files.truncate(limit);
+
+    Ok(LargestFiles {
+        files,
+        total_bytes,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChangedFile {
+    pub path: String,
+    // Seconds since the Unix epoch
+    pub modified: i64,
+}
+
+// Workspace files modified after `since` (seconds since the Unix epoch), most recent first
+pub async fn files_changed_since(workspace_id: &str, since: i64, use_git_ignore: bool, limits: &IoLimits) -> Result<Vec<ChangedFile>> {
+    let mut changed = Vec::new();
+
+    for path in get_all_files_in_workspace(workspace_id, use_git_ignore, limits).await? {
+        // Files deleted since the scan, or without a usable mtime, are skipped
+        let modified = tokio::fs::metadata(&path)
+            .await
+            .ok()
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs() as i64);
+
+        if let Some(modified) = modified.filter(|&modified| modified > since) {
+            changed.push(ChangedFile {
+                path,
+                modified,
+            });
+        }
     }
 
-    Ok(all_files)
+    changed.sort_by_key(|f| std::cmp::Reverse(f.modified));
+
+    Ok(changed)
+}
+
+// Select workspace files matching glob patterns relative to each folder root
+// Patterns prefixed with `!` exclude files after the positive patterns are applied
+pub async fn select_files_by_glob(workspace_id: &str, patterns: &[String], use_git_ignore: bool, limits: &IoLimits) -> Result<Vec<String>> {
+    let mut include_patterns = Vec::new();
+    let mut exclude_patterns = Vec::new();
+
+    for pattern in patterns {
+        match pattern.strip_prefix('!') {
+            Some(negated) => exclude_patterns.push(negated),
+            None => include_patterns.push(pattern.as_str()),
+        }
+    }
+
+    let include = build_glob_set(&include_patterns)?;
+    let exclude = build_glob_set(&exclude_patterns)?;
+
+    let workspace = get_workspace(workspace_id).await?;
+    let mut selected = Vec::new();
+
+    for (folder, file_tree) in scan_workspace_folders(&workspace, use_git_ignore, limits).await? {
+        let mut files = Vec::new();
+        collect_file_paths(&file_tree, false, &mut files);
+
+        for file in files {
+            let relative = Path::new(&file).strip_prefix(&folder.path)
+                .map(|p| p.to_string_lossy().replace('\\', "/"))
+                .unwrap_or_else(|_| file.clone());
+
+            // With only negative patterns, everything not excluded is selected
+            let included = include_patterns.is_empty() || include.is_match(&relative) || include.is_match(&file);
+            let excluded = exclude.is_match(&relative) || exclude.is_match(&file);
+
+            if included && !excluded {
+                selected.push(file);
+            }
+        }
+    }
+
+    Ok(selected)
+}
+
+fn build_glob_set(patterns: &[&str]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid glob pattern: {}", pattern))?;
+        builder.add(glob);
+    }
+
+    builder.build().context("Failed to build glob set")
+}
+
+// Scan every folder of a workspace, honoring per-folder gitignore overrides
+async fn scan_workspace_folders<'a>(workspace: &'a Workspace, use_git_ignore: bool, limits: &IoLimits) -> Result<Vec<(&'a WorkspaceFolder, crate::fs::browser::FileItem)>> {
+    let mut trees = Vec::new();
+
+    for folder in &workspace.folders {
+        let folder_git_ignore = folder.use_git_ignore.unwrap_or(use_git_ignore);
+        let file_tree = crate::fs::browser::scan_directory(&folder.path, folder_git_ignore, limits).await?;
+        trees.push((folder, file_tree));
+    }
+
+    Ok(trees)
 }
 
 // Helper function to collect all file paths from a file tree
-fn collect_file_paths(item: &crate::fs::browser::FileItem, paths: &mut Vec<String>) {
+fn collect_file_sizes(item: &crate::fs::browser::FileItem, sizes: &mut Vec<FileSize>) {
     if matches!(item.file_type, crate::fs::browser::FileType::File) {
-        paths.push(item.path.clone());
+        sizes.push(FileSize {
+            path: item.path.clone(),
+            size: item.size,
+        });
     }
 
     if let Some(children) = &item.children {
         for child in children {
-            collect_file_paths(child, paths);
+            collect_file_sizes(child, sizes);
         }
     }
-}
\ No newline at end of file
+}
+
+/// Appends the paths in a scanned tree, depth-first; directories are included only when asked
+pub fn collect_file_paths(item: &crate::fs::browser::FileItem, include_directories: bool, paths: &mut Vec<String>) {
+    match item.file_type {
+        crate::fs::browser::FileType::File => paths.push(item.path.clone()),
+        crate::fs::browser::FileType::Directory if include_directories => paths.push(item.path.clone()),
+        crate::fs::browser::FileType::Directory => {},
+    }
+
+    if let Some(children) = &item.children {
+        for child in children {
+            collect_file_paths(child, include_directories, paths);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn nested_folders_list_each_file_once() {
+        let root = std::env::temp_dir().join(format!("proprompter-nested-{}", Uuid::new_v4()));
+        let inner = root.join("inner");
+        std::fs::create_dir_all(&inner).unwrap();
+        std::fs::write(root.join("top.rs"), "").unwrap();
+        std::fs::write(inner.join("a.rs"), "").unwrap();
+
+        let mut all_files = Vec::new();
+        for folder in [&root, &inner] {
+            let tree = crate::fs::browser::scan_directory(&folder.to_string_lossy(), false, &IoLimits::default()).await.unwrap();
+            collect_file_paths(&tree, false, &mut all_files);
+        }
+        // Scanning both folders lists inner/a.rs twice; a third, differently spelled path must match too
+        all_files.push(inner.join(".").join("a.rs").to_string_lossy().to_string());
+
+        let deduped = dedup_by_canonical_path(all_files);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(deduped, [
+            inner.join("a.rs").to_string_lossy().to_string(),
+            root.join("top.rs").to_string_lossy().to_string(),
+        ]);
+    }
+
+    #[test]
+    fn missing_paths_are_deduped_as_written() {
+        let deduped = dedup_by_canonical_path(vec![
+            "/no/such/file.rs".to_string(),
+            "/no/such/file.rs".to_string(),
+            "/no/such/other.rs".to_string(),
+        ]);
+
+        assert_eq!(deduped, ["/no/such/file.rs", "/no/such/other.rs"]);
+    }
+
+    #[test]
+    fn missing_pins_are_pruned_and_counted() {
+        let dir = std::env::temp_dir().join(format!("proprompter-pins-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let kept = dir.join("main.rs").to_string_lossy().to_string();
+        std::fs::write(&kept, "").unwrap();
+
+        let now = chrono::Utc::now().timestamp();
+        let mut workspaces = vec![Workspace {
+            id: Uuid::new_v4().to_string(),
+            name: "pins".to_string(),
+            folders: Vec::new(),
+            created_at: now,
+            updated_at: now,
+            last_opened_at: None,
+            pinned_paths: vec![kept.clone(), dir.join("gone.rs").to_string_lossy().to_string()],
+            pruned_pins: 1,
+            language_overrides: HashMap::new(),
+            auto_sort_folders: false,
+        }];
+
+        let pruned_first = prune_missing_pins(&mut workspaces);
+        let pruned_again = prune_missing_pins(&mut workspaces);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(pruned_first);
+        assert!(!pruned_again);
+        assert_eq!(workspaces[0].pinned_paths, [kept]);
+        assert_eq!(workspaces[0].pruned_pins, 2);
+    }
+}
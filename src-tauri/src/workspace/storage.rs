@@ -3,7 +3,8 @@ use serde_json;
 use std::path::PathBuf;
 use tokio::fs;
 
-use super::Workspace;
+use crate::fs::writer::set_aside_corrupt_file;
+use super::{prune_missing_pins, Workspace};
 
 // Get the path to the workspaces file
 fn get_workspaces_file_path() -> Result<PathBuf> {
@@ -23,7 +24,7 @@ fn get_workspaces_file_path() -> Result<PathBuf> {
     Ok(workspaces_dir.join("workspaces.json"))
 }
 
-// Load workspaces from storage
+// Load workspaces from storage, dropping pins whose files no longer exist
 pub async fn load_workspaces() -> Result<Vec<Workspace>> {
     let file_path = get_workspaces_file_path()?;
 
@@ -32,12 +33,23 @@ pub async fn load_workspaces() -> Result<Vec<Workspace>> {
         return Ok(Vec::new());
     }
 
-    let content = fs::read_to_string(file_path)
+    let content = fs::read_to_string(&file_path)
         .await
         .context("Failed to read workspaces file")?;
 
-    let workspaces = serde_json::from_str(&content)
-        .context("Failed to parse workspaces file")?;
+    let mut workspaces: Vec<Workspace> = match serde_json::from_str(&content) {
+        Ok(workspaces) => workspaces,
+        Err(err) => {
+            // A truncated or corrupted file would otherwise fail every command that loads it
+            set_aside_corrupt_file(&file_path, &format!("Failed to parse workspaces file: {}", err)).await?;
+
+            return Ok(Vec::new());
+        },
+    };
+
+    if prune_missing_pins(&mut workspaces) {
+        save_workspaces(&workspaces).await?;
+    }
 
     Ok(workspaces)
 }
@@ -0,0 +1,190 @@
+use std::path::Path;
+
+/// How comments are written in a family of languages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommentStyle {
+    // `//` and `/* */` (Rust, JS/TS, Go, Java, C-like languages)
+    CStyle,
+    // `/* */` only (CSS)
+    BlockOnly,
+    // `#` (Python, Ruby, shell, YAML, TOML)
+    Hash,
+}
+
+// Quote characters that start a string literal in each family. Rust is left out of `'`
+// because lifetimes (`'a`) would look like unterminated strings.
+fn string_quotes(extension: &str) -> &'static [char] {
+    match extension {
+        "rs" => &['"'],
+        "js" | "jsx" | "ts" | "tsx" | "mjs" | "cjs" => &['"', '\'', '`'],
+        "go" => &['"', '`'],
+        _ => &['"', '\''],
+    }
+}
+
+/// Picks the comment style for a path, or None when comments shouldn't be touched
+pub fn comment_style(path: &str) -> Option<CommentStyle> {
+    let extension = Path::new(path).extension()?.to_str()?;
+
+    match extension {
+        "rs" | "js" | "jsx" | "ts" | "tsx" | "mjs" | "cjs" | "go" | "java" | "kt" | "swift"
+        | "c" | "h" | "cpp" | "hpp" | "cs" | "scss" => Some(CommentStyle::CStyle),
+        "css" => Some(CommentStyle::BlockOnly),
+        "py" | "rb" | "sh" | "yaml" | "yml" | "toml" => Some(CommentStyle::Hash),
+        _ => None,
+    }
+}
+
+/// Formats `text` as a single-line comment in the file's language, falling back to `//`
+pub fn comment_line(path: &str, text: &str) -> String {
+    let extension = Path::new(path).extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("");
+
+    match extension {
+        "html" | "xml" | "md" | "vue" | "svelte" => format!("<!-- {} -->", text),
+        "sql" | "lua" => format!("-- {}", text),
+        _ => match comment_style(path) {
+            Some(CommentStyle::BlockOnly) => format!("/* {} */", text),
+            Some(CommentStyle::Hash) => format!("# {}", text),
+            _ => format!("// {}", text),
+        },
+    }
+}
+
+/// Removes comments from a file's content, or returns None for unrecognized languages.
+/// With `keep_lines` every input line stays in place (emptied when it only held a comment),
+/// so the result can still be matched up with the original line numbers.
+pub fn strip_comments_for_path(path: &str, content: &str, keep_lines: bool) -> Option<String> {
+    let style = comment_style(path)?;
+    let extension = Path::new(path).extension()?.to_str()?;
+    let quotes = string_quotes(extension);
+
+    Some(match keep_lines {
+        true => strip_comments_in_place(content, style, quotes),
+        false => strip_comments(content, style, quotes),
+    })
+}
+
+/// Removes comments in the given style, leaving anything inside string literals alone
+pub fn strip_comments(content: &str, style: CommentStyle, quotes: &[char]) -> String {
+    collapse_blank_lines(&strip_comments_in_place(content, style, quotes), content.ends_with('\n'))
+}
+
+// Removes comments without dropping any line breaks, so line N of the result is line N of the input
+fn strip_comments_in_place(content: &str, style: CommentStyle, quotes: &[char]) -> String {
+    match style {
+        CommentStyle::CStyle => strip_with(content, Some("//"), Some(("/*", "*/")), quotes),
+        CommentStyle::BlockOnly => strip_with(content, None, Some(("/*", "*/")), quotes),
+        CommentStyle::Hash => {
+            // Keep a shebang line intact
+            match content.strip_prefix("#!") {
+                Some(_) => {
+                    let (shebang, rest) = content.split_at(content.find('\n').map(|i| i + 1).unwrap_or(content.len()));
+                    format!("{}{}", shebang, strip_with(rest, Some("#"), None, quotes))
+                },
+                None => strip_with(content, Some("#"), None, quotes),
+            }
+        }
+    }
+}
+
+// Single pass over the text tracking string literals; comments are dropped but the line
+// breaks inside block comments are kept
+fn strip_with(content: &str, line_marker: Option<&str>, block_markers: Option<(&str, &str)>, quotes: &[char]) -> String {
+    let chars: Vec<char> = content.chars().collect();
+    let mut stripped = String::with_capacity(content.len());
+    let mut in_string: Option<char> = None;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if let Some(quote) = in_string {
+            stripped.push(c);
+
+            if c == '\\' && i + 1 < chars.len() {
+                stripped.push(chars[i + 1]);
+                i += 2;
+                continue;
+            }
+
+            // Only template literals span lines; anything else unterminated ends at the newline
+            if c == quote || (c == '\n' && quote != '`') {
+                in_string = None;
+            }
+
+            i += 1;
+            continue;
+        }
+
+        if quotes.contains(&c) {
+            in_string = Some(c);
+            stripped.push(c);
+            i += 1;
+            continue;
+        }
+
+        if let Some(marker) = line_marker {
+            if starts_with_at(&chars, i, marker) {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+        }
+
+        if let Some((open, close)) = block_markers {
+            if starts_with_at(&chars, i, open) {
+                i += open.chars().count();
+
+                while i < chars.len() && !starts_with_at(&chars, i, close) {
+                    if chars[i] == '\n' {
+                        stripped.push('\n');
+                    }
+                    i += 1;
+                }
+
+                i = (i + close.chars().count()).min(chars.len());
+                continue;
+            }
+        }
+
+        stripped.push(c);
+        i += 1;
+    }
+
+    stripped
+}
+
+fn starts_with_at(chars: &[char], index: usize, pattern: &str) -> bool {
+    pattern.chars()
+        .enumerate()
+        .all(|(offset, expected)| chars.get(index + offset) == Some(&expected))
+}
+
+// Trims trailing whitespace and keeps at most one blank line in a row
+fn collapse_blank_lines(content: &str, trailing_newline: bool) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+
+    for line in content.lines().map(|l| l.trim_end()) {
+        let previous_blank = lines.last().map(|l| l.is_empty()).unwrap_or(true);
+
+        if line.is_empty() && previous_blank {
+            continue;
+        }
+
+        lines.push(line);
+    }
+
+    while lines.last().map(|l| l.is_empty()).unwrap_or(false) {
+        lines.pop();
+    }
+
+    let mut collapsed = lines.join("\n");
+    if trailing_newline {
+        collapsed.push('\n');
+    }
+
+    collapsed
+}
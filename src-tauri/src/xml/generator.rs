@@ -1,53 +1,990 @@
-use anyhow::Result;
-use std::path::Path;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
 
-use crate::fs::reader::read_file;
+use crate::config::IoLimits;
+use crate::error;
+use crate::fs::browser::sort_paths_like_tree;
+use crate::fs::reader::{has_text_extension, read_file, read_file_with_encoding, TEXT_EXTENSIONS};
+use crate::fs::writer::write_file;
+use crate::prompt::redact::redact_secrets;
+use crate::xml::comments::{comment_line, strip_comments_for_path};
+use crate::xml::json_diff::JSON_FORMATTING_INSTRUCTIONS;
+use crate::xml::stubs::stub_bodies_for_path;
 
-pub async fn generate_xml_prompt(file_paths: &[String], user_prompt: &str) -> Result<String> {
+/// How `generate_copy_content` lays out the files
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum CopyFormat {
+    // Each file in its own fenced code block
+    #[default]
+    Fenced,
+    // One continuous text, each file introduced by a `// === path ===` style comment
+    PathComments,
+}
+
+/// The line(s) introducing each file's fenced block, in the XML and fenced copy layouts
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub enum HeaderFormat {
+    // `File: src/main.rs`
+    #[default]
+    FileLabel,
+    // `### src/main.rs`
+    MarkdownHeading,
+    // `<file path="src/main.rs">`, closed with `</file>` after the block
+    XmlTag,
+    // A template with a `{path}` placeholder, plus optional `{lang}` (fence language)
+    // and `{modified}` (filled only when `with_mtimes` is set)
+    Custom(String),
+}
+
+impl HeaderFormat {
+    pub fn validate(&self) -> Result<()> {
+        if let HeaderFormat::Custom(template) = self {
+            if !template.contains("{path}") {
+                return Err(error::invalid_input(format!("Header template must contain {{path}}: {}", template)));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Header and footer around one file's block
+    fn render(&self, file: &PreparedFile, lang: &str) -> (String, String) {
+        match self {
+            HeaderFormat::FileLabel => (format!("File: {}\n", header_path(file)), String::new()),
+            HeaderFormat::MarkdownHeading => (format!("### {}\n", header_path(file)), String::new()),
+            HeaderFormat::XmlTag => {
+                let modified = file.modified.as_ref()
+                    .map(|modified| format!(" modified=\"{}\"", modified))
+                    .unwrap_or_default();
+                (format!("<file path=\"{}\"{}>\n", file.display_path, modified), "</file>\n".to_string())
+            },
+            HeaderFormat::Custom(template) => {
+                let header = template.replace("{path}", &file.display_path)
+                    .replace("{lang}", lang)
+                    .replace("{modified}", file.modified.as_deref().unwrap_or(""));
+                (format!("{}\n", header.trim_end_matches('\n')), String::new())
+            },
+        }
+    }
+}
+
+/// Which formatter `generate_prompt` hands the gathered files to
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum PromptMode {
+    // Plain file blocks followed by the prompt (see `generate_copy_content`)
+    #[default]
+    Copy,
+    // File map, file contents and formatting instructions (see `generate_xml_prompt`)
+    Xml,
+}
+
+/// Content that isn't on disk (a log snippet, an error message) included as if it were a file
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VirtualFile {
+    // Shown in place of a path
+    pub name: String,
+    pub content: String,
+    // Code fence language; guessed from the name's extension when missing
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+/// Options shared by the XML and copy generators
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct GenerateOptions {
+    // Files with more lines than this are cut down, with a notice marking the omitted part
+    pub max_lines_per_file: Option<usize>,
+    // Files with more lines than this are left out entirely (checked before `max_lines_per_file`)
+    pub exclude_above_lines: Option<usize>,
+    // How many of the kept lines come from the end of a truncated file
+    pub tail_lines: usize,
+    // Stop adding files once their combined size would exceed this many bytes
+    pub max_total_bytes: Option<u64>,
+    // Per-file notes keyed by path, shown just above that file's content
+    pub notes: HashMap<String, String>,
+    // Prefix every line with its 1-based number (`  12 | `) so changes can be anchored to lines
+    pub with_line_numbers: bool,
+    // Only read files with a known text extension; others still appear in the file map
+    pub text_extensions_only: bool,
+    // Replaces reader::TEXT_EXTENSIONS as the allowlist when set
+    pub text_extensions: Option<Vec<String>>,
+    // Drop line and block comments from files in recognized languages
+    pub strip_comments: bool,
+    // Decode files with the encodings set on this workspace's folders
+    pub workspace_id: Option<String>,
+    // Replaces XML_FORMATTING_INSTRUCTIONS when set
+    pub formatting_instructions: Option<String>,
+    // Extra guidance added after the formatting instructions
+    pub extra_instructions: Option<String>,
+    // Layout used by the copy generator
+    pub copy_format: CopyFormat,
+    // Replace likely secrets (API keys, tokens, passwords) with ***REDACTED***
+    pub redact_secrets: bool,
+    // Show only declarations, with function bodies replaced by `{ ... }` (Rust and TypeScript/JavaScript)
+    pub stub_bodies: bool,
+    // Pasted content emitted after the real files, with the same formatting
+    pub virtual_files: Vec<VirtualFile>,
+    // Show paths relative to their workspace folder (`folder-name/src/main.rs`) instead of absolute;
+    // needs `workspace_id`. apply_xml_changes maps these back to absolute paths.
+    pub relative_paths: bool,
+    // Add each file's last-modified time (ISO-8601, UTC) to its header
+    pub with_mtimes: bool,
+    // How file headers are written; the PathComments copy layout keeps its own comment headers
+    pub header_format: HeaderFormat,
+    // Start the XML prompt with a <manifest> listing each included file's size and language
+    // (not written by generate_xml_prompt_to_file, which doesn't know the files up front)
+    pub with_manifest: bool,
+    // Fence language per path, used verbatim instead of the extension guess; takes precedence
+    // over the overrides stored on the workspace named by `workspace_id`
+    pub language_overrides: HashMap<String, String>,
+}
+
+impl GenerateOptions {
+    // Counted with a newline scan before the file is read and prepared
+    async fn exceeds_line_limit(&self, path: &str, limits: &IoLimits) -> Result<bool> {
+        match self.exclude_above_lines {
+            Some(max) => Ok(count_file_lines(path, limits).await? > max),
+            None => Ok(false),
+        }
+    }
+
+    // Whether the file's contents may be read under the extension allowlist
+    fn allows_contents(&self, path: &str) -> bool {
+        if !self.text_extensions_only {
+            return true;
+        }
+
+        match &self.text_extensions {
+            Some(extensions) => has_text_extension(path, extensions),
+            None => has_text_extension(path, TEXT_EXTENSIONS),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GeneratedPrompt {
+    pub content: String,
+    pub included_files: Vec<String>,
+    pub skipped_files: Vec<String>,
+    pub truncated_files: Vec<String>,
+    // True when `max_total_bytes` cut the output short
+    pub limit_reached: bool,
+    // Number of redacted secrets per file, for files where any were found
+    #[serde(default)]
+    pub redactions: HashMap<String, usize>,
+    // Paths dropped because they named a file already in the selection
+    #[serde(default)]
+    pub duplicates_removed: usize,
+    // Files left out for exceeding `exclude_above_lines`; also listed in `skipped_files`
+    #[serde(default)]
+    pub line_limited_files: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GeneratedFile {
+    pub path: String,
+    pub bytes: u64,
+    // Files left out for exceeding `exclude_above_lines`
+    #[serde(default)]
+    pub line_limited_files: Vec<String>,
+}
+
+// A selected file after reading it and applying the content options
+struct PreparedFile {
+    path: String,
+    // How the path is shown in the prompt
+    display_path: String,
+    // Fence language override, from a virtual file or the language overrides
+    language: Option<String>,
+    // Last-modified time, only read when `with_mtimes` is set
+    modified: Option<String>,
+    content: String,
+    truncated: bool,
+    comments_stripped: bool,
+    stubbed: bool,
+    redactions: usize,
+}
+
+// The files that made it into the output, plus what happened to the rest
+struct GatheredFiles {
+    files: Vec<PreparedFile>,
+    skipped_files: Vec<String>,
+    line_limited_files: Vec<String>,
+    limit_reached: bool,
+}
+
+impl GatheredFiles {
+    fn into_prompt(self, content: String) -> GeneratedPrompt {
+        GeneratedPrompt {
+            content,
+            included_files: self.files.iter().map(|f| f.path.clone()).collect(),
+            skipped_files: self.skipped_files,
+            truncated_files: self.files.iter().filter(|f| f.truncated).map(|f| f.path.clone()).collect(),
+            limit_reached: self.limit_reached,
+            redactions: self.files.iter()
+                .filter(|f| f.redactions > 0)
+                .map(|f| (f.path.clone(), f.redactions))
+                .collect(),
+            duplicates_removed: 0,
+            line_limited_files: self.line_limited_files,
+        }
+    }
+}
+
+/// Reads the selection once and formats it for the requested mode
+pub async fn generate_prompt(file_paths: &[String], prompt: &str, mode: PromptMode, options: &GenerateOptions, limits: &IoLimits) -> Result<GeneratedPrompt> {
+    match mode {
+        PromptMode::Copy => {
+            let prompts: Vec<String> = if prompt.trim().is_empty() {
+                Vec::new()
+            } else {
+                vec![prompt.to_string()]
+            };
+
+            generate_copy_content(file_paths, &prompts, options, limits).await
+        },
+        PromptMode::Xml => generate_xml_prompt(file_paths, prompt, options, limits).await,
+    }
+}
+
+/// The `<file_map>` contents generate_xml_prompt would produce for the selection, without reading any files
+pub async fn preview_file_map(file_paths: &[String], options: &GenerateOptions) -> Result<String> {
+    let (file_paths, _) = dedup_paths(file_paths);
+    let map_paths = display_paths(&file_paths, &folder_prefixes(options).await?);
+
+    Ok(format!("{}{}", generate_file_tree(&map_paths)?, virtual_file_map(options)))
+}
+
+pub async fn generate_xml_prompt(file_paths: &[String], user_prompt: &str, options: &GenerateOptions, limits: &IoLimits) -> Result<GeneratedPrompt> {
+    let (file_paths, duplicates_removed) = dedup_paths(file_paths);
+    let file_paths = file_paths.as_slice();
+
+    let mut content_paths: Vec<String> = file_paths.iter()
+        .filter(|path| Path::new(path).is_file())
+        .cloned()
+        .collect();
+
+    // Same order as the file tree, so output is deterministic whatever order the selection arrives in
+    sort_paths_like_tree(&mut content_paths);
+
+    let gathered = gather_files(&content_paths, options, limits).await?;
+
+    let map_paths = display_paths(file_paths, &folder_prefixes(options).await?);
+    let xml = format_xml_prompt(&map_paths, &gathered.files, user_prompt, options)?;
+
+    let mut prompt = gathered.into_prompt(xml);
+    prompt.duplicates_removed = duplicates_removed;
+
+    Ok(prompt)
+}
+
+/// Builds the plain copy-mode output: each file in a fenced block, followed by the prompts
+pub async fn generate_copy_content(file_paths: &[String], prompts: &[String], options: &GenerateOptions, limits: &IoLimits) -> Result<GeneratedPrompt> {
+    let (file_paths, duplicates_removed) = dedup_paths(file_paths);
+    let gathered = gather_files(&file_paths, options, limits).await?;
+
+    let content = format_copy_content(&gathered.files, prompts, options);
+
+    let mut prompt = gathered.into_prompt(content);
+    prompt.duplicates_removed = duplicates_removed;
+
+    Ok(prompt)
+}
+
+// Drops paths naming a file already seen (compared canonically, so `./a` and `a` match),
+// keeping the first occurrence. Returns the remaining paths and how many were dropped.
+fn dedup_paths(file_paths: &[String]) -> (Vec<String>, usize) {
+    let mut seen = std::collections::HashSet::new();
+    let mut unique = Vec::with_capacity(file_paths.len());
+
+    for path in file_paths {
+        if seen.insert(canonical_key(path)) {
+            unique.push(path.clone());
+        }
+    }
+
+    let removed = file_paths.len() - unique.len();
+    (unique, removed)
+}
+
+// The canonical form of a path, or the path as given when it doesn't resolve
+fn canonical_key(path: &str) -> PathBuf {
+    Path::new(path).canonicalize().unwrap_or_else(|_| PathBuf::from(path))
+}
+
+// File map, file contents and trailer for the XML mode; `file_paths` are the paths as displayed
+fn format_xml_prompt(file_paths: &[String], files: &[PreparedFile], user_prompt: &str, options: &GenerateOptions) -> Result<String> {
     let mut xml = String::new();
 
+    if options.with_manifest {
+        xml.push_str(&format_manifest(files));
+    }
+
     // Start with file map (directory structure)
     xml.push_str("<file_map>\n");
     xml.push_str(&generate_file_tree(file_paths)?);
+    xml.push_str(&virtual_file_map(options));
     xml.push_str("</file_map>\n\n");
 
     // Add file contents
     xml.push_str("<file_contents>\n");
 
+    for file in files {
+        xml.push_str(&format_file_block(file, options));
+    }
+
+    xml.push_str("</file_contents>\n\n");
+
+    xml.push_str(&format_trailer(user_prompt, options));
+
+    Ok(xml)
+}
+
+// File blocks in the chosen copy layout, followed by the prompts
+fn format_copy_content(files: &[PreparedFile], prompts: &[String], options: &GenerateOptions) -> String {
+    let mut content = String::new();
+
+    // Add file contents with clear headers
+    match options.copy_format {
+        CopyFormat::Fenced => {
+            for file in files {
+                let (header, footer) = options.header_format.render(file, file_language(file));
+
+                content.push_str(&format!(
+                    "{}{}```\n{}\n```\n{}\n",
+                    header,
+                    format_annotations(file, options),
+                    file.content,
+                    footer
+                ));
+            }
+        },
+        CopyFormat::PathComments => {
+            content.push_str(&format_path_comment_files(files, options));
+        },
+    }
+
+    // Add prompts
+    if !prompts.is_empty() {
+        content.push_str("===== Prompts =====\n\n");
+
+        for prompt in prompts {
+            content.push_str(&format!("{}\n\n", prompt));
+        }
+    }
+
+    content
+}
+
+// Concatenates the files without fences, each under a header comment in its own language
+fn format_path_comment_files(files: &[PreparedFile], options: &GenerateOptions) -> String {
+    let mut content = String::new();
+
+    for file in files {
+        content.push_str(&comment_line(&file.path, &format!("=== {} ===", header_path(file))));
+        content.push('\n');
+
+        if let Some(note) = options.notes.get(&file.path).map(|n| n.trim()).filter(|n| !n.is_empty()) {
+            content.push_str(&comment_line(&file.path, &format!("Note: {}", note)));
+            content.push('\n');
+        }
+
+        if file.comments_stripped {
+            content.push_str(&comment_line(&file.path, "Comments were stripped from this file"));
+            content.push('\n');
+        }
+
+        if file.stubbed {
+            content.push_str(&comment_line(&file.path, STUB_NOTICE));
+            content.push('\n');
+        }
+
+        if file.redactions > 0 {
+            content.push_str(&comment_line(&file.path, &redaction_notice(file.redactions)));
+            content.push('\n');
+        }
+
+        content.push_str(&file.content);
+        content.push_str("\n\n");
+    }
+
+    if let Some(last) = files.last() {
+        content.push_str(&comment_line(&last.path, "=== end of files ==="));
+        content.push_str("\n\n");
+    }
+
+    content
+}
+
+/// Same prompt as `generate_xml_prompt`, but asking the model to answer with a JSON change list
+/// (see `json_diff::parse_json_diff`). Custom `formatting_instructions` still take precedence.
+pub async fn generate_json_prompt(file_paths: &[String], user_prompt: &str, options: &GenerateOptions, limits: &IoLimits) -> Result<GeneratedPrompt> {
+    let mut options = options.clone();
+    options.formatting_instructions.get_or_insert_with(|| JSON_FORMATTING_INSTRUCTIONS.to_string());
+
+    generate_xml_prompt(file_paths, user_prompt, &options, limits).await
+}
+
+/// Writes an already generated prompt to disk so it can be kept and compared across iterations
+pub async fn save_prompt_output(content: &str, output_path: &str) -> Result<GeneratedFile> {
+    write_file(output_path, content).await?;
+
+    Ok(GeneratedFile {
+        path: output_path.to_string(),
+        bytes: content.len() as u64,
+        line_limited_files: Vec::new(),
+    })
+}
+
+/// Streams the XML prompt straight to `output_path`, holding one file in memory at a time
+pub async fn generate_xml_prompt_to_file(file_paths: &[String], user_prompt: &str, output_path: &str, options: &GenerateOptions, limits: &IoLimits) -> Result<GeneratedFile> {
+    options.header_format.validate()?;
+
+    let output = Path::new(output_path);
+
+    if let Some(parent) = output.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+    }
+
+    let file = tokio::fs::File::create(output)
+        .await
+        .with_context(|| format!("Failed to create output file: {}", output_path))?;
+    let mut writer = BufWriter::new(file);
+    let mut bytes = 0u64;
+
+    let prefixes = folder_prefixes(options).await?;
+    let header = format!(
+        "<file_map>\n{}{}</file_map>\n\n<file_contents>\n",
+        generate_file_tree(&display_paths(file_paths, &prefixes))?,
+        virtual_file_map(options)
+    );
+    writer.write_all(header.as_bytes()).await?;
+    bytes += header.len() as u64;
+
+    let encodings = folder_encodings(options).await?;
+    let languages = language_overrides(options).await?;
+
+    let mut content_paths = file_paths.to_vec();
+    sort_paths_like_tree(&mut content_paths);
+    let mut line_limited_files = Vec::new();
+
+    for path in &content_paths {
+        if Path::new(path).is_file() && options.allows_contents(path) {
+            if options.exceeds_line_limit(path, limits).await? {
+                line_limited_files.push(path.clone());
+                continue;
+            }
+
+            let mut file = prepare_file(path, options, encoding_for(path, &encodings), limits).await?;
+            file.display_path = display_path(path, &prefixes);
+            file.language = languages.get(&canonical_key(path)).cloned();
+
+            let block = format_file_block(&file, options);
+            writer.write_all(block.as_bytes()).await?;
+            bytes += block.len() as u64;
+        }
+    }
+
+    for virtual_file in &options.virtual_files {
+        let file = prepare_virtual_file(virtual_file, options)?;
+        let block = format_file_block(&file, options);
+        writer.write_all(block.as_bytes()).await?;
+        bytes += block.len() as u64;
+    }
+
+    let footer = format!("</file_contents>\n\n{}", format_trailer(user_prompt, options));
+    writer.write_all(footer.as_bytes()).await?;
+    bytes += footer.len() as u64;
+
+    writer.flush()
+        .await
+        .with_context(|| format!("Failed to write output file: {}", output_path))?;
+
+    Ok(GeneratedFile {
+        path: output_path.to_string(),
+        bytes,
+        line_limited_files,
+    })
+}
+
+// Reads the files in order, skipping everything after the byte budget would be exceeded
+async fn gather_files(file_paths: &[String], options: &GenerateOptions, limits: &IoLimits) -> Result<GatheredFiles> {
+    options.header_format.validate()?;
+
+    let mut files = Vec::new();
+    let mut skipped_files = Vec::new();
+    let mut line_limited_files = Vec::new();
+    let mut limit_reached = false;
+    let mut total_bytes = 0u64;
+    let encodings = folder_encodings(options).await?;
+    let prefixes = folder_prefixes(options).await?;
+    let languages = language_overrides(options).await?;
+
     for path in file_paths {
-        if Path::new(path).is_file() {
-            let content = read_file(path).await?;
-            let extension = Path::new(path).extension()
-                .and_then(|ext| ext.to_str())
-                .unwrap_or("");
-
-            let lang_identifier = match extension {
-                "js" => "javascript",
-                "ts" => "typescript",
-                "jsx" | "tsx" => "tsx",
-                "py" => "python",
-                "rs" => "rust",
-                "go" => "go",
-                "java" => "java",
-                "cpp" | "c" | "h" => "cpp",
-                _ => extension
-            };
+        if limit_reached || !options.allows_contents(path) {
+            skipped_files.push(path.clone());
+            continue;
+        }
 
-            xml.push_str(&format!("File: {}\n```{}\n{}\n```\n\n", path, lang_identifier, content));
+        if options.exceeds_line_limit(path, limits).await? {
+            skipped_files.push(path.clone());
+            line_limited_files.push(path.clone());
+            continue;
         }
+
+        let mut file = prepare_file(path, options, encoding_for(path, &encodings), limits).await?;
+        file.display_path = display_path(path, &prefixes);
+        file.language = languages.get(&canonical_key(path)).cloned();
+
+        let file_bytes = file.content.len() as u64;
+
+        if options.max_total_bytes.is_some_and(|max| total_bytes + file_bytes > max) {
+            limit_reached = true;
+            skipped_files.push(path.clone());
+            continue;
+        }
+
+        total_bytes += file_bytes;
+        files.push(file);
     }
 
-    xml.push_str("</file_contents>\n\n");
+    // Virtual files come last and count toward the same budget
+    for virtual_file in &options.virtual_files {
+        let file = prepare_virtual_file(virtual_file, options)?;
+        let file_bytes = file.content.len() as u64;
+
+        if limit_reached || options.max_total_bytes.is_some_and(|max| total_bytes + file_bytes > max) {
+            limit_reached = true;
+            skipped_files.push(virtual_file.name.clone());
+            continue;
+        }
+
+        total_bytes += file_bytes;
+        files.push(file);
+    }
+
+    Ok(GatheredFiles {
+        files,
+        skipped_files,
+        line_limited_files,
+        limit_reached,
+    })
+}
+
+// Folder roots with an explicit encoding in the workspace named by the options
+async fn folder_encodings(options: &GenerateOptions) -> Result<Vec<(PathBuf, String)>> {
+    let Some(workspace_id) = &options.workspace_id else {
+        return Ok(Vec::new());
+    };
+
+    let workspace = crate::workspace::get_workspace(workspace_id).await?;
+
+    let encodings = workspace.folders.into_iter()
+        .filter_map(|folder| folder.encoding.map(|encoding| (PathBuf::from(folder.path), encoding)))
+        .collect();
 
-    xml.push_str("<xml_formatting_instructions>\n");
-    xml.push_str("</xml_formatting_instructions>\n\n");
+    Ok(encodings)
+}
+
+// The workspace's stored language overrides with the ones from the options on top
+async fn language_overrides(options: &GenerateOptions) -> Result<HashMap<PathBuf, String>> {
+    let stored = match &options.workspace_id {
+        Some(workspace_id) => crate::workspace::get_workspace(workspace_id).await?.language_overrides,
+        None => HashMap::new(),
+    };
+
+    // Keyed canonically so `./src/a.rs` and `/abs/src/a.rs` find the same override
+    let mut overrides: HashMap<PathBuf, String> = stored.into_iter()
+        .chain(options.language_overrides.clone())
+        .map(|(path, language)| (canonical_key(&path), language))
+        .collect();
+
+    overrides.retain(|_, language| !language.trim().is_empty());
+
+    Ok(overrides)
+}
+
+// Folder roots and the names that replace them in displayed paths, when `relative_paths` is set
+async fn folder_prefixes(options: &GenerateOptions) -> Result<Vec<(PathBuf, String)>> {
+    let Some(workspace_id) = options.workspace_id.as_ref().filter(|_| options.relative_paths) else {
+        return Ok(Vec::new());
+    };
+
+    let workspace = crate::workspace::get_workspace(workspace_id).await?;
+
+    Ok(workspace.folders.into_iter()
+        .map(|folder| (PathBuf::from(folder.path), folder.name))
+        .collect())
+}
+
+// The path relative to the deepest folder containing it, prefixed with the folder's name;
+// paths outside every folder stay absolute
+fn display_path(path: &str, prefixes: &[(PathBuf, String)]) -> String {
+    prefixes.iter()
+        .filter_map(|(root, name)| Path::new(path).strip_prefix(root).ok().map(|rest| (root, name, rest)))
+        .max_by_key(|(root, _, _)| root.components().count())
+        .map(|(_, name, rest)| Path::new(name).join(rest).to_string_lossy().replace('\\', "/"))
+        .unwrap_or_else(|| path.to_string())
+}
+
+fn display_paths(paths: &[String], prefixes: &[(PathBuf, String)]) -> Vec<String> {
+    paths.iter().map(|path| display_path(path, prefixes)).collect()
+}
+
+// The encoding of the deepest folder containing the path
+fn encoding_for<'a>(path: &str, encodings: &'a [(PathBuf, String)]) -> Option<&'a str> {
+    encodings.iter()
+        .filter(|(root, _)| Path::new(path).starts_with(root))
+        .max_by_key(|(root, _)| root.components().count())
+        .map(|(_, encoding)| encoding.as_str())
+}
+
+// Reads a file and applies the content options to it
+async fn prepare_file(path: &str, options: &GenerateOptions, encoding: Option<&str>, limits: &IoLimits) -> Result<PreparedFile> {
+    let content = match encoding {
+        Some(label) => read_file_with_encoding(path, label, limits).await?,
+        None => read_file(path, limits).await?,
+    };
+
+    let mut file = prepare_content(path, content, options)?;
+
+    if options.with_mtimes {
+        file.modified = modified_time(path).await;
+    }
+
+    Ok(file)
+}
+
+// The file's modification time as ISO-8601 UTC, or None when the platform doesn't report it
+async fn modified_time(path: &str) -> Option<String> {
+    let modified = tokio::fs::metadata(path).await.ok()?.modified().ok()?;
+
+    Some(chrono::DateTime::<chrono::Utc>::from(modified).to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+}
+
+// The path shown in a file's header, followed by its modification time when known
+fn header_path(file: &PreparedFile) -> String {
+    match &file.modified {
+        Some(modified) => format!("{} (modified {})", file.display_path, modified),
+        None => file.display_path.clone(),
+    }
+}
+
+fn prepare_virtual_file(virtual_file: &VirtualFile, options: &GenerateOptions) -> Result<PreparedFile> {
+    let mut file = prepare_content(&virtual_file.name, virtual_file.content.clone(), options)?;
+    file.language = virtual_file.language.clone().filter(|l| !l.trim().is_empty());
+
+    Ok(file)
+}
+
+// Applies the content options to a file's text
+fn prepare_content(path: &str, mut content: String, options: &GenerateOptions) -> Result<PreparedFile> {
+    let mut truncated = false;
+
+    // Redact on the raw content, before line numbers get in the way of the patterns
+    let mut redactions = 0;
+
+    if options.redact_secrets {
+        let redaction = redact_secrets(path, &content);
+        content = redaction.content;
+        redactions = redaction.count;
+    }
+
+    // Stubbing and stripping run on the raw text. When numbering, they keep every line in
+    // place so the lines that are left can be labelled with their real positions.
+    let original = options.with_line_numbers.then(|| content.clone());
+    let mut stubbed = false;
+
+    if options.stub_bodies {
+        if let Some(stubs) = stub_bodies_for_path(path, &content, options.with_line_numbers) {
+            content = stubs;
+            stubbed = true;
+        }
+    }
+
+    let mut comments_stripped = false;
+
+    if options.strip_comments {
+        if let Some(stripped) = strip_comments_for_path(path, &content, options.with_line_numbers) {
+            content = stripped;
+            comments_stripped = true;
+        }
+    }
+
+    // Number lines before truncating so the kept lines show their real positions
+    if let Some(original) = original {
+        content = add_line_numbers(&original, &content);
+    }
+
+    if let Some(max_lines) = options.max_lines_per_file {
+        if let Some(shortened) = truncate_lines(&content, max_lines, options.tail_lines) {
+            content = shortened;
+            truncated = true;
+        }
+    }
+
+    Ok(PreparedFile {
+        path: path.to_string(),
+        display_path: path.to_string(),
+        language: None,
+        modified: None,
+        content,
+        truncated,
+        comments_stripped,
+        stubbed,
+        redactions,
+    })
+}
+
+// Counts newlines, plus a final line without one, scanning the raw bytes without decoding
+// or holding the whole file
+async fn count_file_lines(path: &str, limits: &IoLimits) -> Result<usize> {
+    let _permit = limits.acquire_io_permit().await?;
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("Failed to open file: {}", path))?;
+
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut newlines = 0;
+    let mut last_byte = None;
+
+    loop {
+        let read = file.read(&mut buffer)
+            .await
+            .with_context(|| format!("Failed to read file: {}", path))?;
+
+        if read == 0 {
+            break;
+        }
+
+        newlines += buffer[..read].iter().filter(|&&b| b == b'\n').count();
+        last_byte = Some(buffer[read - 1]);
+    }
+
+    Ok(match last_byte {
+        None | Some(b'\n') => newlines,
+        Some(_) => newlines + 1,
+    })
+}
+
+// Keeps `max_lines` lines (the last `tail_lines` of them taken from the end of the file)
+// and replaces the rest with a notice. Returns None when the file is short enough.
+fn truncate_lines(content: &str, max_lines: usize, tail_lines: usize) -> Option<String> {
+    let lines: Vec<&str> = content.lines().collect();
+
+    if lines.len() <= max_lines {
+        return None;
+    }
+
+    let tail = tail_lines.min(max_lines);
+    let head = max_lines - tail;
+    let omitted = lines.len() - head - tail;
+
+    let mut truncated = lines[..head].join("\n");
+    if head > 0 {
+        truncated.push('\n');
+    }
+
+    truncated.push_str(&format!("... [truncated {} lines] ...", omitted));
+
+    if tail > 0 {
+        truncated.push('\n');
+        truncated.push_str(&lines[lines.len() - tail..].join("\n"));
+    }
+
+    Some(truncated)
+}
+
+// Prefixes each line with a right-aligned line number, sized to the file's line count
+// `content` has the same lines as `original` after stubbing or stripping. Lines those emptied
+// are dropped, and the rest keep the number of the line they came from.
+fn add_line_numbers(original: &str, content: &str) -> String {
+    let line_count = original.lines().count();
+    let width = line_count.to_string().len();
+
+    original.lines()
+        .zip(content.lines())
+        .enumerate()
+        .filter(|(_, (before, after))| !after.trim().is_empty() || before.trim().is_empty())
+        .map(|(index, (before, after))| {
+            let line = if after == before { after } else { after.trim_end() };
+            format!("{:>width$} | {}", index + 1, line, width = width)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Maps a file extension to the language identifier used on code fences
+fn language_identifier(path: &str) -> &str {
+    let extension = Path::new(path).extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("");
+
+    match extension {
+        "js" => "javascript",
+        "ts" => "typescript",
+        "jsx" | "tsx" => "tsx",
+        "py" => "python",
+        "rs" => "rust",
+        "go" => "go",
+        "java" => "java",
+        "cpp" | "c" | "h" => "cpp",
+        _ => extension
+    }
+}
+
+fn format_file_block(file: &PreparedFile, options: &GenerateOptions) -> String {
+    let language = file_language(file);
+    let (header, footer) = options.header_format.render(file, language);
+
+    format!(
+        "{}{}```{}\n{}\n```\n{}\n",
+        header,
+        format_annotations(file, options),
+        language,
+        file.content,
+        footer
+    )
+}
+
+fn file_language(file: &PreparedFile) -> &str {
+    file.language.as_deref().unwrap_or_else(|| language_identifier(&file.path))
+}
+
+// Comment lines shown between a file's header and its content block
+fn format_annotations(file: &PreparedFile, options: &GenerateOptions) -> String {
+    let mut annotations = format_note(options.notes.get(&file.path));
+
+    if file.comments_stripped {
+        annotations.push_str("<!-- Comments were stripped from this file -->\n");
+    }
+
+    if file.stubbed {
+        annotations.push_str(&format!("<!-- {} -->\n", STUB_NOTICE));
+    }
+
+    if file.redactions > 0 {
+        annotations.push_str(&format!("<!-- {} -->\n", redaction_notice(file.redactions)));
+    }
+
+    annotations
+}
+
+const STUB_NOTICE: &str = "Stubbed: only declarations are shown, function bodies are replaced with { ... }";
+
+fn redaction_notice(count: usize) -> String {
+    match count {
+        1 => "1 secret was redacted from this file".to_string(),
+        n => format!("{} secrets were redacted from this file", n),
+    }
+}
+
+// Renders a file note as an HTML-style comment line so it can't be mistaken for file content
+fn format_note(note: Option<&String>) -> String {
+    match note.map(|n| n.trim()).filter(|n| !n.is_empty()) {
+        Some(note) => format!("<!-- Note: {} -->\n", note.replace("-->", "-- >")),
+        None => String::new(),
+    }
+}
+
+/// Tells the model how to answer so `parser::parse_xml_diff` can read the response
+pub const XML_FORMATTING_INSTRUCTIONS: &str = r#"Respond with your changes in this XML format:
+
+<Plan>
+Briefly describe the overall approach.
+</Plan>
+
+<file path="/absolute/path/to/file" action="modify">
+  <change>
+    <description>What this change does</description>
+    <search>
+===
+exact lines from the current file to replace
+===
+    </search>
+    <content>
+===
+the replacement lines
+===
+    </content>
+  </change>
+</file>
+
+Rules:
+- `action` is one of: create, rewrite, modify, delete, regex_replace.
+- create and rewrite take a single <change> whose <content> is the complete file; delete takes no <change>.
+- modify needs a <search> that matches the current file exactly, including whitespace; keep it short but unique.
+- regex_replace treats <search> as a regular expression and <content> as the replacement ($1 refers to a capture group).
+- Wrap every <search> and <content> body in lines containing only `===`.
+- A modify <change> may hold several `===` hunks; the Nth search hunk is replaced by the Nth content hunk.
+- Use the full file paths shown in <file_map>.
+- Inside <search> and <content>, write `<` as `&lt;` and `&` as `&amp;`.
+"#;
+
+const LINE_NUMBER_NOTE: &str = "File contents are shown with line numbers in a `  N | ` gutter. \
+The numbers are annotations, not part of the files: never include them in search or content sections. \
+Use them for start_line/end_line on <change> to replace a line range.\n";
+
+// Everything after the file contents: formatting instructions and the user prompt
+fn format_trailer(user_prompt: &str, options: &GenerateOptions) -> String {
+    let mut trailer = String::new();
+
+    trailer.push_str("<xml_formatting_instructions>\n");
+    let instructions = options.formatting_instructions.as_deref().unwrap_or(XML_FORMATTING_INSTRUCTIONS);
+    trailer.push_str(instructions);
+    if !instructions.ends_with('\n') {
+        trailer.push('\n');
+    }
+
+    if options.with_line_numbers {
+        trailer.push_str(LINE_NUMBER_NOTE);
+    }
+
+    if let Some(extra) = options.extra_instructions.as_deref().filter(|e| !e.trim().is_empty()) {
+        trailer.push_str(extra.trim_end());
+        trailer.push('\n');
+    }
+
+    trailer.push_str("</xml_formatting_instructions>\n\n");
 
     // Add user prompt
-    xml.push_str("<user_instructions>\n");
-    xml.push_str(user_prompt);
-    xml.push_str("\n</user_instructions>\n");
+    trailer.push_str("<user_instructions>\n");
+    trailer.push_str(user_prompt);
+    trailer.push_str("\n</user_instructions>\n");
 
-    Ok(xml)
+    trailer
+}
+
+// One line per included file: path, bytes as included and fence language
+fn format_manifest(files: &[PreparedFile]) -> String {
+    let mut manifest = String::from("<manifest>\n");
+
+    for file in files {
+        let language = match file_language(file) {
+            "" => "unknown",
+            language => language,
+        };
+
+        manifest.push_str(&format!("{} ({} bytes, {})\n", file.display_path, file.content.len(), language));
+    }
+
+    manifest.push_str("</manifest>\n\n");
+    manifest
+}
+
+// File map entries for the virtual files, marked so the model doesn't try to edit them
+fn virtual_file_map(options: &GenerateOptions) -> String {
+    options.virtual_files.iter()
+        .map(|file| format!("{} (pasted content, not a file on disk)\n", file.name))
+        .collect()
 }
 
 fn generate_file_tree(file_paths: &[String]) -> Result<String> {
@@ -62,10 +999,120 @@ fn generate_file_tree(file_paths: &[String]) -> Result<String> {
     Ok(tree)
 }
 
-pub async fn generate_xml_prompt_for_workspace(workspace_id: &str, user_prompt: &str, use_git_ignore: bool) -> Result<String> {
-  // Get all files in the workspace
-  let file_paths = crate::workspace::get_all_files_in_workspace(workspace_id, use_git_ignore).await?;
+pub async fn generate_xml_prompt_for_workspace(workspace_id: &str, user_prompt: &str, use_git_ignore: bool, options: &GenerateOptions, limits: &IoLimits) -> Result<GeneratedPrompt> {
+  // Get all files in the workspace, honoring each folder's gitignore override
+  let file_paths = crate::workspace::get_all_files_in_workspace(workspace_id, use_git_ignore, limits).await?;
+
+  // Decode with the workspace's folder encodings unless the caller chose another workspace
+  let mut options = options.clone();
+  options.workspace_id.get_or_insert_with(|| workspace_id.to_string());
 
   // Use the existing function with the file paths
-  generate_xml_prompt(&file_paths, user_prompt).await
+  generate_xml_prompt(&file_paths, user_prompt, &options, limits).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(path: &str, content: &str, options: GenerateOptions) -> String {
+        let options = GenerateOptions { with_line_numbers: true, ..options };
+        prepare_content(path, content.to_string(), &options).unwrap().content
+    }
+
+    #[test]
+    fn stripped_comments_keep_real_line_numbers() {
+        let content = "let a = 1; // one\n// only a comment\n/* spans\n   lines */\nlet b = 2;\n";
+        let options = GenerateOptions { strip_comments: true, ..Default::default() };
+
+        assert_eq!(numbered("main.rs", content, options), "1 | let a = 1;\n5 | let b = 2;");
+    }
+
+    #[test]
+    fn stripping_keeps_shebang() {
+        let content = "#!/bin/sh\n# comment\necho hi\n";
+        let options = GenerateOptions { strip_comments: true, ..Default::default() };
+
+        assert_eq!(numbered("run.sh", content, options), "1 | #!/bin/sh\n3 | echo hi");
+    }
+
+    #[test]
+    fn stubbed_bodies_keep_real_line_numbers() {
+        let content = "fn a() {\n    1\n}\n\nfn b() {\n    2\n}\n";
+        let options = GenerateOptions { stub_bodies: true, ..Default::default() };
+
+        assert_eq!(numbered("lib.rs", content, options), "1 | fn a() { ... }\n4 | \n5 | fn b() { ... }");
+    }
+
+    #[tokio::test]
+    async fn language_override_beats_extension_mapping() {
+        let dir = std::env::temp_dir().join(format!("proprompter-languages-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("data.rs");
+        std::fs::write(&file, "(a: 1)\n").unwrap();
+
+        // Spelled differently from the selected path, which still finds it
+        let override_path = dir.join(".").join("data.rs").to_string_lossy().to_string();
+        let options = GenerateOptions {
+            language_overrides: HashMap::from([(override_path, "ron".to_string())]),
+            ..Default::default()
+        };
+
+        let selected = file.to_string_lossy().to_string();
+        let prompt = generate_xml_prompt(&[selected], "", &options, &IoLimits::default()).await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(prompt.content.contains("```ron\n"));
+        assert!(!prompt.content.contains("```rust"));
+    }
+
+    #[test]
+    fn plain_numbering_keeps_every_line() {
+        assert_eq!(numbered("notes.txt", "a\n\nb\n", Default::default()), "1 | a\n2 | \n3 | b");
+    }
+
+    #[tokio::test]
+    async fn mtimes_are_annotated_as_utc_seconds() {
+        let dir = std::env::temp_dir().join(format!("proprompter-mtimes-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("main.rs");
+        std::fs::write(&file, "fn main() {}\n").unwrap();
+
+        // 2024-01-02T03:04:05Z
+        let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_704_164_645);
+        std::fs::File::options().write(true).open(&file).unwrap().set_modified(modified).unwrap();
+
+        let selected = file.to_string_lossy().to_string();
+        let labelled = GenerateOptions { with_mtimes: true, ..Default::default() };
+        let tagged = GenerateOptions { with_mtimes: true, header_format: HeaderFormat::XmlTag, ..Default::default() };
+        let labelled = generate_xml_prompt(std::slice::from_ref(&selected), "", &labelled, &IoLimits::default()).await.unwrap();
+        let tagged = generate_xml_prompt(std::slice::from_ref(&selected), "", &tagged, &IoLimits::default()).await.unwrap();
+        let unannotated = generate_xml_prompt(std::slice::from_ref(&selected), "", &GenerateOptions::default(), &IoLimits::default()).await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(labelled.content.contains("main.rs (modified 2024-01-02T03:04:05Z)\n"));
+        assert!(tagged.content.contains("main.rs\" modified=\"2024-01-02T03:04:05Z\">\n"));
+        assert!(!unannotated.content.contains("(modified "));
+    }
+
+    #[tokio::test]
+    async fn files_above_the_line_limit_are_reported() {
+        let dir = std::env::temp_dir().join(format!("proprompter-line-limit-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let long = dir.join("long.csv").to_string_lossy().to_string();
+        let short = dir.join("short.rs").to_string_lossy().to_string();
+        std::fs::write(&long, "a\nb\nc\nd").unwrap();
+        std::fs::write(&short, "a\nb\nc\n").unwrap();
+
+        let options = GenerateOptions { exclude_above_lines: Some(3), ..Default::default() };
+        let paths = [long.clone(), short.clone()];
+        let prompt = generate_xml_prompt(&paths, "", &options, &IoLimits::default()).await.unwrap();
+        let output = dir.join("prompt.xml").to_string_lossy().to_string();
+        let written = generate_xml_prompt_to_file(&paths, "", &output, &options, &IoLimits::default()).await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(prompt.line_limited_files, std::slice::from_ref(&long));
+        assert_eq!(prompt.included_files, [short]);
+        assert_eq!(written.line_limited_files, [long]);
+    }
 }
@@ -0,0 +1,162 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::error;
+use crate::xml::parser::{parse_action, Change, ChangeAction, FileChange};
+
+/// Tells the model how to answer so `parse_json_diff` can read the response
+pub const JSON_FORMATTING_INSTRUCTIONS: &str = r#"Respond with your changes as a JSON array, one object per change:
+
+```json
+[
+  {
+    "path": "/absolute/path/to/file",
+    "action": "modify",
+    "description": "What this change does",
+    "search": "exact lines from the current file to replace\n",
+    "content": "the replacement lines\n"
+  }
+]
+```
+
+Rules:
+- `action` is one of: create, rewrite, modify, delete, regex_replace.
+- create and rewrite put the complete file in `content` and leave out `search`; delete needs only `path` and `action`.
+- modify needs a `search` that matches the current file exactly, including whitespace; keep it short but unique.
+- regex_replace treats `search` as a regular expression and `content` as the replacement ($1 refers to a capture group).
+- Several changes to the same file are separate objects listed one after another.
+- Use the full file paths shown in <file_map>.
+- Output valid JSON: escape newlines as \n, quotes as \" and backslashes as \\.
+"#;
+
+// One element of the array; the flat shape is easier for models to get right than nested changes
+#[derive(Debug, Deserialize)]
+struct JsonChange {
+    path: String,
+    action: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    search: Option<String>,
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    start_line: Option<usize>,
+    #[serde(default)]
+    end_line: Option<usize>,
+}
+
+/// Parses a JSON change list into the same `FileChange`s as `parse_xml_diff`. Text around the
+/// array (a ```json fence, a sentence of preamble) is ignored. Consecutive entries for the same
+/// path and action become one FileChange with several changes.
+pub fn parse_json_diff(response: &str) -> Result<Vec<FileChange>> {
+    let entries = extract_changes(response)?;
+
+    let mut file_changes: Vec<FileChange> = Vec::new();
+
+    for (index, entry) in entries.into_iter().enumerate() {
+        let action = parse_action(&entry.action)
+            .ok_or_else(|| error::parse_error(format!("Invalid action in change {}: {}", index + 1, entry.action)))?;
+
+        if entry.path.trim().is_empty() {
+            return Err(error::parse_error(format!("Change {} has no path", index + 1)));
+        }
+
+        let change = Change {
+            description: entry.description.unwrap_or_default(),
+            search: entry.search,
+            content: entry.content.unwrap_or_default(),
+            start_line: entry.start_line,
+            end_line: entry.end_line,
+        };
+
+        match file_changes.last_mut() {
+            Some(last) if last.path == entry.path && last.action == action && action != ChangeAction::Delete => {
+                last.changes.push(change);
+            },
+            _ => file_changes.push(FileChange {
+                path: entry.path,
+                changes: if action == ChangeAction::Delete { Vec::new() } else { vec![change] },
+                action,
+            }),
+        }
+    }
+
+    Ok(file_changes)
+}
+
+// Reads the change array from a ```json fence when the response has one. Otherwise each `[` is
+// tried in turn until one starts a complete change array, so brackets in the preamble or after
+// the array (`[1]`, "see [docs]") don't get in the way.
+fn extract_changes(response: &str) -> Result<Vec<JsonChange>> {
+    if let Some(fenced) = fenced_json(response) {
+        return serde_json::from_str(fenced).context("Failed to parse JSON changes");
+    }
+
+    let mut first_error = None;
+
+    for (start, _) in response.match_indices('[') {
+        let mut values = serde_json::Deserializer::from_str(&response[start..]).into_iter::<Vec<JsonChange>>();
+
+        match values.next() {
+            Some(Ok(entries)) => return Ok(entries),
+            Some(Err(e)) => {
+                first_error.get_or_insert(e);
+            },
+            None => {},
+        }
+    }
+
+    match first_error {
+        Some(e) => Err(anyhow::Error::new(e).context("Failed to parse JSON changes")),
+        None => Err(error::parse_error("No JSON array found in the response")),
+    }
+}
+
+// The body of the first ```json fence, if the response has a closed one
+fn fenced_json(response: &str) -> Option<&str> {
+    let start = response.find("```json")? + "```json".len();
+    let end = response[start..].find("```")? + start;
+
+    Some(response[start..end].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_the_fenced_block() {
+        let response = r#"Changes [1] below:
+
+```json
+[{"path": "/p/a.rs", "action": "delete"}]
+```
+
+See [the docs]."#;
+
+        let changes = parse_json_diff(response).unwrap();
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "/p/a.rs");
+        assert_eq!(changes[0].action, ChangeAction::Delete);
+    }
+
+    #[test]
+    fn skips_brackets_around_an_unfenced_array() {
+        let response = r#"[Note] One edit:
+[{"path": "/p/a.rs", "action": "create", "content": "let v = [1];\n"}]
+Done [ok]."#;
+
+        let changes = parse_json_diff(response).unwrap();
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].changes[0].content, "let v = [1];\n");
+    }
+
+    #[test]
+    fn reports_missing_or_invalid_arrays() {
+        assert!(parse_json_diff("No changes needed.").unwrap_err().to_string().contains("No JSON array"));
+        assert!(parse_json_diff(r#"[{"path": "/p/a.rs""#).unwrap_err().to_string().contains("Failed to parse JSON changes"));
+    }
+}
@@ -1,5 +1,9 @@
+pub mod comments;
 pub mod generator;
+pub mod json_diff;
 pub mod parser;
+pub mod stubs;
 
-pub use generator::generate_xml_prompt;
-pub use parser::{parse_xml_diff, apply_changes, FileChange, ChangeAction, ChangeResult};
\ No newline at end of file
+pub use generator::{generate_prompt, generate_xml_prompt, generate_json_prompt, generate_copy_content, generate_xml_prompt_to_file, preview_file_map, GenerateOptions, CopyFormat, HeaderFormat, PromptMode, GeneratedPrompt, GeneratedFile, VirtualFile, XML_FORMATTING_INSTRUCTIONS};
+pub use json_diff::{parse_json_diff, JSON_FORMATTING_INSTRUCTIONS};
+pub use parser::{parse_xml_diff, summarize_changes, validate_response_format, apply_changes, diff_file_change, FileChange, ChangeAction, ChangeResult, ApplyOptions, ApplySummary};
\ No newline at end of file
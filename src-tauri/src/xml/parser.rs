@@ -1,10 +1,16 @@
 use anyhow::{Context, Result};
 use quick_xml::events::Event;
 use quick_xml::reader::Reader;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use std::path::{Path, PathBuf};
 
+use crate::config::IoLimits;
+use crate::error;
 use crate::fs::reader::read_file;
-use crate::fs::writer::write_file;
+use crate::fs::sandbox::resolve_within_roots;
+use crate::fs::writer::{normalize_line_endings, tidy_whitespace, write_file_with_eol, BackupLocation, EolMode, LineEnding};
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum ChangeAction {
@@ -12,6 +18,8 @@ pub enum ChangeAction {
     Rewrite,
     Modify,
     Delete,
+    // `search` is a regular expression, `content` the replacement (may reference groups like `$1`)
+    RegexReplace,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -19,6 +27,12 @@ pub struct Change {
     pub description: String,
     pub search: Option<String>,
     pub content: String,
+    // 1-based, inclusive line range replaced by `content` in a Modify change, instead of `search`.
+    // `start_line == end_line + 1` inserts before `start_line` without removing anything.
+    #[serde(default)]
+    pub start_line: Option<usize>,
+    #[serde(default)]
+    pub end_line: Option<usize>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -28,12 +42,187 @@ pub struct FileChange {
     pub changes: Vec<Change>,
 }
 
+// Search blocks with fewer non-whitespace characters are rejected unless allowed explicitly
+const DEFAULT_MIN_SEARCH_CHARS: usize = 8;
+
+/// Options controlling how changes are written to disk
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ApplyOptions {
+    pub eol: EolMode,
+    pub backup_location: BackupLocation,
+    // Write a Modify/RegexReplace file even when some of its changes failed
+    pub allow_partial: bool,
+    // When a Modify search block isn't found verbatim, merge it into the closest matching
+    // region instead of failing, writing conflict markers where the merge isn't clean
+    pub three_way_merge: bool,
+    // Minimum non-whitespace characters in a Modify search block; shorter ones (like a lone `}`)
+    // would match all over the file
+    pub min_search_chars: usize,
+    // Apply search blocks shorter than `min_search_chars` anyway
+    pub allow_short_search: bool,
+    // Strip trailing spaces and tabs from every line of written files
+    pub trim_trailing_whitespace: bool,
+    // End written files with exactly one newline
+    pub ensure_final_newline: bool,
+}
+
+impl Default for ApplyOptions {
+    fn default() -> Self {
+        ApplyOptions {
+            eol: EolMode::default(),
+            backup_location: BackupLocation::default(),
+            allow_partial: false,
+            three_way_merge: false,
+            min_search_chars: DEFAULT_MIN_SEARCH_CHARS,
+            allow_short_search: false,
+            trim_trailing_whitespace: false,
+            ensure_final_newline: false,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ChangeResult {
     pub path: String,
     pub action: ChangeAction,
     pub success: bool,
     pub message: Option<String>,
+    // Number of matches replaced, for RegexReplace changes
+    #[serde(default)]
+    pub replacements: Option<usize>,
+    // Outcome of each individual change, for Modify and RegexReplace
+    #[serde(default)]
+    pub changes: Vec<ChangeStatus>,
+    // True when a three-way merge left conflict markers in the file
+    #[serde(default)]
+    pub has_conflicts: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChangeStatus {
+    // Position of the change within its file
+    pub index: usize,
+    pub description: String,
+    pub success: bool,
+    pub message: Option<String>,
+}
+
+impl ChangeStatus {
+    fn new(index: usize, change: &Change, outcome: Result<()>) -> Self {
+        ChangeStatus {
+            index,
+            description: change.description.clone(),
+            success: outcome.is_ok(),
+            message: outcome.err().map(|e| e.to_string()),
+        }
+    }
+}
+
+/// The results of applying a batch, with the counts a summary banner needs
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApplySummary {
+    pub results: Vec<ChangeResult>,
+    pub succeeded: usize,
+    pub failed: usize,
+    // Successful files per action; regex replacements count as modified
+    pub created: usize,
+    pub modified: usize,
+    pub rewritten: usize,
+    pub deleted: usize,
+}
+
+impl ApplySummary {
+    pub fn from_results(results: Vec<ChangeResult>) -> Self {
+        let succeeded: Vec<&ChangeResult> = results.iter().filter(|r| r.success).collect();
+        let count = |actions: &[ChangeAction]| succeeded.iter().filter(|r| actions.contains(&r.action)).count();
+
+        ApplySummary {
+            succeeded: succeeded.len(),
+            failed: results.len() - succeeded.len(),
+            created: count(&[ChangeAction::Create]),
+            modified: count(&[ChangeAction::Modify, ChangeAction::RegexReplace]),
+            rewritten: count(&[ChangeAction::Rewrite]),
+            deleted: count(&[ChangeAction::Delete]),
+            results,
+        }
+    }
+}
+
+// The content the successful changes leave behind, plus per-change outcomes
+struct NewContent {
+    content: String,
+    replacements: Option<usize>,
+    statuses: Vec<ChangeStatus>,
+    has_conflicts: bool,
+}
+
+impl NewContent {
+    fn whole(content: String) -> Self {
+        NewContent {
+            content,
+            replacements: None,
+            statuses: Vec::new(),
+            has_conflicts: false,
+        }
+    }
+
+    fn failed_count(&self) -> usize {
+        self.statuses.iter().filter(|s| !s.success).count()
+    }
+
+    // Error describing the first failed change, if any
+    fn first_failure(&self) -> Option<anyhow::Error> {
+        self.statuses.iter()
+            .find(|s| !s.success)
+            .map(|s| anyhow::anyhow!("{}", s.message.clone().unwrap_or_default()))
+    }
+}
+
+// What applying one file change did
+struct FileOutcome {
+    written: bool,
+    replacements: Option<usize>,
+    statuses: Vec<ChangeStatus>,
+    has_conflicts: bool,
+}
+
+/// A parse failure with the byte offset in the input where it was detected
+#[derive(Debug)]
+pub struct XmlParseError {
+    pub message: String,
+    pub offset: usize,
+}
+
+impl std::fmt::Display for XmlParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for XmlParseError {}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ResponseValidation {
+    pub valid: bool,
+    pub file_count: usize,
+    pub change_count: usize,
+    pub error: Option<String>,
+    // 1-based position of the error, when the parser could report one
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+/// Maps an action name as written in a response (`modify`, `regex_replace`) to its ChangeAction
+pub fn parse_action(value: &str) -> Option<ChangeAction> {
+    match value.trim() {
+        "create" => Some(ChangeAction::Create),
+        "rewrite" => Some(ChangeAction::Rewrite),
+        "modify" => Some(ChangeAction::Modify),
+        "delete" => Some(ChangeAction::Delete),
+        "regex_replace" => Some(ChangeAction::RegexReplace),
+        _ => None,
+    }
 }
 
 pub async fn parse_xml_diff(xml: &str) -> Result<Vec<FileChange>> {
@@ -44,6 +233,9 @@ pub async fn parse_xml_diff(xml: &str) -> Result<Vec<FileChange>> {
   let mut file_changes = Vec::new();
   let mut current_file: Option<FileChange> = None;
   let mut current_change: Option<Change> = None;
+  // Marker-delimited hunks of the current change's search and content sections
+  let mut search_segments: Vec<String> = Vec::new();
+  let mut content_segments: Vec<String> = Vec::new();
 
   let mut buf = Vec::new();
   // Remove unused variables and use underscore prefix for these state tracking variables
@@ -69,20 +261,17 @@ pub async fn parse_xml_diff(xml: &str) -> Result<Vec<FileChange>> {
                       for attr in e.attributes() {
                           let attr = attr.context("Invalid XML attribute")?;
                           let key = std::str::from_utf8(attr.key.as_ref())
-                              .context("Invalid UTF-8 in attribute key")?;
+                              .map_err(|_| error::parse_error("Invalid UTF-8 in attribute key"))?;
                           let value = attr.decode_and_unescape_value(reader.decoder())
                               .context("Failed to decode attribute value")?;
 
                           match key {
                               "path" => path = value.to_string(),
                               "action" => {
-                                  action = match value.as_ref() {
-                                      "create" => ChangeAction::Create,
-                                      "rewrite" => ChangeAction::Rewrite,
-                                      "modify" => ChangeAction::Modify,
-                                      "delete" => ChangeAction::Delete,
-                                      _ => anyhow::bail!("Invalid action: {}", value),
-                                  };
+                                  action = parse_action(&value).ok_or_else(|| XmlParseError {
+                                      message: format!("Invalid action: {}", value),
+                                      offset: reader.buffer_position() as usize,
+                                  })?;
                               },
                               _ => {}
                           }
@@ -96,10 +285,32 @@ pub async fn parse_xml_diff(xml: &str) -> Result<Vec<FileChange>> {
                   },
                   b"change" => {
                       in_change = true;
+
+                      // Optional line anchors: <change start_line="10" end_line="12">
+                      let mut start_line = None;
+                      let mut end_line = None;
+
+                      for attr in e.attributes() {
+                          let attr = attr.context("Invalid XML attribute")?;
+                          let value = attr.decode_and_unescape_value(reader.decoder())
+                              .context("Failed to decode attribute value")?;
+
+                          match attr.key.as_ref() {
+                              b"start_line" => start_line = Some(parse_line_number(&value)?),
+                              b"end_line" => end_line = Some(parse_line_number(&value)?),
+                              _ => {}
+                          }
+                      }
+
+                      search_segments.clear();
+                      content_segments.clear();
+
                       current_change = Some(Change {
                           description: String::new(),
                           search: None,
                           content: String::new(),
+                          start_line,
+                          end_line,
                       });
                   },
                   b"description" => { in_description = true; },
@@ -120,7 +331,12 @@ pub async fn parse_xml_diff(xml: &str) -> Result<Vec<FileChange>> {
                   b"change" => {
                       in_change = false;
                       if let (Some(ref mut file), Some(change)) = (&mut current_file, current_change.take()) {
-                          file.changes.push(change);
+                          let hunks = split_hunks(change, &search_segments, &content_segments)
+                              .map_err(|message| XmlParseError {
+                                  message,
+                                  offset: reader.buffer_position() as usize,
+                              })?;
+                          file.changes.extend(hunks);
                       }
                   },
                   b"description" => { in_description = false; },
@@ -137,21 +353,22 @@ pub async fn parse_xml_diff(xml: &str) -> Result<Vec<FileChange>> {
                       change.description = text;
                   }
               } else if in_search && in_change {
-                  if let Some(ref mut change) = current_change {
-                      if let Some(content) = extract_between_markers(&text) {
-                          change.search = Some(content);
-                      }
-                  }
+                  search_segments = extract_between_markers(&text).map_err(|message| XmlParseError {
+                      message,
+                      offset: reader.buffer_position() as usize,
+                  })?;
               } else if in_content && in_change {
-                  if let Some(ref mut change) = current_change {
-                      if let Some(content) = extract_between_markers(&text) {
-                          change.content = content;
-                      }
-                  }
+                  content_segments = extract_between_markers(&text).map_err(|message| XmlParseError {
+                      message,
+                      offset: reader.buffer_position() as usize,
+                  })?;
               }
           },
           Ok(Event::Eof) => break,
-          Err(e) => anyhow::bail!("Error parsing XML: {}", e),
+          Err(e) => return Err(XmlParseError {
+              message: format!("Error parsing XML: {}", e),
+              offset: reader.error_position() as usize,
+          }.into()),
           _ => {}
       }
 
@@ -161,46 +378,197 @@ pub async fn parse_xml_diff(xml: &str) -> Result<Vec<FileChange>> {
   Ok(file_changes)
 }
 
-// The rest of the code remains unchanged
-fn extract_between_markers(text: &str) -> Option<String> {
-    let lines: Vec<&str> = text.lines().collect();
-
-    let mut start_idx = None;
-    let mut end_idx = None;
+/// Runs a model response through the parser and reports whether it would apply, with the error location if not
+pub async fn validate_response_format(xml: &str) -> ResponseValidation {
+    match parse_xml_diff(xml).await {
+        Ok(file_changes) if file_changes.is_empty() => ResponseValidation {
+            valid: false,
+            file_count: 0,
+            change_count: 0,
+            error: Some("No <file> elements found in the response".to_string()),
+            line: None,
+            column: None,
+        },
+        Ok(file_changes) => ResponseValidation {
+            valid: true,
+            file_count: file_changes.len(),
+            change_count: file_changes.iter().map(|f| f.changes.len()).sum(),
+            error: None,
+            line: None,
+            column: None,
+        },
+        Err(e) => {
+            let position = e.chain()
+                .find_map(|cause| cause.downcast_ref::<XmlParseError>())
+                .map(|parse_error| line_and_column(xml, parse_error.offset));
 
-    for (i, line) in lines.iter().enumerate() {
-        if line.trim() == "===" {
-            if start_idx.is_none() {
-                start_idx = Some(i);
-            } else if end_idx.is_none() {
-                end_idx = Some(i);
+            ResponseValidation {
+                valid: false,
+                file_count: 0,
+                change_count: 0,
+                error: Some(e.to_string()),
+                line: position.map(|(line, _)| line),
+                column: position.map(|(_, column)| column),
             }
         }
     }
+}
+
+// Converts a byte offset into a 1-based line and column (counted in characters)
+fn line_and_column(text: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map(|l| l.chars().count()).unwrap_or(0) + 1;
+
+    (line, column)
+}
+
+fn parse_line_number(value: &str) -> Result<usize> {
+    value.trim().parse::<usize>()
+        .map_err(|_| error::parse_error(format!("Invalid line number: {}", value)))
+}
+
+// Returns every segment enclosed by a pair of `===` lines. Text without markers is a
+// single segment as-is; an odd number of markers is an error.
+fn extract_between_markers(text: &str) -> std::result::Result<Vec<String>, String> {
+    let lines: Vec<&str> = text.lines().collect();
+
+    let markers: Vec<usize> = lines.iter()
+        .enumerate()
+        .filter(|(_, line)| line.trim() == "===")
+        .map(|(i, _)| i)
+        .collect();
+
+    if markers.is_empty() {
+        return Ok(vec![text.to_string()]);
+    }
 
-    if let (Some(start), Some(end)) = (start_idx, end_idx) {
-        if start + 1 < end {
-            let content = lines[start+1..end].join("\n");
-            return Some(content);
+    if markers.len() % 2 == 1 {
+        return Err(format!("Unmatched === marker ({} markers found, expected pairs)", markers.len()));
+    }
+
+    let segments = markers.chunks(2)
+        .map(|pair| lines[pair[0] + 1..pair[1]].join("\n"))
+        .collect();
+
+    Ok(segments)
+}
+
+// Turns a change with several search/content hunks into one change per hunk.
+// Search and content must have the same number of hunks.
+fn split_hunks(change: Change, search_segments: &[String], content_segments: &[String]) -> std::result::Result<Vec<Change>, String> {
+    let content_segments = if content_segments.is_empty() { &[String::new()][..] } else { content_segments };
+
+    if search_segments.is_empty() {
+        if content_segments.len() > 1 {
+            return Err(format!(
+                "Change \"{}\" has {} content hunks but no search section to pair them with",
+                change.description,
+                content_segments.len()
+            ));
         }
+
+        return Ok(vec![Change {
+            content: content_segments[0].clone(),
+            ..change
+        }]);
+    }
+
+    if search_segments.len() != content_segments.len() {
+        return Err(format!(
+            "Change \"{}\" has {} search hunks but {} content hunks",
+            change.description,
+            search_segments.len(),
+            content_segments.len()
+        ));
+    }
+
+    let total = search_segments.len();
+
+    let hunks = search_segments.iter()
+        .zip(content_segments)
+        .enumerate()
+        .map(|(i, (search, content))| Change {
+            description: if total > 1 { format!("{} (hunk {}/{})", change.description, i + 1, total) } else { change.description.clone() },
+            search: Some(search.clone()),
+            content: content.clone(),
+            ..change.clone()
+        })
+        .collect();
+
+    Ok(hunks)
+}
+
+// How many file names a change summary lists before eliding the rest
+const SUMMARY_FILE_NAMES: usize = 3;
+
+/// Short label for a batch of changes, e.g. `Modified 3, Created 1: auth.rs, login.ts, main.rs, +1 more`
+pub fn summarize_changes(file_changes: &[FileChange]) -> String {
+    if file_changes.is_empty() {
+        return "Applied XML changes".to_string();
+    }
+
+    let actions = [
+        (ChangeAction::Modify, "Modified"),
+        (ChangeAction::Create, "Created"),
+        (ChangeAction::Rewrite, "Rewrote"),
+        (ChangeAction::RegexReplace, "Regex-replaced"),
+        (ChangeAction::Delete, "Deleted"),
+    ];
+
+    let counts: Vec<String> = actions.iter()
+        .filter_map(|(action, verb)| {
+            let count = file_changes.iter().filter(|c| &c.action == action).count();
+            (count > 0).then(|| format!("{} {}", verb, count))
+        })
+        .collect();
+
+    let mut names: Vec<String> = file_changes.iter()
+        .take(SUMMARY_FILE_NAMES)
+        .map(|c| Path::new(&c.path).file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| c.path.clone()))
+        .collect();
+
+    if file_changes.len() > SUMMARY_FILE_NAMES {
+        names.push(format!("+{} more", file_changes.len() - SUMMARY_FILE_NAMES));
     }
 
-    Some(text.to_string())
+    format!("{}: {}", counts.join(", "), names.join(", "))
 }
 
-pub async fn apply_changes(file_changes: &[FileChange]) -> Result<Vec<ChangeResult>> {
+/// Applies parsed changes; every path must resolve inside one of `allowed_roots` (the workspace folders)
+pub async fn apply_changes(file_changes: &[FileChange], allowed_roots: &[PathBuf], options: &ApplyOptions, limits: &IoLimits) -> Result<Vec<ChangeResult>> {
     let mut results = Vec::new();
 
     for file_change in file_changes {
-        let result = apply_file_change(file_change).await;
+        let result = apply_file_change(file_change, allowed_roots, options, limits).await;
 
         match result {
-            Ok(_) => {
+            Ok(outcome) => {
+                let total = outcome.statuses.len();
+                let failed = outcome.statuses.iter().filter(|s| !s.success).count();
+
+                let message = match (failed, outcome.written) {
+                    (0, true) if outcome.has_conflicts => Some("Merged with conflicts; resolve the conflict markers in the file".to_string()),
+                    (0, _) => None,
+                    (_, true) => Some(format!("Applied {} of {} changes; failed changes were skipped", total - failed, total)),
+                    (_, false) => Some(format!("{} of {} changes failed; file left unchanged", failed, total)),
+                };
+
                 results.push(ChangeResult {
                     path: file_change.path.clone(),
                     action: file_change.action.clone(),
-                    success: true,
-                    message: None,
+                    success: failed == 0,
+                    message,
+                    replacements: outcome.replacements.filter(|_| outcome.written),
+                    changes: outcome.statuses,
+                    has_conflicts: outcome.has_conflicts && outcome.written,
                 });
             },
             Err(e) => {
@@ -209,6 +577,9 @@ pub async fn apply_changes(file_changes: &[FileChange]) -> Result<Vec<ChangeResu
                     action: file_change.action.clone(),
                     success: false,
                     message: Some(format!("Error: {}", e)),
+                    replacements: None,
+                    changes: Vec::new(),
+                    has_conflicts: false,
                 });
             }
         }
@@ -217,40 +588,623 @@ pub async fn apply_changes(file_changes: &[FileChange]) -> Result<Vec<ChangeResu
     Ok(results)
 }
 
-async fn apply_file_change(file_change: &FileChange) -> Result<()> {
+async fn apply_file_change(file_change: &FileChange, allowed_roots: &[PathBuf], options: &ApplyOptions, limits: &IoLimits) -> Result<FileOutcome> {
+    // Reject anything escaping the workspace before touching the filesystem
+    let resolved = resolve_within_roots(&file_change.path, allowed_roots)?;
+    let path = resolved.to_string_lossy().to_string();
+
     match file_change.action {
-        ChangeAction::Create => {
-            let content = &file_change.changes[0].content;
-            write_file(&file_change.path, content).await?;
+        ChangeAction::Create | ChangeAction::Rewrite => {
+            let new_content = compute_new_content(file_change, "", options)?;
+            write_file_with_eol(&path, &new_content.content, options.eol).await?;
         },
-        ChangeAction::Rewrite => {
-            let content = &file_change.changes[0].content;
-            write_file(&file_change.path, content).await?;
+        ChangeAction::Modify | ChangeAction::RegexReplace => {
+            // Match against LF text so CRLF files still find LF search blocks;
+            // the original style is restored when writing
+            let original_content = normalize_line_endings(&read_file(&path, limits).await?, LineEnding::Lf);
+            let new_content = compute_new_content(file_change, &original_content, options)?;
+
+            // The file may have changed since the response was parsed; unless partial
+            // writes are allowed, any failed change leaves it untouched
+            let write = new_content.failed_count() == 0 || options.allow_partial;
+
+            if write {
+                write_file_with_eol(&path, &new_content.content, options.eol).await?;
+            }
+
+            return Ok(FileOutcome {
+                written: write,
+                replacements: new_content.replacements,
+                statuses: new_content.statuses,
+                has_conflicts: new_content.has_conflicts,
+            });
         },
-        ChangeAction::Modify => {
-            let original_content = read_file(&file_change.path).await?;
-            let mut modified_content = original_content.clone();
-
-            for change in &file_change.changes {
-                if let Some(ref search) = change.search {
-                    if !modified_content.contains(search) {
-                        anyhow::bail!("Search text not found in file: {}", file_change.path);
-                    }
+        ChangeAction::Delete => {
+            if resolved.is_dir() {
+                tokio::fs::remove_dir_all(&resolved)
+                    .await
+                    .with_context(|| format!("Failed to delete directory: {}", file_change.path))?;
+            } else {
+                tokio::fs::remove_file(&resolved)
+                    .await
+                    .with_context(|| format!("Failed to delete file: {}", file_change.path))?;
+            }
+        }
+    }
+
+    Ok(FileOutcome {
+        written: true,
+        replacements: None,
+        statuses: Vec::new(),
+        has_conflicts: false,
+    })
+}
 
-                    modified_content = modified_content.replace(search, &change.content);
+// Computes the content a change would leave in the file, given its current content.
+// Modify and RegexReplace changes are attempted one by one; failures are recorded, not fatal.
+fn compute_new_content(file_change: &FileChange, original_content: &str, options: &ApplyOptions) -> Result<NewContent> {
+    let mut new_content = match file_change.action {
+        ChangeAction::Create | ChangeAction::Rewrite => {
+            // Malformed responses can leave a file element without any <change>
+            let change = file_change.changes.first()
+                .ok_or_else(|| error::invalid_input(format!("No content provided for create/rewrite: {}", file_change.path)))?;
+
+            NewContent::whole(change.content.clone())
+        },
+        ChangeAction::Modify => modify_content(file_change, original_content, options),
+        ChangeAction::RegexReplace => regex_replace(file_change, original_content),
+        ChangeAction::Delete => return Ok(NewContent::whole(String::new())),
+    };
+
+    if options.trim_trailing_whitespace || options.ensure_final_newline {
+        new_content.content = tidy_whitespace(&new_content.content, options.trim_trailing_whitespace, options.ensure_final_newline);
+    }
+
+    Ok(new_content)
+}
+
+fn modify_content(file_change: &FileChange, original_content: &str, options: &ApplyOptions) -> NewContent {
+    let is_anchored = |c: &Change| c.start_line.is_some() || c.end_line.is_some();
+    let mut statuses = Vec::new();
+    let mut has_conflicts = false;
+
+    // Line anchors refer to the file as given, so they go first, before search
+    // replacements can shift lines around. They succeed or fail together.
+    let mut modified_content = match apply_line_ranges(file_change, original_content) {
+        Ok(content) => {
+            for (index, change) in file_change.changes.iter().enumerate().filter(|(_, c)| is_anchored(c)) {
+                statuses.push(ChangeStatus::new(index, change, Ok(())));
+            }
+            content
+        },
+        Err(e) => {
+            for (index, change) in file_change.changes.iter().enumerate().filter(|(_, c)| is_anchored(c)) {
+                statuses.push(ChangeStatus::new(index, change, Err(anyhow::anyhow!("{}", e))));
+            }
+            original_content.to_string()
+        }
+    };
+
+    for (index, change) in file_change.changes.iter().enumerate().filter(|(_, c)| !is_anchored(c)) {
+        let outcome = match change.search {
+            Some(ref search) => {
+                let search = normalize_line_endings(search, LineEnding::Lf);
+                let search_chars = search.chars().filter(|c| !c.is_whitespace()).count();
+
+                if search_chars < options.min_search_chars && !options.allow_short_search {
+                    Err(anyhow::anyhow!(
+                        "Search block is too short ({} non-whitespace characters, minimum {}) and could match unintended places; set allow_short_search to apply it anyway",
+                        search_chars,
+                        options.min_search_chars
+                    ))
+                } else if modified_content.contains(&search) {
+                    modified_content = modified_content.replace(&search, &change.content);
+                    Ok(())
+                } else if options.three_way_merge {
+                    merge_into_closest_region(&modified_content, &search, &change.content)
+                        .map(|merge| {
+                            modified_content = merge.content;
+                            has_conflicts |= merge.has_conflicts;
+                        })
+                        .map_err(|e| anyhow::anyhow!("Search text not found in file: {} ({})", file_change.path, e))
                 } else {
-                    anyhow::bail!("Modify action requires a search section");
+                    Err(anyhow::anyhow!("Search text not found in file: {}", file_change.path))
                 }
+            },
+            None => Err(error::invalid_input("Modify action requires a search section")),
+        };
+
+        statuses.push(ChangeStatus::new(index, change, outcome));
+    }
+
+    statuses.sort_by_key(|s| s.index);
+
+    NewContent {
+        content: modified_content,
+        replacements: None,
+        statuses,
+        has_conflicts,
+    }
+}
+
+// Below this similarity a region isn't considered a drifted copy of the search block
+const MIN_MERGE_SIMILARITY: f32 = 0.5;
+
+struct MergedContent {
+    content: String,
+    has_conflicts: bool,
+}
+
+// Three-way merge of one change: the search block is the base, the closest matching region
+// of the file is "mine" and the change's content is "theirs"
+fn merge_into_closest_region(content: &str, search: &str, replacement: &str) -> Result<MergedContent> {
+    // diffy works on whole lines, so give all three sides the same trailing newline
+    let with_newline = |text: &str| if text.ends_with('\n') { text.to_string() } else { format!("{}\n", text) };
+    let search = with_newline(search);
+
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let window = search.lines().count().max(1);
+
+    if lines.len() < window {
+        anyhow::bail!("File is shorter than the search block");
+    }
+
+    // Slide a window of the search block's height over the file and keep the most similar region
+    let (start, similarity) = (0..=lines.len() - window)
+        .map(|start| {
+            let region = lines[start..start + window].concat();
+            (start, TextDiff::from_lines(search.as_str(), with_newline(&region).as_str()).ratio())
+        })
+        .fold((0, 0.0), |best, candidate| if candidate.1 > best.1 { candidate } else { best });
+
+    if similarity < MIN_MERGE_SIMILARITY {
+        anyhow::bail!("No region of the file is similar enough to the search block to merge");
+    }
+
+    let region = lines[start..start + window].concat();
+    let region_had_newline = region.ends_with('\n');
+
+    let (mut merged, has_conflicts) = match diffy::merge(&search, &with_newline(&region), &with_newline(replacement)) {
+        Ok(merged) => (merged, false),
+        Err(conflicted) => (conflicted, true),
+    };
+
+    if !region_had_newline {
+        merged.pop();
+    }
+
+    Ok(MergedContent {
+        content: format!("{}{}{}", lines[..start].concat(), merged, lines[start + window..].concat()),
+        has_conflicts,
+    })
+}
+
+// Replaces the line ranges of anchored changes, working bottom-up so earlier ranges keep their numbers
+fn apply_line_ranges(file_change: &FileChange, original_content: &str) -> Result<String> {
+    let mut ranges = Vec::new();
+
+    for change in &file_change.changes {
+        match (change.start_line, change.end_line) {
+            (None, None) => {},
+            (Some(start), Some(end)) => ranges.push((start, end, change.content.as_str())),
+            _ => return Err(error::invalid_input("Line-anchored changes need both start_line and end_line")),
+        }
+    }
+
+    if ranges.is_empty() {
+        return Ok(original_content.to_string());
+    }
+
+    let mut lines: Vec<String> = original_content.split_inclusive('\n').map(|l| l.to_string()).collect();
+    let line_count = lines.len();
+    let ends_with_newline = original_content.is_empty() || original_content.ends_with('\n');
+
+    ranges.sort_by_key(|(start, _, _)| std::cmp::Reverse(*start));
+
+    let mut previous_start = usize::MAX;
+
+    for (start, end, content) in ranges {
+        if start == 0 {
+            return Err(error::invalid_input("Line numbers start at 1 (got start_line 0)"));
+        }
+
+        if start > end + 1 {
+            return Err(error::invalid_input(format!("start_line {} is after end_line {}", start, end)));
+        }
+
+        if end > line_count {
+            return Err(error::invalid_input(format!("Line range {}-{} is outside the file ({} lines)", start, end, line_count)));
+        }
+
+        if end >= previous_start {
+            return Err(error::invalid_input(format!("Line range {}-{} overlaps another anchored change", start, end)));
+        }
+
+        // Appending after a last line that has no newline needs one first
+        if start > line_count && !ends_with_newline {
+            if let Some(last) = lines.last_mut() {
+                last.push('\n');
             }
+        }
 
-            write_file(&file_change.path, &modified_content).await?;
-        },
-        ChangeAction::Delete => {
-            tokio::fs::remove_file(&file_change.path)
-                .await
-                .with_context(|| format!("Failed to delete file: {}", file_change.path))?;
+        let mut replacement = content.to_string();
+        let is_file_end = end == line_count && !ends_with_newline;
+
+        if !replacement.is_empty() && !replacement.ends_with('\n') && !is_file_end {
+            replacement.push('\n');
+        }
+
+        let new_lines: Vec<String> = replacement.split_inclusive('\n').map(|l| l.to_string()).collect();
+        lines.splice(start - 1..end, new_lines);
+
+        previous_start = start;
+    }
+
+    Ok(lines.concat())
+}
+
+// Applies each change's pattern to every match in the file, counting the replacements
+fn regex_replace(file_change: &FileChange, original_content: &str) -> NewContent {
+    let mut modified_content = original_content.to_string();
+    let mut replacements = 0;
+    let mut statuses = Vec::new();
+
+    for (index, change) in file_change.changes.iter().enumerate() {
+        let outcome = replace_pattern(&modified_content, change, &file_change.path);
+
+        let outcome = outcome.map(|(content, matches)| {
+            modified_content = content;
+            replacements += matches;
+        });
+
+        statuses.push(ChangeStatus::new(index, change, outcome));
+    }
+
+    NewContent {
+        content: modified_content,
+        replacements: Some(replacements),
+        statuses,
+        has_conflicts: false,
+    }
+}
+
+// Replaces every match of one change's pattern, returning the new content and match count
+fn replace_pattern(content: &str, change: &Change, path: &str) -> Result<(String, usize)> {
+    let pattern = change.search.as_ref()
+        .ok_or_else(|| error::invalid_input("RegexReplace action requires a search section"))?;
+
+    let regex = Regex::new(pattern)
+        .map_err(|e| error::invalid_input(format!("Invalid regex `{}`: {}", pattern, e)))?;
+
+    let matches = regex.find_iter(content).count();
+    if matches == 0 {
+        anyhow::bail!("Pattern `{}` matched nothing in file: {}", pattern, path);
+    }
+
+    Ok((regex.replace_all(content, change.content.as_str()).into_owned(), matches))
+}
+
+/// Returns a unified diff of what applying the change with `options` would do, without touching disk
+pub async fn diff_file_change(file_change: &FileChange, context_lines: usize, options: &ApplyOptions, limits: &IoLimits) -> Result<String> {
+    let exists = Path::new(&file_change.path).is_file();
+
+    let original_content = match file_change.action {
+        ChangeAction::Create => String::new(),
+        _ if exists => normalize_line_endings(&read_file(&file_change.path, limits).await?, LineEnding::Lf),
+        _ => String::new(),
+    };
+
+    let new_content = compute_new_content(file_change, &original_content, options)?;
+
+    if let Some(failure) = new_content.first_failure() {
+        return Err(failure);
+    }
+
+    let proposed_content = new_content.content;
+
+    let old_header = if exists && file_change.action != ChangeAction::Create { file_change.path.as_str() } else { "/dev/null" };
+    let new_header = if file_change.action == ChangeAction::Delete { "/dev/null" } else { file_change.path.as_str() };
+
+    let diff = TextDiff::from_lines(&original_content, &proposed_content);
+    let mut unified = diff.unified_diff();
+    unified.context_radius(context_lines).header(old_header, new_header);
+
+    Ok(unified.to_string())
+}
+
+/// One place a Modify change would apply, with surrounding lines before and after the change
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MatchWindow {
+    // 1-based lines of the matched text in the current file
+    pub match_start_line: usize,
+    pub match_end_line: usize,
+    // 1-based first line of the window, i.e. of both `current` and `proposed`
+    pub window_start_line: usize,
+    pub current: String,
+    pub proposed: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChangePreview {
+    // Position of the change within its file
+    pub index: usize,
+    pub description: String,
+    // False when the search text (or line range) wasn't found; `windows` is then empty
+    pub found: bool,
+    // Every match, in file order; more than one means the search is ambiguous
+    pub windows: Vec<MatchWindow>,
+}
+
+/// Shows where each change of a Modify would land in the current file, with `context_lines`
+/// of surrounding code, without applying anything
+pub async fn preview_modify_context(file_change: &FileChange, context_lines: usize, limits: &IoLimits) -> Result<Vec<ChangePreview>> {
+    if file_change.action != ChangeAction::Modify {
+        return Err(error::invalid_input(format!("Context previews are only available for modify changes: {}", file_change.path)));
+    }
+
+    let content = normalize_line_endings(&read_file(&file_change.path, limits).await?, LineEnding::Lf);
+
+    // Byte offset where each line starts, plus the end of the file
+    let mut line_starts: Vec<usize> = std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(i, _)| i + 1).filter(|&i| i < content.len()))
+        .collect();
+    line_starts.push(content.len());
+    let line_count = line_starts.len() - 1;
+    let ends_with_newline = content.is_empty() || content.ends_with('\n');
+
+    // Line-anchored content gets the line break apply_changes would add to it
+    let anchored = |end: usize, replacement: &str| {
+        let mut replacement = replacement.to_string();
+        let is_file_end = end == line_count && !ends_with_newline;
+
+        if !replacement.is_empty() && !replacement.ends_with('\n') && !is_file_end {
+            replacement.push('\n');
+        }
+
+        replacement
+    };
+
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset).max(1);
+
+    let window = |match_start: usize, match_end: usize, replacement: &str| {
+        let first_line = line_of(match_start);
+        let last_line = line_of(match_end.saturating_sub(1).max(match_start));
+        let window_start_line = first_line.saturating_sub(context_lines).max(1);
+        let window_end_line = (last_line + context_lines).min(line_count);
+
+        let window_start = line_starts[window_start_line - 1];
+        let window_end = line_starts[window_end_line].max(match_end);
+
+        MatchWindow {
+            match_start_line: first_line,
+            match_end_line: last_line,
+            window_start_line,
+            current: content[window_start..window_end].to_string(),
+            proposed: format!("{}{}{}", &content[window_start..match_start], replacement, &content[match_end..window_end]),
+        }
+    };
+
+    let previews = file_change.changes.iter().enumerate()
+        .map(|(index, change)| {
+            let windows: Vec<MatchWindow> = match (change.start_line, change.end_line, change.search.as_deref()) {
+                (Some(start), Some(end), _) if start >= 1 && start <= end && end <= line_count => {
+                    vec![window(line_starts[start - 1], line_starts[end], &anchored(end, &change.content))]
+                },
+                // `start_line == end_line + 1` inserts before `start_line`, or appends after the last line
+                (Some(start), Some(end), _) if start >= 1 && start == end + 1 && end <= line_count => {
+                    let offset = line_starts[start - 1];
+                    let mut replacement = anchored(end, &change.content);
+
+                    if start > line_count && !ends_with_newline {
+                        replacement.insert(0, '\n');
+                    }
+
+                    vec![window(offset, offset, &replacement)]
+                },
+                (None, None, Some(search)) if !search.is_empty() => {
+                    let search = normalize_line_endings(search, LineEnding::Lf);
+                    content.match_indices(&search)
+                        .map(|(start, matched)| window(start, start + matched.len(), &change.content))
+                        .collect()
+                },
+                _ => Vec::new(),
+            };
+
+            ChangePreview {
+                index,
+                description: change.description.clone(),
+                found: !windows.is_empty(),
+                windows,
+            }
+        })
+        .collect();
+
+    Ok(previews)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("proprompter-{}-{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn create(path: &str, content: &str) -> FileChange {
+        FileChange {
+            path: path.to_string(),
+            action: ChangeAction::Create,
+            changes: vec![Change {
+                description: String::new(),
+                search: None,
+                content: content.to_string(),
+                start_line: None,
+                end_line: None,
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn changes_outside_the_workspace_fail_without_writing() {
+        let root = scratch_dir("apply-root");
+        let escaped = root.join("..").join(format!("escaped-{}.txt", uuid::Uuid::new_v4()));
+        let change = create(&escaped.to_string_lossy(), "nope\n");
+
+        let results = apply_changes(&[change], std::slice::from_ref(&root), &ApplyOptions::default(), &IoLimits::default()).await.unwrap();
+        let written = escaped.exists();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(!results[0].success);
+        assert!(results[0].message.as_deref().unwrap_or("").contains("outside workspace"));
+        assert!(!written);
+    }
+
+    #[tokio::test]
+    async fn modify_keeps_crlf_line_endings() {
+        let root = scratch_dir("apply-crlf");
+        let path = root.join("main.rs");
+        std::fs::write(&path, "let a = 1;\r\nlet beta = 2;\r\nlet c = 3;\r\n").unwrap();
+
+        let change = FileChange {
+            path: path.to_string_lossy().to_string(),
+            action: ChangeAction::Modify,
+            changes: vec![Change {
+                description: String::new(),
+                search: Some("let beta = 2;\n".to_string()),
+                content: "let b = 20;\nlet d = 4;\n".to_string(),
+                start_line: None,
+                end_line: None,
+            }],
+        };
+
+        let results = apply_changes(&[change], std::slice::from_ref(&root), &ApplyOptions::default(), &IoLimits::default()).await.unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(results[0].success);
+        assert_eq!(written, "let a = 1;\r\nlet b = 20;\r\nlet d = 4;\r\nlet c = 3;\r\n");
+    }
+
+    // The response example embedded in the formatting instructions, from <Plan> up to the rules
+    fn instruction_example() -> &'static str {
+        let instructions = crate::xml::generator::XML_FORMATTING_INSTRUCTIONS;
+        let start = instructions.find("<Plan>").unwrap();
+        let end = instructions.find("\nRules:").unwrap();
+        &instructions[start..end]
+    }
+
+    #[tokio::test]
+    async fn instruction_example_parses() {
+        let file_changes = parse_xml_diff(instruction_example()).await.unwrap();
+
+        assert_eq!(file_changes.len(), 1);
+        assert_eq!(file_changes[0].path, "/absolute/path/to/file");
+        assert_eq!(file_changes[0].action, ChangeAction::Modify);
+        assert_eq!(file_changes[0].changes.len(), 1);
+
+        let change = &file_changes[0].changes[0];
+        assert_eq!(change.description, "What this change does");
+        assert_eq!(change.search.as_deref().map(str::trim_end), Some("exact lines from the current file to replace"));
+        assert_eq!(change.content.trim_end(), "the replacement lines");
+    }
+
+    #[tokio::test]
+    async fn validation_reports_counts_and_error_positions() {
+        let valid = validate_response_format(instruction_example()).await;
+        assert!(valid.valid);
+        assert_eq!((valid.file_count, valid.change_count), (1, 1));
+        assert!(valid.error.is_none());
+
+        let empty = validate_response_format("<Plan>Nothing to do.</Plan>").await;
+        assert!(!empty.valid);
+        assert_eq!(empty.file_count, 0);
+
+        let broken = validate_response_format("<file path=\"a.rs\" action=\"modify\">\n  <change>\n</file>").await;
+        assert!(!broken.valid);
+        assert!(broken.error.is_some());
+        assert!(broken.line.is_some());
+    }
+
+    #[tokio::test]
+    async fn create_or_rewrite_without_a_change_fails_cleanly() {
+        let root = scratch_dir("apply-empty");
+        let existing = root.join("existing.txt");
+        std::fs::write(&existing, "keep me\n").unwrap();
+
+        let changes = [
+            FileChange { path: root.join("new.txt").to_string_lossy().to_string(), action: ChangeAction::Create, changes: Vec::new() },
+            FileChange { path: existing.to_string_lossy().to_string(), action: ChangeAction::Rewrite, changes: Vec::new() },
+        ];
+
+        let results = apply_changes(&changes, std::slice::from_ref(&root), &ApplyOptions::default(), &IoLimits::default()).await.unwrap();
+        let created = root.join("new.txt").exists();
+        let kept = std::fs::read_to_string(&existing).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        for result in &results {
+            assert!(!result.success);
+            assert!(result.message.as_deref().unwrap_or("").contains("No content provided for create/rewrite"));
+        }
+        assert!(!created);
+        assert_eq!(kept, "keep me\n");
+    }
+
+    fn anchored(start: usize, end: usize, content: &str) -> FileChange {
+        FileChange {
+            path: String::new(),
+            action: ChangeAction::Modify,
+            changes: vec![Change {
+                description: String::new(),
+                search: None,
+                content: content.to_string(),
+                start_line: Some(start),
+                end_line: Some(end),
+            }],
         }
     }
 
-    Ok(())
-}
\ No newline at end of file
+    #[tokio::test]
+    async fn insert_anchors_preview_like_they_apply() {
+        let root = scratch_dir("preview-insert");
+        let path = root.join("list.txt");
+
+        for (original, start, inserted) in [("one\ntwo\n", 2, "one\nnew\ntwo\n"), ("one\ntwo", 3, "one\ntwo\nnew")] {
+            std::fs::write(&path, original).unwrap();
+            let mut change = anchored(start, start - 1, "new");
+            change.path = path.to_string_lossy().to_string();
+
+            let previews = preview_modify_context(&change, 5, &IoLimits::default()).await.unwrap();
+            let applied = apply_changes(&[change], std::slice::from_ref(&root), &ApplyOptions::default(), &IoLimits::default()).await.unwrap();
+            let written = std::fs::read_to_string(&path).unwrap();
+
+            assert!(previews[0].found);
+            assert!(applied[0].success);
+            assert_eq!(written, inserted);
+            assert_eq!(previews[0].windows[0].current, original);
+            assert_eq!(previews[0].windows[0].proposed, inserted);
+        }
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn diffs_use_the_given_apply_options() {
+        let root = scratch_dir("diff-options");
+        let path = root.join("config.toml");
+        std::fs::write(&path, "value = 0\n").unwrap();
+
+        let mut change = create(&path.to_string_lossy(), "value = 1   ");
+        change.action = ChangeAction::Rewrite;
+        let tidy = ApplyOptions { trim_trailing_whitespace: true, ensure_final_newline: true, ..Default::default() };
+
+        let plain = diff_file_change(&change, 3, &ApplyOptions::default(), &IoLimits::default()).await.unwrap();
+        let tidied = diff_file_change(&change, 3, &tidy, &IoLimits::default()).await.unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(plain.contains("+value = 1   \n\\ No newline at end of file"));
+        assert!(tidied.contains("+value = 1\n"));
+        assert!(!tidied.contains("No newline at end of file"));
+    }
+}
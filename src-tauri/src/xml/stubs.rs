@@ -0,0 +1,279 @@
+use std::path::Path;
+
+/// Languages the signature extractor understands
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StubLanguage {
+    Rust,
+    TypeScript,
+}
+
+/// What a function body is replaced with
+pub const STUB_BODY: &str = "{ ... }";
+
+/// Picks the stub language for a path, or None when bodies should be left alone
+pub fn stub_language(path: &str) -> Option<StubLanguage> {
+    let extension = Path::new(path).extension()?.to_str()?;
+
+    match extension {
+        "rs" => Some(StubLanguage::Rust),
+        "ts" | "tsx" | "js" | "jsx" | "mjs" | "cjs" => Some(StubLanguage::TypeScript),
+        _ => None,
+    }
+}
+
+/// Replaces function bodies with `{ ... }` so only declarations remain, or returns None for unrecognized languages.
+/// With `keep_lines` each removed body leaves its line breaks behind, so the lines after it don't move.
+pub fn stub_bodies_for_path(path: &str, content: &str, keep_lines: bool) -> Option<String> {
+    Some(stub_with(content, stub_language(path)?, keep_lines))
+}
+
+/// Keeps every declaration (and the blocks that contain them, like `impl` or `class`) but collapses
+/// function bodies. This is a bracket counter aware of strings and comments, not a parser, so
+/// unusual syntax (e.g. a `'{'` char literal in Rust) can confuse it.
+pub fn stub_bodies(content: &str, language: StubLanguage) -> String {
+    stub_with(content, language, false)
+}
+
+fn stub_with(content: &str, language: StubLanguage, keep_lines: bool) -> String {
+    let chars: Vec<char> = content.chars().collect();
+    let quotes: &[char] = match language {
+        // `'` is left out because lifetimes (`'a`) would look like unterminated strings
+        StubLanguage::Rust => &['"'],
+        StubLanguage::TypeScript => &['"', '\'', '`'],
+    };
+
+    let mut output = String::with_capacity(content.len());
+    // Code seen since the last `;`, `{` or `}`, without comments or string contents
+    let mut header = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if let Some(end) = skip_comment(&chars, i) {
+            output.extend(&chars[i..end]);
+            i = end;
+            continue;
+        }
+
+        if quotes.contains(&c) {
+            let end = skip_string(&chars, i);
+            output.extend(&chars[i..end]);
+            header.push_str("\"\"");
+            i = end;
+            continue;
+        }
+
+        match c {
+            '{' if is_function_header(&header, language) => {
+                output.push_str(STUB_BODY);
+                let end = skip_block(&chars, i, quotes);
+
+                if keep_lines {
+                    output.extend(chars[i..end].iter().filter(|&&c| c == '\n'));
+                }
+
+                i = end;
+                header.clear();
+                continue;
+            },
+            '{' | '}' | ';' => header.clear(),
+            _ => header.push(c),
+        }
+
+        output.push(c);
+        i += 1;
+    }
+
+    output
+}
+
+// Whether the code before a `{` opens a function body rather than a type, impl, class or object
+fn is_function_header(header: &str, language: StubLanguage) -> bool {
+    let header = header.trim();
+
+    match language {
+        StubLanguage::Rust => header.split(|c: char| !c.is_alphanumeric() && c != '_').any(|word| word == "fn"),
+        StubLanguage::TypeScript => {
+            let declaration = header.split_whitespace()
+                .find(|word| !matches!(*word, "export" | "default" | "declare" | "abstract"))
+                .unwrap_or("");
+
+            if matches!(declaration, "class" | "interface" | "enum" | "namespace" | "module" | "type") {
+                return false;
+            }
+
+            // `() => {`, `foo(a, b) {` and `foo(): Promise<void> {`
+            header.ends_with("=>") || header.ends_with(')') || has_return_type(header)
+        },
+    }
+}
+
+// `...): SomeType` at the end of a header, i.e. a signature with a return type annotation
+fn has_return_type(header: &str) -> bool {
+    let Some(close) = header.rfind(')') else {
+        return false;
+    };
+
+    let rest = header[close + 1..].trim_start();
+
+    match rest.strip_prefix(':') {
+        Some(return_type) => !return_type.contains('=') && !return_type.contains('('),
+        None => false,
+    }
+}
+
+// Returns the index just past a `//` or `/* */` comment starting at `index`
+fn skip_comment(chars: &[char], index: usize) -> Option<usize> {
+    if chars.get(index) != Some(&'/') {
+        return None;
+    }
+
+    match chars.get(index + 1) {
+        Some('/') => {
+            let end = chars[index..].iter().position(|&c| c == '\n').map(|p| index + p).unwrap_or(chars.len());
+            Some(end)
+        },
+        Some('*') => {
+            let mut i = index + 2;
+            while i + 1 < chars.len() && !(chars[i] == '*' && chars[i + 1] == '/') {
+                i += 1;
+            }
+            Some((i + 2).min(chars.len()))
+        },
+        _ => None,
+    }
+}
+
+// Returns the index just past the string literal opened at `index`
+fn skip_string(chars: &[char], index: usize) -> usize {
+    let quote = chars[index];
+    let mut i = index + 1;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            c if c == quote => return i + 1,
+            // Only template literals span lines
+            '\n' if quote != '`' => return i + 1,
+            _ => i += 1,
+        }
+    }
+
+    chars.len()
+}
+
+// Returns the index just past the `}` matching the `{` at `index`
+fn skip_block(chars: &[char], index: usize, quotes: &[char]) -> usize {
+    let mut depth = 0;
+    let mut i = index;
+
+    while i < chars.len() {
+        if let Some(end) = skip_comment(chars, i) {
+            i = end;
+            continue;
+        }
+
+        match chars[i] {
+            c if quotes.contains(&c) => {
+                i = skip_string(chars, i);
+                continue;
+            },
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            },
+            _ => {},
+        }
+
+        i += 1;
+    }
+
+    chars.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stubs_rust_functions_inside_impls_and_traits() {
+        let source = r#"struct Point { x: i32 }
+
+impl Point {
+    // Builds a point { not a block }
+    pub fn new(x: i32) -> Self {
+        let label = "{ unbalanced";
+        Point { x }
+    }
+}
+
+trait Shape {
+    fn area(&self) -> f64;
+    fn name(&self) -> &str { "shape" }
+}
+"#;
+
+        let expected = r#"struct Point { x: i32 }
+
+impl Point {
+    // Builds a point { not a block }
+    pub fn new(x: i32) -> Self { ... }
+}
+
+trait Shape {
+    fn area(&self) -> f64;
+    fn name(&self) -> &str { ... }
+}
+"#;
+
+        assert_eq!(stub_bodies(source, StubLanguage::Rust), expected);
+    }
+
+    #[test]
+    fn stubs_typescript_functions_but_not_types_or_objects() {
+        let source = r#"export interface User { name: string }
+
+export class Greeter {
+    greet(user: User): string {
+        return `hi ${user.name}`;
+    }
+}
+
+const config = { retries: 3 };
+const handler = (event) => {
+    console.log("}", event);
+};
+export default function main() {
+    run();
+}
+"#;
+
+        let expected = r#"export interface User { name: string }
+
+export class Greeter {
+    greet(user: User): string { ... }
+}
+
+const config = { retries: 3 };
+const handler = (event) => { ... };
+export default function main() { ... }
+"#;
+
+        assert_eq!(stub_bodies(source, StubLanguage::TypeScript), expected);
+    }
+
+    #[test]
+    fn keep_lines_leaves_the_body_line_breaks() {
+        let source = "fn main() {\n    run();\n}\nfn after() {}\n";
+
+        let stubbed = stub_bodies_for_path("main.rs", source, true).unwrap();
+
+        assert_eq!(stubbed, "fn main() { ... }\n\n\nfn after() { ... }\n");
+        assert_eq!(stubbed.lines().count(), source.lines().count());
+        assert!(stub_bodies_for_path("notes.md", source, true).is_none());
+    }
+}
@@ -93,7 +93,7 @@ export function XmlMode({
             hasChanges: true,
           })
         } else {
-          // Rewrite or Modify
+          // Rewrite, Modify or RegexReplace
           const original = fileContents.get(change.path) || '// File not found'
           let modified = original
 
@@ -106,6 +106,16 @@ export function XmlMode({
                 modified = modified.replace(c.search, c.content)
               }
             }
+          } else if (change.action === 'RegexReplace') {
+            for (const c of change.changes) {
+              if (c.search) {
+                try {
+                  modified = modified.replace(new RegExp(c.search, 'g'), c.content)
+                } catch {
+                  // Invalid patterns are reported when the change is applied
+                }
+              }
+            }
           }
 
           preview.push({
@@ -0,0 +1,43 @@
+// src/hooks/use-config.ts
+import { invoke } from '@tauri-apps/api/core'
+import { useCallback, useEffect, useState } from 'react'
+import type { Config } from '../types'
+import { errorMessage } from '../lib/utils'
+
+export function useConfig() {
+  const [config, setConfigState] = useState<Config | null>(null)
+  const [error, setError] = useState<string | null>(null)
+
+  const fetchConfig = useCallback(async () => {
+    try {
+      setConfigState(await invoke<Config>('get_config'))
+    } catch (err) {
+      console.error('Error fetching config:', err)
+      setError(errorMessage(err))
+    }
+  }, [])
+
+  const saveConfig = async (next: Config): Promise<boolean> => {
+    setError(null)
+
+    try {
+      setConfigState(await invoke<Config>('set_config', { config: next }))
+      return true
+    } catch (err) {
+      console.error('Error saving config:', err)
+      setError(errorMessage(err))
+      return false
+    }
+  }
+
+  useEffect(() => {
+    fetchConfig()
+  }, [fetchConfig])
+
+  return {
+    config,
+    error,
+    fetchConfig,
+    saveConfig,
+  }
+}
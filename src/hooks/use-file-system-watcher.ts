@@ -3,11 +3,13 @@ import { listen } from '@tauri-apps/api/event'
 // src/hooks/use-file-system-watcher.ts
 import { useState } from 'react'
 import { useEffect } from 'react'
+import { errorMessage } from '../lib/utils'
 
 export function useFileSystemWatcher() {
   const [isWatching, setIsWatching] = useState(false)
   const [error, setError] = useState<string | null>(null)
   const [changedFiles, setChangedFiles] = useState<string[]>([])
+  const [stalePaths, setStalePaths] = useState<string[]>([])
 
   // Set up the event listener for file changes
   useEffect(() => {
@@ -20,6 +22,17 @@ export function useFileSystemWatcher() {
     }
   }, [])
 
+  // Selected files whose content changed since the prompt was generated
+  useEffect(() => {
+    const unlisten = listen<string[]>('prompt-stale', event => {
+      setStalePaths(prev => [...new Set([...prev, ...event.payload])])
+    })
+
+    return () => {
+      unlisten.then(unlistenFn => unlistenFn())
+    }
+  }, [])
+
   const startWatching = async (): Promise<boolean> => {
     try {
       await invoke<void>('start_watching_filesystem')
@@ -27,7 +40,7 @@ export function useFileSystemWatcher() {
       return true
     } catch (err) {
       console.error('Error starting file system watcher:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
       return false
     }
   }
@@ -39,7 +52,7 @@ export function useFileSystemWatcher() {
       return true
     } catch (err) {
       console.error('Error stopping file system watcher:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
       return false
     }
   }
@@ -50,7 +63,7 @@ export function useFileSystemWatcher() {
       return true
     } catch (err) {
       console.error('Error watching path:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
       return false
     }
   }
@@ -61,7 +74,29 @@ export function useFileSystemWatcher() {
       return true
     } catch (err) {
       console.error('Error unwatching path:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
+      return false
+    }
+  }
+
+  const listWatchedPaths = async (): Promise<string[]> => {
+    try {
+      return await invoke<string[]>('list_watched_paths')
+    } catch (err) {
+      console.error('Error listing watched paths:', err)
+      setError(errorMessage(err))
+      return []
+    }
+  }
+
+  const setPromptSelection = async (paths: string[]): Promise<boolean> => {
+    try {
+      await invoke<void>('set_prompt_selection', { paths })
+      setStalePaths([])
+      return true
+    } catch (err) {
+      console.error('Error setting prompt selection:', err)
+      setError(errorMessage(err))
       return false
     }
   }
@@ -74,10 +109,13 @@ export function useFileSystemWatcher() {
     isWatching,
     error,
     changedFiles,
+    stalePaths,
     startWatching,
     stopWatching,
     watchPath,
     unwatchPath,
+    listWatchedPaths,
+    setPromptSelection,
     clearChangedFiles,
   }
 }
@@ -1,18 +1,55 @@
 // src/hooks/use-file-system.ts
 import { invoke } from '@tauri-apps/api/core'
-import { open } from '@tauri-apps/plugin-dialog'
+import { listen } from '@tauri-apps/api/event'
+import { open, save } from '@tauri-apps/plugin-dialog'
 import { useEffect, useState } from 'react'
-import type { FileItem, Workspace } from '../types'
+import type {
+  DetectedProject,
+  DisplayContent,
+  FileItem,
+  FilePreview,
+  GeneratedFile,
+  GeneratedPrompt,
+  LongLineMode,
+  RecoveredFile,
+  ResolvedFile,
+  ScanSkipped,
+  TokenEstimate,
+  TokenProgress,
+  Workspace,
+} from '../types'
 import { useWorkspace } from './use-workspace'
+import { errorMessage } from '../lib/utils'
 
 export function useFileSystem() {
   const [fileTree, setFileTree] = useState<FileItem[]>([])
   const [isLoading, setIsLoading] = useState(false)
   const [error, setError] = useState<string | null>(null)
+  // Unreadable entries left out of each scanned root, keyed by root path
+  const [skippedEntries, setSkippedEntries] = useState<Record<string, string[]>>({})
 
   // Get workspace information
   const { currentWorkspace } = useWorkspace()
 
+  useEffect(() => {
+    const unlisten = listen<ScanSkipped>('scan-skipped', event => {
+      const { root, paths } = event.payload
+      setSkippedEntries(prev => {
+        const next = { ...prev }
+        if (paths.length > 0) {
+          next[root] = paths
+        } else {
+          delete next[root]
+        }
+        return next
+      })
+    })
+
+    return () => {
+      unlisten.then(unlistenFn => unlistenFn())
+    }
+  }, [])
+
   // Load all folders in current workspace when workspace changes
   useEffect(() => {
     if (currentWorkspace) {
@@ -51,7 +88,7 @@ export function useFileSystem() {
       setFileTree(newFileTree)
     } catch (err) {
       console.error('Error loading workspace folders:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
     } finally {
       setIsLoading(false)
     }
@@ -71,7 +108,7 @@ export function useFileSystem() {
       return result
     } catch (err) {
       console.error('Error scanning directory:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
       throw err
     } finally {
       setIsLoading(false)
@@ -91,6 +128,80 @@ export function useFileSystem() {
     }
   }
 
+  const detectProjectType = async (path: string): Promise<DetectedProject[]> => {
+    try {
+      return await invoke<DetectedProject[]>('detect_project_type', { path })
+    } catch (err) {
+      console.error('Error detecting project type:', err)
+      return []
+    }
+  }
+
+  // Deepest directory containing every path, or null when they share no root
+  const commonRoot = async (paths: string[]): Promise<string | null> => {
+    try {
+      return await invoke<string | null>('common_root', { paths })
+    } catch (err) {
+      console.error('Error computing common root:', err)
+      return null
+    }
+  }
+
+  // Exclude patterns suggested by every project type detected at the root
+  const suggestExcludePatterns = async (path: string): Promise<string[]> => {
+    const projects = await detectProjectType(path)
+    return [...new Set(projects.flatMap(project => project.suggested_excludes))]
+  }
+
+  // Safe for rendering: very long lines are truncated or wrapped
+  const readFileResolved = async (
+    path: string,
+    followSymlinks = true,
+    workspaceId?: string
+  ): Promise<ResolvedFile> => {
+    try {
+      return await invoke<ResolvedFile>('read_file_resolved', {
+        path,
+        follow_symlinks: followSymlinks,
+        workspace_id: workspaceId,
+      })
+    } catch (err) {
+      console.error('Error reading file:', err)
+      throw err
+    }
+  }
+
+  const readFileForDisplay = async (
+    path: string,
+    maxLineLength?: number,
+    mode?: LongLineMode
+  ): Promise<DisplayContent> => {
+    try {
+      return await invoke<DisplayContent>('read_file_safe', { path, max_line_length: maxLineLength, mode })
+    } catch (err) {
+      console.error('Error reading file:', err)
+      throw err
+    }
+  }
+
+  const readFileHead = async (path: string, maxBytes?: number, maxLines?: number): Promise<FilePreview> => {
+    try {
+      return await invoke<FilePreview>('read_file_head', { path, max_bytes: maxBytes, max_lines: maxLines })
+    } catch (err) {
+      console.error('Error reading file head:', err)
+      throw err
+    }
+  }
+
+  const readFileTail = async (path: string, maxBytes?: number, maxLines?: number): Promise<FilePreview> => {
+    try {
+      return await invoke<FilePreview>('read_file_tail', { path, max_bytes: maxBytes, max_lines: maxLines })
+    } catch (err) {
+      console.error('Error reading file tail:', err)
+      throw err
+    }
+  }
+
   const openDirectoryDialog = async () => {
     try {
       const selected = await open({
@@ -104,20 +215,78 @@ export function useFileSystem() {
       }
     } catch (err) {
       console.error('Error opening directory dialog:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
     }
     return null
   }
 
   const generateCopyContent = async (files: string[], prompts: string[]): Promise<string> => {
     try {
-      return await invoke<string>('generate_copy_content', { files, prompts })
+      const result = await invoke<GeneratedPrompt>('generate_copy_content', { files, prompts })
+      return result.content
     } catch (err) {
       console.error('Error generating copy content:', err)
       throw err
     }
   }
 
+  // Saves generated prompt content, asking for a destination when no path is given
+  const savePromptOutput = async (content: string, path?: string): Promise<GeneratedFile | null> => {
+    try {
+      const destination =
+        path ??
+        (await save({
+          title: 'Save prompt',
+          defaultPath: 'prompt.xml',
+          filters: [{ name: 'Prompt', extensions: ['xml', 'txt', 'md'] }],
+        }))
+
+      if (!destination) {
+        return null
+      }
+
+      return await invoke<GeneratedFile>('save_prompt_output', { content, path: destination })
+    } catch (err) {
+      console.error('Error saving prompt output:', err)
+      setError(errorMessage(err))
+      return null
+    }
+  }
+
+  // Counts tokens for a large selection, reporting each file as it is counted
+  const estimateTokens = async (
+    paths: string[],
+    onProgress?: (progress: TokenProgress) => void
+  ): Promise<TokenEstimate | null> => {
+    const unlisten = await listen<TokenProgress>('token-progress', event => {
+      onProgress?.(event.payload)
+    })
+
+    try {
+      return await invoke<TokenEstimate>('estimate_tokens_streaming', { paths })
+    } catch (err) {
+      console.error('Error estimating tokens:', err)
+      setError(errorMessage(err))
+      return null
+    } finally {
+      unlisten()
+    }
+  }
+
+  // Data files moved aside since the last call; each recovery is returned once
+  const takeRecoveredFiles = async (): Promise<RecoveredFile[]> => {
+    try {
+      return await invoke<RecoveredFile[]>('take_recovered_files')
+    } catch (err) {
+      console.error('Error loading recovered files:', err)
+      return []
+    }
+  }
+
+  const cancelTokenEstimate = async (): Promise<void> => {
+    await invoke<void>('cancel_token_estimate')
+  }
+
   const copyToClipboard = async (content: string): Promise<void> => {
     try {
       await invoke<void>('copy_to_clipboard', { content })
@@ -131,11 +300,23 @@ export function useFileSystem() {
     fileTree,
     isLoading,
     error,
+    skippedEntries,
     scanDirectory,
     removeRootFolder,
     readFileContent,
+    readFileResolved,
+    readFileForDisplay,
+    readFileHead,
+    readFileTail,
+    detectProjectType,
+    suggestExcludePatterns,
+    commonRoot,
     openDirectoryDialog,
     generateCopyContent,
+    savePromptOutput,
+    estimateTokens,
+    cancelTokenEstimate,
+    takeRecoveredFiles,
     copyToClipboard,
     loadAllWorkspaceFolders,
   }
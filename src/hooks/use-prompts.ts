@@ -1,7 +1,8 @@
 import { invoke } from '@tauri-apps/api/core'
 // src/hooks/use-prompts.ts
 import { useCallback, useEffect, useState } from 'react'
-import type { Prompt, PromptTag } from '../types'
+import type { Prompt, PromptSearchResult, PromptTag } from '../types'
+import { errorMessage } from '../lib/utils'
 
 export function usePrompts() {
   const [prompts, setPrompts] = useState<Prompt[]>([])
@@ -17,7 +18,7 @@ export function usePrompts() {
       setPrompts(result)
     } catch (err) {
       console.error('Error fetching prompts:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
     } finally {
       setIsLoading(false)
     }
@@ -37,13 +38,55 @@ export function usePrompts() {
       return result
     } catch (err) {
       console.error('Error saving prompt:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
       return null
     } finally {
       setIsLoading(false)
     }
   }
 
+  const setPromptTags = async (id: string, tags: PromptTag[]): Promise<Prompt | null> => {
+    setError(null)
+
+    try {
+      const result = await invoke<Prompt>('set_prompt_tags', { id, tags })
+      await fetchPrompts() // Refresh the list
+      return result
+    } catch (err) {
+      console.error('Error setting prompt tags:', err)
+      setError(errorMessage(err))
+      return null
+    }
+  }
+
+  const addTagToPrompts = async (tag: PromptTag, ids: string[]): Promise<number> => {
+    setError(null)
+
+    try {
+      const modified = await invoke<number>('add_tag_to_prompts', { tag, ids })
+      await fetchPrompts() // Refresh the list
+      return modified
+    } catch (err) {
+      console.error('Error adding tag to prompts:', err)
+      setError(errorMessage(err))
+      return 0
+    }
+  }
+
+  const removeTagFromPrompts = async (tagId: string, ids: string[]): Promise<number> => {
+    setError(null)
+
+    try {
+      const modified = await invoke<number>('remove_tag_from_prompts', { tag_id: tagId, ids })
+      await fetchPrompts() // Refresh the list
+      return modified
+    } catch (err) {
+      console.error('Error removing tag from prompts:', err)
+      setError(errorMessage(err))
+      return 0
+    }
+  }
+
   const deletePrompt = async (id: string): Promise<boolean> => {
     setIsLoading(true)
     setError(null)
@@ -54,13 +97,40 @@ export function usePrompts() {
       return true
     } catch (err) {
       console.error('Error deleting prompt:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
       return false
     } finally {
       setIsLoading(false)
     }
   }
 
+  const deletePrompts = async (ids: string[]): Promise<number> => {
+    setIsLoading(true)
+    setError(null)
+
+    try {
+      const deleted = await invoke<number>('delete_prompts', { ids })
+      await fetchPrompts() // Refresh the list
+      return deleted
+    } catch (err) {
+      console.error('Error deleting prompts:', err)
+      setError(errorMessage(err))
+      return 0
+    } finally {
+      setIsLoading(false)
+    }
+  }
+
+  const searchPrompts = async (query: string): Promise<PromptSearchResult[]> => {
+    try {
+      return await invoke<PromptSearchResult[]>('search_prompts_with_snippets', { query })
+    } catch (err) {
+      console.error('Error searching prompts:', err)
+      setError(errorMessage(err))
+      return []
+    }
+  }
+
   // Load prompts on mount
   useEffect(() => {
     fetchPrompts()
@@ -72,6 +142,11 @@ export function usePrompts() {
     error,
     fetchPrompts,
     savePrompt,
+    setPromptTags,
+    addTagToPrompts,
+    removeTagFromPrompts,
     deletePrompt,
+    deletePrompts,
+    searchPrompts,
   }
 }
@@ -1,7 +1,8 @@
 import { invoke } from '@tauri-apps/api/core'
 // src/hooks/use-workspace.ts
 import { useCallback, useEffect, useState } from 'react'
-import type { Workspace, WorkspaceFolder } from '../types'
+import type { ChangedFile, Workspace, WorkspaceFolder } from '../types'
+import { errorMessage } from '../lib/utils'
 
 export function useWorkspace() {
   const [workspaces, setWorkspaces] = useState<Workspace[]>([])
@@ -18,7 +19,7 @@ export function useWorkspace() {
       setWorkspaces(result)
     } catch (err) {
       console.error('Error fetching workspaces:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
     } finally {
       setIsLoading(false)
     }
@@ -33,7 +34,7 @@ export function useWorkspace() {
       return result
     } catch (err) {
       console.error('Error getting workspace:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
       return null
     } finally {
       setIsLoading(false)
@@ -50,7 +51,24 @@ export function useWorkspace() {
       return result
     } catch (err) {
       console.error('Error creating workspace:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
+      return null
+    } finally {
+      setIsLoading(false)
+    }
+  }
+
+  const createWorkspaceFromPath = async (path: string): Promise<Workspace | null> => {
+    setIsLoading(true)
+    setError(null)
+
+    try {
+      const result = await invoke<Workspace>('create_workspace_from_path', { path })
+      await fetchWorkspaces() // Refresh the list
+      return result
+    } catch (err) {
+      console.error('Error creating workspace from path:', err)
+      setError(errorMessage(err))
       return null
     } finally {
       setIsLoading(false)
@@ -73,7 +91,57 @@ export function useWorkspace() {
       return result
     } catch (err) {
       console.error('Error updating workspace:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
+      return null
+    } finally {
+      setIsLoading(false)
+    }
+  }
+
+  // Fence language for one file in generated prompts; null removes the override
+  const setLanguageOverride = async (
+    workspaceId: string,
+    path: string,
+    language: string | null
+  ): Promise<Workspace | null> => {
+    setError(null)
+
+    try {
+      const result = await invoke<Workspace>('set_language_override', {
+        workspace_id: workspaceId,
+        path,
+        language,
+      })
+
+      if (currentWorkspace?.id === workspaceId) {
+        setCurrentWorkspace(result)
+      }
+
+      return result
+    } catch (err) {
+      console.error('Error setting language override:', err)
+      setError(errorMessage(err))
+      return null
+    }
+  }
+
+  // Clears pins and usage markers, keeping the name and folders
+  const resetWorkspaceState = async (id: string): Promise<Workspace | null> => {
+    setIsLoading(true)
+    setError(null)
+
+    try {
+      const result = await invoke<Workspace>('reset_workspace_state', { id })
+      await fetchWorkspaces() // Refresh the list
+
+      if (currentWorkspace?.id === id) {
+        setCurrentWorkspace(result)
+      }
+
+      return result
+    } catch (err) {
+      console.error('Error resetting workspace state:', err)
+      setError(errorMessage(err))
       return null
     } finally {
       setIsLoading(false)
@@ -96,7 +164,7 @@ export function useWorkspace() {
       return true
     } catch (err) {
       console.error('Error deleting workspace:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
       return false
     } finally {
       setIsLoading(false)
@@ -131,7 +199,7 @@ export function useWorkspace() {
       return result
     } catch (err) {
       console.error('Error adding folder to workspace:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
       return null
     } finally {
       setIsLoading(false)
@@ -159,13 +227,65 @@ export function useWorkspace() {
       return true
     } catch (err) {
       console.error('Error removing folder from workspace:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
       return false
     } finally {
       setIsLoading(false)
     }
   }
 
+  const moveFolderBetweenWorkspaces = async (
+    sourceWorkspaceId: string,
+    targetWorkspaceId: string,
+    folderId: string
+  ): Promise<WorkspaceFolder | null> => {
+    setIsLoading(true)
+    setError(null)
+
+    try {
+      const folder = await invoke<WorkspaceFolder>('move_folder_between_workspaces', {
+        source_workspace_id: sourceWorkspaceId,
+        target_workspace_id: targetWorkspaceId,
+        folder_id: folderId,
+      })
+
+      if (currentWorkspace?.id === sourceWorkspaceId || currentWorkspace?.id === targetWorkspaceId) {
+        const updated = await getWorkspace(currentWorkspace.id)
+        if (updated) setCurrentWorkspace(updated)
+      }
+
+      return folder
+    } catch (err) {
+      console.error('Error moving folder between workspaces:', err)
+      setError(errorMessage(err))
+      return null
+    } finally {
+      setIsLoading(false)
+    }
+  }
+
+  // Sorts folders by name; autoSort keeps them sorted as folders are added or renamed
+  const sortWorkspaceFolders = async (workspaceId: string, autoSort?: boolean): Promise<Workspace | null> => {
+    setError(null)
+
+    try {
+      const result = await invoke<Workspace>('sort_workspace_folders', {
+        workspace_id: workspaceId,
+        auto_sort: autoSort,
+      })
+
+      if (currentWorkspace?.id === workspaceId) {
+        setCurrentWorkspace(result)
+      }
+
+      return result
+    } catch (err) {
+      console.error('Error sorting workspace folders:', err)
+      setError(errorMessage(err))
+      return null
+    }
+  }
+
   const updateFolder = async (
     workspaceId: string,
     folderId: string,
@@ -189,7 +309,7 @@ export function useWorkspace() {
       return result
     } catch (err) {
       console.error('Error updating folder:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
       return null
     } finally {
       setIsLoading(false)
@@ -210,13 +330,32 @@ export function useWorkspace() {
       })
     } catch (err) {
       console.error('Error getting all files in workspace:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
       return []
     } finally {
       setIsLoading(false)
     }
   }
 
+  // Files modified after `since` (seconds since the Unix epoch), most recent first
+  const filesChangedSince = async (
+    workspaceId: string,
+    since: number,
+    useGitIgnore = true
+  ): Promise<ChangedFile[]> => {
+    try {
+      return await invoke<ChangedFile[]>('files_changed_since', {
+        workspace_id: workspaceId,
+        since,
+        use_git_ignore: useGitIgnore,
+      })
+    } catch (err) {
+      console.error('Error listing changed files:', err)
+      setError(errorMessage(err))
+      return []
+    }
+  }
+
   // Load workspaces on mount
   useEffect(() => {
     fetchWorkspaces()
@@ -231,11 +370,17 @@ export function useWorkspace() {
     fetchWorkspaces,
     getWorkspace,
     createWorkspace,
+    createWorkspaceFromPath,
     updateWorkspace,
+    resetWorkspaceState,
+    setLanguageOverride,
     deleteWorkspace,
     addFolderToWorkspace,
     removeFolderFromWorkspace,
+    moveFolderBetweenWorkspaces,
+    sortWorkspaceFolders,
     updateFolder,
     getAllFilesInWorkspace,
+    filesChangedSince,
   }
 }
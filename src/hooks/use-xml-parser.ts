@@ -1,6 +1,17 @@
 import { invoke } from '@tauri-apps/api/core'
 import { useState } from 'react'
-import type { ChangeResult, FileChange } from '../types'
+import type {
+  ApplyOptions,
+  ApplySummary,
+  ChangePreview,
+  ChangeSetIntegrity,
+  ExportedUndoArchive,
+  FileChange,
+  GeneratedPrompt,
+  ImportedUndoArchive,
+  SelfTestReport,
+} from '../types'
+import { errorMessage } from '../lib/utils'
 
 export function useXmlParser() {
   const [isLoading, setIsLoading] = useState(false)
@@ -11,16 +22,28 @@ export function useXmlParser() {
     setError(null)
 
     try {
-      return await invoke<string>('generate_xml_prompt', { files, prompt })
+      const result = await invoke<GeneratedPrompt>('generate_xml_prompt', { files, prompt })
+      return result.content
     } catch (err) {
       console.error('Error generating XML prompt:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
       throw err
     } finally {
       setIsLoading(false)
     }
   }
 
+  // The file map the XML prompt would start with, without reading file contents
+  const previewFileMap = async (files: string[]): Promise<string> => {
+    try {
+      return await invoke<string>('preview_file_map', { files })
+    } catch (err) {
+      console.error('Error previewing file map:', err)
+      setError(errorMessage(err))
+      return ''
+    }
+  }
+
   const generateXmlPromptForWorkspace = async (
     workspaceId: string,
     prompt: string,
@@ -30,14 +53,47 @@ export function useXmlParser() {
     setError(null)
 
     try {
-      return await invoke<string>('generate_xml_prompt_for_workspace', {
+      const result = await invoke<GeneratedPrompt>('generate_xml_prompt_for_workspace', {
         workspace_id: workspaceId,
         prompt,
         use_git_ignore: useGitIgnore,
       })
+      return result.content
     } catch (err) {
       console.error('Error generating XML prompt for workspace:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
+      throw err
+    } finally {
+      setIsLoading(false)
+    }
+  }
+
+  // Same prompt as generateXmlPrompt, asking for a JSON change list instead of XML
+  const generateJsonPrompt = async (files: string[], prompt: string): Promise<string> => {
+    setIsLoading(true)
+    setError(null)
+
+    try {
+      const result = await invoke<GeneratedPrompt>('generate_json_prompt', { files, prompt })
+      return result.content
+    } catch (err) {
+      console.error('Error generating JSON prompt:', err)
+      setError(errorMessage(err))
+      throw err
+    } finally {
+      setIsLoading(false)
+    }
+  }
+
+  const parseJsonResponse = async (json: string): Promise<FileChange[]> => {
+    setIsLoading(true)
+    setError(null)
+
+    try {
+      return await invoke<FileChange[]>('parse_json_response', { json })
+    } catch (err) {
+      console.error('Error parsing JSON response:', err)
+      setError(errorMessage(err))
       throw err
     } finally {
       setIsLoading(false)
@@ -52,22 +108,44 @@ export function useXmlParser() {
       return await invoke<FileChange[]>('parse_xml_response', { xml })
     } catch (err) {
       console.error('Error parsing XML response:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
       throw err
     } finally {
       setIsLoading(false)
     }
   }
 
-  const applyXmlChanges = async (changes: FileChange[]): Promise<ChangeResult[]> => {
+  // Where each change of a modify would land, with surrounding lines
+  const previewModifyContext = async (change: FileChange, contextLines?: number): Promise<ChangePreview[]> => {
+    try {
+      return await invoke<ChangePreview[]>('preview_modify_context', { change, context_lines: contextLines })
+    } catch (err) {
+      console.error('Error previewing change context:', err)
+      setError(errorMessage(err))
+      return []
+    }
+  }
+
+  // Unified diff of a change, computed with the options it will be applied with
+  const diffFileChange = async (change: FileChange, options?: ApplyOptions, contextLines?: number): Promise<string | null> => {
+    try {
+      return await invoke<string>('diff_file_change', { change, options, context_lines: contextLines })
+    } catch (err) {
+      console.error('Error diffing change:', err)
+      setError(errorMessage(err))
+      return null
+    }
+  }
+
+  const applyXmlChanges = async (changes: FileChange[], options?: ApplyOptions): Promise<ApplySummary> => {
     setIsLoading(true)
     setError(null)
 
     try {
-      return await invoke<ChangeResult[]>('apply_xml_changes', { changes })
+      return await invoke<ApplySummary>('apply_xml_changes', { changes, options })
     } catch (err) {
       console.error('Error applying XML changes:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
       throw err
     } finally {
       setIsLoading(false)
@@ -82,7 +160,7 @@ export function useXmlParser() {
       return await invoke<string | null>('undo_last_change')
     } catch (err) {
       console.error('Error undoing last change:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
       throw err
     } finally {
       setIsLoading(false)
@@ -97,21 +175,72 @@ export function useXmlParser() {
       return await invoke<boolean>('undo_file_change', { file_path: filePath })
     } catch (err) {
       console.error('Error undoing file change:', err)
-      setError(err instanceof Error ? err.message : String(err))
+      setError(errorMessage(err))
       throw err
     } finally {
       setIsLoading(false)
     }
   }
 
+  // Per change set, whether its backups are still intact
+  const verifyUndoIntegrity = async (): Promise<ChangeSetIntegrity[]> => {
+    try {
+      return await invoke<ChangeSetIntegrity[]>('verify_undo_integrity')
+    } catch (err) {
+      console.error('Error verifying undo history:', err)
+      setError(errorMessage(err))
+      return []
+    }
+  }
+
+  // Checks that writing, backing up and undoing work on this machine
+  const runSelfTest = async (): Promise<SelfTestReport | null> => {
+    try {
+      return await invoke<SelfTestReport>('run_self_test')
+    } catch (err) {
+      console.error('Error running self-test:', err)
+      setError(errorMessage(err))
+      return null
+    }
+  }
+
+  const exportUndoArchive = async (outputPath: string): Promise<ExportedUndoArchive | null> => {
+    try {
+      return await invoke<ExportedUndoArchive>('export_undo_archive', { output_path: outputPath })
+    } catch (err) {
+      console.error('Error exporting undo history:', err)
+      setError(errorMessage(err))
+      return null
+    }
+  }
+
+  const importUndoArchive = async (archivePath: string): Promise<ImportedUndoArchive | null> => {
+    try {
+      return await invoke<ImportedUndoArchive>('import_undo_archive', { archive_path: archivePath })
+    } catch (err) {
+      console.error('Error importing undo history:', err)
+      setError(errorMessage(err))
+      return null
+    }
+  }
+
   return {
     isLoading,
     error,
     generateXmlPrompt,
+    previewFileMap,
     generateXmlPromptForWorkspace,
+    generateJsonPrompt,
     parseXmlResponse,
+    parseJsonResponse,
+    previewModifyContext,
+    diffFileChange,
     applyXmlChanges,
     undoLastChange,
     undoFileChange,
+    verifyUndoIntegrity,
+    runSelfTest,
+    exportUndoArchive,
+    importUndoArchive,
   }
 }
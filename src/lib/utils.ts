@@ -1,6 +1,17 @@
 import { type ClassValue, clsx } from 'clsx'
 import { twMerge } from 'tailwind-merge'
+import type { CommandError } from '../types'
 
 export function cn(...inputs: ClassValue[]) {
   return twMerge(clsx(inputs))
 }
+
+export function isCommandError(err: unknown): err is CommandError {
+  return typeof err === 'object' && err !== null && 'kind' in err && 'message' in err
+}
+
+// Commands reject with a CommandError object; anything else falls back to its string form
+export function errorMessage(err: unknown): string {
+  if (isCommandError(err)) return err.message
+  return err instanceof Error ? err.message : String(err)
+}
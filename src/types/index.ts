@@ -8,11 +8,100 @@ export interface FileItem {
   size?: number
 }
 
+// Backend limits, persisted in the app data dir
+export interface Config {
+  max_concurrency: number
+  mmap_threshold_bytes: number
+  preview_max_bytes: number
+}
+
+export interface ChangedFile {
+  path: string
+  // Seconds since the Unix epoch
+  modified: number
+}
+
+export interface FileMetadata {
+  path: string
+  size: number
+  modified: number | null
+  is_binary: boolean
+  is_symlink: boolean
+  line_count: number | null
+  line_endings: LineEndings | null
+  approximate: boolean
+}
+
+export interface LineEndings {
+  style: 'lf' | 'crlf' | 'mixed' | null
+  crlf_count: number
+  lf_count: number
+}
+
+export type ProjectType = 'Rust' | 'Node' | 'Python' | 'Go'
+
+export interface DetectedProject {
+  project_type: ProjectType
+  marker: string
+  suggested_excludes: string[]
+}
+
+export type LongLineMode = 'Truncate' | 'Wrap'
+
+// Display-only content; never use it for applying edits when `altered` is set
+export interface DisplayContent {
+  content: string
+  altered: boolean
+  long_lines: number
+}
+
+export interface ResolvedFile {
+  content: string
+  resolved_path: string
+  is_symlink: boolean
+}
+
+export interface FilePreview {
+  content: string
+  size: number
+  truncated: boolean
+}
+
+export interface Base64File {
+  content: string
+  mime_type: string
+  size: number
+}
+
 export interface PromptTag {
   id: string
   name: string
 }
 
+export interface Snippet {
+  text: string
+  // Character offsets of the match within `text`
+  match_start: number
+  match_end: number
+}
+
+export interface PromptSearchResult {
+  prompt: Prompt
+  title_match: boolean
+  match_count: number
+  snippets: Snippet[]
+}
+
+export interface TagCount {
+  tag: PromptTag
+  count: number
+}
+
+export interface PromptVersion {
+  content: string
+  timestamp: number
+}
+
 export interface Prompt {
   id: string
   title: string
@@ -20,6 +109,10 @@ export interface Prompt {
   tags: PromptTag[]
   created_at: number
   updated_at: number
+  workspace_id?: string | null
+  history: PromptVersion[]
+  usage_count: number
+  last_used_at?: number | null
 }
 
 export interface Workspace {
@@ -28,27 +121,190 @@ export interface Workspace {
   folders: WorkspaceFolder[]
   created_at: number
   updated_at: number
+  last_opened_at?: number | null
+  pinned_paths?: string[]
+  pruned_pins?: number
+  language_overrides?: Record<string, string>
+  auto_sort_folders?: boolean
 }
 
 export interface WorkspaceFolder {
   id: string
   path: string
   name: string
+  use_git_ignore?: boolean | null
+  encoding?: string | null
 }
 
 export interface FileChange {
   path: string
-  action: 'Create' | 'Rewrite' | 'Modify' | 'Delete'
+  action: 'Create' | 'Rewrite' | 'Modify' | 'Delete' | 'RegexReplace'
   changes: Array<{
     description: string
     search?: string
     content: string
+    start_line?: number | null
+    end_line?: number | null
   }>
 }
 
 export interface ChangeResult {
   path: string
-  action: 'Create' | 'Rewrite' | 'Modify' | 'Delete'
+  action: 'Create' | 'Rewrite' | 'Modify' | 'Delete' | 'RegexReplace'
   success: boolean
   message?: string
+  replacements?: number | null
+  changes?: ChangeStatus[]
+  has_conflicts?: boolean
+}
+
+export interface MatchWindow {
+  match_start_line: number
+  match_end_line: number
+  window_start_line: number
+  current: string
+  proposed: string
+}
+
+export interface ChangePreview {
+  index: number
+  description: string
+  found: boolean
+  windows: MatchWindow[]
+}
+
+// How changes are applied; omitted fields use the backend defaults
+export interface ApplyOptions {
+  eol?: 'Auto' | 'Lf' | 'Crlf'
+  backup_location?: 'Global' | 'Project'
+  allow_partial?: boolean
+  three_way_merge?: boolean
+  min_search_chars?: number
+  allow_short_search?: boolean
+  trim_trailing_whitespace?: boolean
+  ensure_final_newline?: boolean
+}
+
+export interface ApplySummary {
+  results: ChangeResult[]
+  succeeded: number
+  failed: number
+  created: number
+  modified: number
+  rewritten: number
+  deleted: number
+}
+
+export interface BackupProblem {
+  original_path: string
+  backup_path: string
+  reason: string
+}
+
+export interface ChangeSetIntegrity {
+  id: string
+  description: string
+  timestamp: number
+  restorable: boolean
+  problems: BackupProblem[]
+}
+
+export interface ExportedUndoArchive {
+  path: string
+  change_sets: number
+  backups: number
+  missing: string[]
+}
+
+export interface ImportedUndoArchive {
+  imported: number
+  skipped: number
+}
+
+export interface SelfTestStep {
+  name: string
+  ok: boolean
+  error: string | null
+}
+
+export interface SelfTestReport {
+  passed: boolean
+  steps: SelfTestStep[]
+}
+
+export interface ChangeStatus {
+  index: number
+  description: string
+  success: boolean
+  message?: string | null
+}
+
+export interface UnresolvedImport {
+  file: string
+  import: string
+}
+
+export interface ExpandedSelection {
+  paths: string[]
+  added: string[]
+  unresolved: UnresolvedImport[]
+}
+
+export interface CostEstimate {
+  model: string
+  tokens: number
+  known_model: boolean
+  input_price_per_million: number | null
+  estimated_cost: number | null
+}
+
+export type PromptMode = 'Copy' | 'Xml'
+
+export interface TokenProgress {
+  path: string
+  tokens: number
+  running_total: number
+  files_done: number
+  files_total: number
+}
+
+// A data file that failed to parse and was moved aside so the app could start empty
+export interface RecoveredFile {
+  path: string
+  moved_to: string
+  reason: string
+}
+
+// Payload of the `scan-skipped` event: entries below `root` left out of its tree
+export interface ScanSkipped {
+  root: string
+  paths: string[]
+}
+
+export interface TokenEstimate {
+  total: number
+  file_count: number
+  cancelled: boolean
+}
+
+export interface GeneratedFile {
+  path: string
+  bytes: number
+  line_limited_files: string[]
+}
+
+export interface GeneratedPrompt {
+  content: string
+  included_files: string[]
+  skipped_files: string[]
+  truncated_files: string[]
+  limit_reached: boolean
+  redactions: Record<string, number>
+  duplicates_removed: number
+  line_limited_files: string[]
+}
+
+export interface CommandError {
+  kind: 'NotFound' | 'PermissionDenied' | 'InvalidInput' | 'Io' | 'Parse' | 'Other'
+  message: string
 }